@@ -12,22 +12,49 @@ export type QueueState = {
   status: string;
 };
 
+export type SpeedSummary = {
+  min: number;
+  max: number;
+  average: number;
+};
+
 export type StatsState = {
   speed: number; // Bytes per second
   time: number; // Seconds,
+  history: SpeedSummary; // Over the last minute
+};
+
+export type FileProgressState = {
+  gameId: string;
+  filename: string;
+  done: number;
+  total: number;
 };
 
 export const useQueueState = () =>
   useState<QueueState>("queue", () => ({ queue: [], status: "Unknown" }));
 
 export const useStatsState = () =>
-  useState<StatsState>("stats", () => ({ speed: 0, time: 0 }));
+  useState<StatsState>("stats", () => ({
+    speed: 0,
+    time: 0,
+    history: { min: 0, max: 0, average: 0 },
+  }));
 
 listen("update_queue", (event) => {
   const queue = useQueueState();
   queue.value = event.payload as QueueState;
 });
 
+export const useFileProgressState = () =>
+  useState<{ [key: string]: FileProgressState }>("file_progress", () => ({}));
+
+listen("download_file_progress", (event) => {
+  const fileProgress = useFileProgressState();
+  const payload = event.payload as FileProgressState;
+  fileProgress.value[payload.gameId] = payload;
+});
+
 listen("update_stats", (event) => {
   const stats = useStatsState();
   stats.value = event.payload as StatsState;
@@ -1,6 +1,13 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import type { Game, GameStatus, GameStatusEnum, GameVersion } from "~/types";
+import type {
+  Game,
+  GameStatus,
+  GameStatusEnum,
+  GameVersion,
+  LaunchConfig,
+  LauncherKind,
+} from "~/types";
 
 const gameRegistry: { [key: string]: { game: Game; version?: GameVersion } } =
   {};
@@ -69,6 +76,33 @@ export const useGame = async (gameId: string) => {
   return { ...game, status };
 };
 
+export type FrontendLaunchConfig = {
+  baseCommand: string;
+  baseArgs: Array<string>;
+  config: LaunchConfig;
+  rawTemplate: string;
+  customTemplate: boolean;
+};
+
 export type FrontendGameConfiguration = {
   launchString: string;
+  launchConfig: LaunchConfig;
+  preferredLauncher?: LauncherKind;
+  launchExecutable?: string;
 };
+
+// Environment variables are edited as one KEY=value per line
+export const formatEnvVars = (envVars: { [key: string]: string }) =>
+  Object.entries(envVars)
+    .map(([key, value]) => `${key}=${value}`)
+    .join("\n");
+
+export function parseEnvVars(text: string): { [key: string]: string } {
+  const envVars: { [key: string]: string } = {};
+  for (const line of text.split("\n")) {
+    const separator = line.indexOf("=");
+    if (separator <= 0) continue;
+    envVars[line.slice(0, separator).trim()] = line.slice(separator + 1);
+  }
+  return envVars;
+}
@@ -1,7 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { data } from "autoprefixer";
-import { AppStatus, type AppState } from "~/types";
+import { AppStatus, type AppState, type LaunchErrorEvent } from "~/types";
 
 export function setupHooks() {
   const router = useRouter();
@@ -22,6 +22,27 @@ export function setupHooks() {
     state.value = JSON.parse(await invoke("fetch_state"));
   });
 
+  listen("auth/needs_reauth", (event) => {
+    const { message } = event.payload as { reason: string; message: string };
+    createModal(
+      ModalType.Confirmation,
+      {
+        title: "You need to sign in again",
+        description: `Drop couldn't authenticate with your server: "${message}". Do you want to sign in now?`,
+        buttonText: "Sign in",
+      },
+      (e, c) => {
+        if (e == "confirm") router.push("/auth/signedout");
+        c();
+      }
+    );
+  });
+
+  listen("app_status_changed", (event) => {
+    const { new: status } = event.payload as { old: AppStatus; new: AppStatus };
+    if (state.value) state.value.status = status;
+  });
+
   listen("download_error", (event) => {
     createModal(
       ModalType.Notification,
@@ -36,6 +57,54 @@ export function setupHooks() {
     );
   });
 
+  listen("uninstall_error", (event) => {
+    createModal(
+      ModalType.Notification,
+      {
+        title: "Drop couldn't finish uninstalling",
+        description: `Some files were left behind, so the game has been marked as partially installed: "${(
+          event.payload as unknown as string
+        ).toString()}"`,
+        buttonText: "Close",
+      },
+      (e, c) => c()
+    );
+  });
+
+  listen("download_warning", (event) => {
+    createModal(
+      ModalType.Notification,
+      {
+        title: "Download progress was reset",
+        description: (event.payload as unknown as string).toString(),
+        buttonText: "Close",
+      },
+      (e, c) => c()
+    );
+  });
+
+  // From drop://install links, and store links when native store pages are on
+  listen("open_game", (event) => {
+    const { gameId, install } = event.payload as {
+      gameId: string;
+      install?: boolean;
+    };
+    router.push(`/library/${gameId}${install ? "?install=true" : ""}`);
+  });
+
+  listen("launch_error", (event) => {
+    const { message, stderrTail } = event.payload as LaunchErrorEvent;
+    createModal(
+      ModalType.Notification,
+      {
+        title: "Couldn't launch game",
+        description: [message, ...stderrTail].join("\n"),
+        buttonText: "Close",
+      },
+      (e, c) => c()
+    );
+  });
+
   // This is for errors that (we think) aren't our fault
   listen("launch_external_error", (event) => {
     createModal(
@@ -1,5 +1,7 @@
 import { convertFileSrc } from "@tauri-apps/api/core";
 
-export const useObject = async (id: string) => {
-  return convertFileSrc(id, "object");
+// `owner` lets a page cancel its own requests with `cancel_object_fetch`
+export const useObject = async (id: string, owner?: string) => {
+  const url = convertFileSrc(id, "object");
+  return owner ? `${url}?owner=${encodeURIComponent(owner)}` : url;
 };
@@ -23,6 +23,7 @@ export type User = {
 export type AppState = {
   status: AppStatus;
   user?: User;
+  safeMode: boolean;
 };
 
 export type Game = {
@@ -46,6 +47,27 @@ export type Collection = {
 
 export type GameVersion = {
   launchCommandTemplate: string;
+  preferredLauncher?: LauncherKind;
+  executableOverride?: string;
+};
+
+export type LaunchConfig = {
+  wrapper?: string;
+  args: Array<string>;
+  env: { [key: string]: string };
+  workingDir?: string;
+};
+
+export enum LauncherKind {
+  Native = "Native",
+  UMU = "UMU",
+  AsahiMuvm = "AsahiMuvm",
+  Gamescope = "Gamescope",
+}
+
+export type GamePlaytime = {
+  playtimeSeconds: number;
+  lastPlayed?: number;
 };
 
 export enum AppStatus {
@@ -93,4 +115,162 @@ export type Settings = {
   autostart: boolean;
   maxDownloadThreads: number;
   forceOffline: boolean;
+  speedWindowSecs: number;
+  extraHeaders: { [key: string]: string };
+  ioBufferSize: number;
+  pauseOnTray: boolean;
+  defaultVersionPolicy: DefaultVersionPolicy;
+  maxConcurrentDownloads: number;
+  downloadRetryAttempts: number;
+  downloadRetryDelayMs: number;
+  notifyOnDownloadComplete: boolean;
+  bucketTargetSize: number;
+  maxFilesPerBucket: number;
+  verifyOnResume: boolean;
+  defaultLaunchConfig: LaunchConfig;
+  gamescopeEnabled: boolean;
+  gamescopeWidth: number;
+  gamescopeHeight: number;
+  discordRichPresence: boolean;
+  maxGameLogs: number;
+  saveExcludePatterns: string[];
+  compressSaveBackups: boolean;
+  saveCompressionLevel: number;
+  objectCacheTtlHours: number;
+  maxCacheBytes: number;
+  proxyUrl?: string;
+  noProxy: string[];
+  monthlyQuotaBytes: number;
+  quotaResetDay: number;
+  nativeStorePages: boolean;
+  healthcheckIntervalSecs: number;
+};
+
+export type QuotaUsage = {
+  used: number;
+  quota: number;
+};
+
+export enum DefaultVersionPolicy {
+  Latest = "Latest",
+  LatestStable = "LatestStable",
+}
+
+export type LaunchConfig = {
+  wrapper?: string;
+  args: string[];
+  env: { [key: string]: string };
+  workingDir?: string;
+};
+
+export type FrontendLaunchConfig = {
+  baseCommand: string;
+  baseArgs: string[];
+  config: LaunchConfig;
+  rawTemplate: string;
+  customTemplate: boolean;
+};
+
+export type DownloadFeasibility = {
+  required: number;
+  available: number;
+  sufficient: boolean;
+};
+
+export type GameComponent = {
+  name: string;
+  size: number;
+  excluded: boolean;
+};
+
+export type DownloadHistoryEntry = {
+  gameId: string;
+  version: string;
+  bytesDownloaded: number;
+  durationSecs: number;
+  completedAt: number;
+};
+
+export type MonthlyDownloadUsage = {
+  month: string;
+  bytesDownloaded: number;
+  durationSecs: number;
+  downloads: number;
+};
+
+export type LaunchErrorEvent = {
+  gameId: string;
+  message: string;
+  exitCode?: number;
+  stderrTail: string[];
+};
+
+export type SavePath = {
+  path: string;
+  platform: "Windows" | "Linux" | "macOS";
+};
+
+export type CloudSaveConfig = {
+  paths: SavePath[];
+  syncOnLaunch: boolean;
+  syncOnExit: boolean;
+  exclude: string[];
+};
+
+export type SaveSyncProgressEvent = {
+  transferred: number;
+  total: number;
+  speed: number;
+  time: number;
+};
+
+export type SaveConflict = {
+  localModified: number;
+  remoteModified: number;
+};
+
+export enum ConflictResolution {
+  KeepLocal = "KeepLocal",
+  KeepRemote = "KeepRemote",
+  KeepBoth = "KeepBoth",
+}
+
+export type SnapshotInfo = {
+  id: string;
+  createdAt: number;
+  size: number;
+  uncompressedSize: number;
+  compressed: boolean;
+};
+
+export type SavePathPreview = {
+  path: string;
+  platform: SavePath["platform"];
+  resolved?: string;
+  exists: boolean;
+  error?: string;
+};
+
+export type UnmappedSave = {
+  path: string;
+  platform?: SavePath["platform"];
+  reason: string;
+};
+
+export type CertificateInfo = {
+  name: string;
+  certificates: number;
+  loaded: boolean;
+};
+
+export type CertificateList = {
+  certificates: CertificateInfo[];
+  restartRequired: boolean;
+};
+
+export type ScanSummary = {
+  restored: string[];
+  imported: string[];
+  skipped: string[];
+  missing: string[];
 };
@@ -15,9 +15,16 @@ pub static UMU_LAUNCHER_EXECUTABLE: LazyLock<Option<PathBuf>> = LazyLock::new(||
     x
 });
 
+pub static GAMESCOPE_EXECUTABLE: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let x = get_gamescope_executable();
+    info!("{:?}", &x);
+    x
+});
+
 #[derive(Clone)]
 pub struct CompatInfo {
     pub umu_installed: bool,
+    pub gamescope_installed: bool,
 }
 
 fn create_new_compat_info() -> Option<CompatInfo> {
@@ -25,8 +32,10 @@ fn create_new_compat_info() -> Option<CompatInfo> {
     return None;
 
     let has_umu_installed = UMU_LAUNCHER_EXECUTABLE.is_some();
+    let has_gamescope_installed = GAMESCOPE_EXECUTABLE.is_some();
     Some(CompatInfo {
         umu_installed: has_umu_installed,
+        gamescope_installed: has_gamescope_installed,
     })
 }
 
@@ -46,6 +55,21 @@ fn get_umu_executable() -> Option<PathBuf> {
     }
     None
 }
+const GAMESCOPE_BASE_EXECUTABLE: &str = "gamescope";
+
+fn get_gamescope_executable() -> Option<PathBuf> {
+    // Without any arguments gamescope starts a nested session, so ask for its
+    // help instead
+    let has_gamescope_installed = Command::new(GAMESCOPE_BASE_EXECUTABLE)
+        .arg("--help")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    has_gamescope_installed
+        .is_ok()
+        .then(|| PathBuf::from(GAMESCOPE_BASE_EXECUTABLE))
+}
+
 fn check_executable_exists<P: AsRef<OsStr>>(exec: P) -> bool {
     let has_umu_installed = Command::new(exec).stdout(Stdio::null()).output();
     has_umu_installed.is_ok()
@@ -1,8 +1,13 @@
-use std::{collections::HashMap, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-#[cfg(target_os = "linux")]
 use database::platform::Platform;
-use database::{GameVersion, db::DATA_ROOT_DIR};
+use database::{GameVersion, borrow_db_checked, db::DATA_ROOT_DIR, prefix::game_prefix_dir};
+use glob::{MatchOptions, Pattern};
 use log::warn;
 
 use crate::error::BackupError;
@@ -23,14 +28,7 @@ impl Default for BackupManager<'_> {
 impl BackupManager<'_> {
     pub fn new() -> Self {
         BackupManager {
-            #[cfg(target_os = "windows")]
-            current_platform: Platform::Windows,
-
-            #[cfg(target_os = "macos")]
-            current_platform: Platform::macOS,
-
-            #[cfg(target_os = "linux")]
-            current_platform: Platform::Linux,
+            current_platform: Platform::HOST,
 
             sources: HashMap::from([
                 // Current platform to target platform
@@ -42,6 +40,10 @@ impl BackupManager<'_> {
                     (Platform::Linux, Platform::Linux),
                     &LinuxBackupManager {} as &(dyn BackupHandler + Sync + Send),
                 ),
+                (
+                    (Platform::Linux, Platform::Windows),
+                    &ProtonBackupManager {} as &(dyn BackupHandler + Sync + Send),
+                ),
                 (
                     (Platform::macOS, Platform::macOS),
                     &MacBackupManager {} as &(dyn BackupHandler + Sync + Send),
@@ -230,5 +232,139 @@ impl BackupHandler for WindowsBackupManager {
         CommonPath::Public.get().ok_or(BackupError::NotFound)
     }
 }
+/// The user Proton runs Windows games as, inside every prefix
+const PROTON_USER: &str = "steamuser";
+
+/// Windows saves of games run through Proton, which live in the game's Wine
+/// prefix rather than the Linux home directory
+pub struct ProtonBackupManager {}
+impl ProtonBackupManager {
+    fn drive_c(game: &GameVersion) -> PathBuf {
+        game_prefix_dir(&borrow_db_checked(), &game.game_id).join("drive_c")
+    }
+    fn user_dir(game: &GameVersion) -> PathBuf {
+        Self::drive_c(game).join("users").join(PROTON_USER)
+    }
+}
+impl BackupHandler for ProtonBackupManager {
+    fn home_translate(&self, _path: &PathBuf, game: &GameVersion) -> Result<PathBuf, BackupError> {
+        Ok(Self::user_dir(game))
+    }
+    fn os_user_name_translate(
+        &self,
+        _path: &PathBuf,
+        _game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(PathBuf::from(PROTON_USER))
+    }
+    fn win_app_data_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::user_dir(game).join("AppData/Roaming"))
+    }
+    fn win_local_app_data_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::user_dir(game).join("AppData/Local"))
+    }
+    fn win_local_app_data_low_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::user_dir(game).join("AppData/LocalLow"))
+    }
+    fn win_documents_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::user_dir(game).join("Documents"))
+    }
+    fn win_public_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::drive_c(game).join("users/Public"))
+    }
+    fn win_program_data_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::drive_c(game).join("ProgramData"))
+    }
+    fn win_dir_translate(
+        &self,
+        _path: &PathBuf,
+        game: &GameVersion,
+    ) -> Result<PathBuf, BackupError> {
+        Ok(Self::drive_c(game).join("windows"))
+    }
+}
 pub struct MacBackupManager {}
 impl BackupHandler for MacBackupManager {}
+
+/// Glob patterns for files to leave out of a backup, matched against paths
+/// relative to the save path they're under
+pub struct ExcludeFilter {
+    patterns: Vec<Pattern>,
+    options: MatchOptions,
+}
+
+impl ExcludeFilter {
+    /// Matches case-insensitively for platforms whose filesystems are
+    pub fn new(patterns: &[String], platform: Platform) -> Result<Self, BackupError> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .map_err(|e| BackupError::InvalidPattern(format!("{pattern}: {e}")))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            patterns,
+            options: MatchOptions {
+                case_sensitive: platform.is_case_sensitive(),
+                require_literal_separator: true,
+                require_literal_leading_dot: false,
+            },
+        })
+    }
+
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_path_with(relative, self.options))
+    }
+}
+
+/// Every file at or under `root` that isn't excluded, as its path relative
+/// to `root` and its full path. A file `root` is returned with an empty
+/// relative path.
+pub fn walk_save_path(root: &Path, filter: &ExcludeFilter) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    if fs::metadata(root)?.is_file() {
+        files.push((PathBuf::new(), root.to_path_buf()));
+        return Ok(files);
+    }
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(root.join(&relative))? {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(relative);
+            } else if file_type.is_file() && !filter.is_excluded(&relative) {
+                files.push((relative, entry.path()));
+            }
+        }
+    }
+    Ok(files)
+}
@@ -1,8 +1,10 @@
-use std::fmt::Display;
+use std::{fmt::Display, io};
 
 use serde_with::SerializeDisplay;
 
-#[derive(Debug, SerializeDisplay, Clone, Copy)]
+use crate::metadata::SaveConflict;
+
+#[derive(Debug, SerializeDisplay, Clone)]
 
 pub enum BackupError {
     InvalidSystem,
@@ -10,6 +12,26 @@ pub enum BackupError {
     NotFound,
 
     ParseError,
+
+    IOError(String),
+
+    SnapshotNotFound(String),
+
+    NotInstalled(String),
+
+    NoSavePaths(String),
+
+    Conflict(SaveConflict),
+
+    UnknownPlaceholder(String),
+
+    InvalidPattern(String),
+}
+
+impl From<io::Error> for BackupError {
+    fn from(value: io::Error) -> Self {
+        BackupError::IOError(value.to_string())
+    }
 }
 
 impl Display for BackupError {
@@ -20,6 +42,24 @@ impl Display for BackupError {
             BackupError::NotFound => "Could not generate or find path",
 
             BackupError::ParseError => "Failed to parse path",
+
+            BackupError::IOError(error) => &format!("Save backup failed: {error}"),
+
+            BackupError::SnapshotNotFound(id) => &format!("Could not find save backup {id}"),
+
+            BackupError::NotInstalled(game_id) => &format!("{game_id} isn't installed"),
+
+            BackupError::NoSavePaths(game_id) => &format!("{game_id} has no save locations set up"),
+
+            BackupError::UnknownPlaceholder(placeholder) => {
+                &format!("Unknown placeholder {placeholder} in save path")
+            }
+
+            BackupError::InvalidPattern(error) => &format!("Invalid exclude pattern {error}"),
+
+            BackupError::Conflict(_) => {
+                "Both the local saves and the backup have changed since the last sync"
+            }
         };
 
         write!(f, "{}", s)
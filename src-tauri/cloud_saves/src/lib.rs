@@ -5,4 +5,9 @@ pub mod metadata;
 pub mod normalise;
 pub mod path;
 pub mod placeholder;
+pub mod progress;
 pub mod resolver;
+pub mod snapshots;
+
+#[cfg(test)]
+mod test;
@@ -1,6 +1,9 @@
-use database::GameVersion;
+use std::path::PathBuf;
 
-use super::conditions::Condition;
+use database::{GameVersion, SavePath, platform::Platform};
+use serde::{Deserialize, Serialize};
+
+use super::{conditions::Condition, error::BackupError};
 
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct CloudSaveMetadata {
@@ -9,6 +12,25 @@ pub struct CloudSaveMetadata {
     pub save_id: String,
 }
 
+impl CloudSaveMetadata {
+    pub fn new(game_version: GameVersion, paths: &[SavePath], save_id: String) -> Self {
+        Self {
+            files: paths
+                .iter()
+                .map(|path| GameFile {
+                    path: path.path.clone(),
+                    id: None,
+                    data_type: DataType::File,
+                    tags: vec![Tag::Save],
+                    conditions: vec![Condition::Os(path.platform)],
+                })
+                .collect(),
+            game_version,
+            save_id,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct GameFile {
     pub path: String,
@@ -34,3 +56,74 @@ pub enum Tag {
     #[serde(other)]
     Other,
 }
+
+/// A backup of a game's saves, stored next to its archive
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub id: String,
+    // Unix timestamp, in seconds
+    pub created_at: i64,
+    // Size of the archive on disk, in bytes
+    pub size: u64,
+    // Size of the saves before compression, in bytes
+    #[serde(default)]
+    pub uncompressed_size: u64,
+    // Backups from before compression was optional were all compressed
+    #[serde(default = "default_compressed")]
+    pub compressed: bool,
+}
+
+fn default_compressed() -> bool {
+    true
+}
+
+/// What the saves looked like the last time they were backed up or restored
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub snapshot_id: String,
+    // Unix timestamp, in seconds, of the newest local save file at the time
+    pub local_modified: i64,
+}
+
+/// The local saves and the latest backup have both changed since they were
+/// last in sync, so neither can replace the other without losing progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveConflict {
+    // Unix timestamps, in seconds
+    pub local_modified: i64,
+    pub remote_modified: i64,
+}
+
+/// How the user wants a [`SaveConflict`] settled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictResolution {
+    KeepLocal,
+    KeepRemote,
+    KeepBoth,
+}
+
+/// What a configured save path resolves to on this machine, without backing
+/// anything up
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavePathPreview {
+    pub path: String,
+    pub platform: Platform,
+    pub resolved: Option<PathBuf>,
+    // Whether anything is there to back up
+    pub exists: bool,
+    // Why the path couldn't be resolved, such as an unknown placeholder
+    pub error: Option<BackupError>,
+}
+
+/// A backed up save that couldn't be put back on this machine
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmappedSave {
+    pub path: String,
+    pub platform: Option<Platform>,
+    pub reason: BackupError,
+}
@@ -0,0 +1,154 @@
+use std::{
+    io::{self, Read, Write},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use download_manager::util::rolling_progress_updates::RollingProgressWindow;
+use serde::Serialize;
+use tauri::AppHandle;
+use utils::{app_emit, lock};
+
+/// How often the transfer rate is sampled and pushed to the frontend
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+/// Number of samples averaged for the transfer rate (5 seconds)
+const ROLLING_WINDOW_SIZE: usize = 20;
+
+#[derive(Serialize, Clone)]
+pub struct SaveSyncProgressEvent {
+    pub transferred: usize,
+    pub total: usize,
+    pub speed: usize,
+    pub time: usize,
+}
+
+/// Tracks bytes moved during a cloud save upload or download and reports
+/// them as `save_sync_progress/<game_id>` events
+pub struct SaveSyncProgress {
+    // No events are sent without one
+    app_handle: Option<AppHandle>,
+    game_id: String,
+    total: AtomicUsize,
+    transferred: AtomicUsize,
+    bytes_last_update: AtomicUsize,
+    last_update_time: Mutex<Instant>,
+    rolling: RollingProgressWindow,
+}
+
+impl SaveSyncProgress {
+    pub fn new(app_handle: AppHandle, game_id: String) -> Self {
+        Self::with_handle(Some(app_handle), game_id)
+    }
+    #[cfg(test)]
+    pub(crate) fn detached(game_id: String) -> Self {
+        Self::with_handle(None, game_id)
+    }
+    fn with_handle(app_handle: Option<AppHandle>, game_id: String) -> Self {
+        Self {
+            app_handle,
+            game_id,
+            total: AtomicUsize::new(0),
+            transferred: AtomicUsize::new(0),
+            bytes_last_update: AtomicUsize::new(0),
+            last_update_time: Mutex::new(Instant::now()),
+            rolling: RollingProgressWindow::new(ROLLING_WINDOW_SIZE),
+        }
+    }
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Release);
+    }
+    pub fn add(&self, amount: usize) {
+        self.transferred.fetch_add(amount, Ordering::AcqRel);
+
+        let mut last_update_time = lock!(self.last_update_time);
+        let elapsed = last_update_time.elapsed();
+        if elapsed < UPDATE_INTERVAL {
+            return;
+        }
+        *last_update_time = Instant::now();
+        drop(last_update_time);
+
+        let transferred = self.transferred.load(Ordering::Acquire);
+        let bytes_at_last_update = self.bytes_last_update.swap(transferred, Ordering::AcqRel);
+        let bytes_since_last_update = transferred.saturating_sub(bytes_at_last_update) as f64;
+        let kilobytes_per_second = bytes_since_last_update / (elapsed.as_secs_f64() * 1000.0);
+        self.rolling.update(kilobytes_per_second as usize);
+
+        self.push_update(self.rolling.get_average());
+    }
+    /// Sends a final event so the frontend sees the transfer reach its total
+    pub fn finish(&self) {
+        self.transferred
+            .store(self.total.load(Ordering::Acquire), Ordering::Release);
+        self.push_update(0);
+    }
+    fn push_update(&self, kilobytes_per_second: usize) {
+        let Some(app_handle) = &self.app_handle else {
+            return;
+        };
+        // Tar headers and padding mean the stream can run slightly past the
+        // sum of the file sizes
+        let total = self.total.load(Ordering::Acquire);
+        let transferred = self.transferred.load(Ordering::Acquire).min(total);
+        let time_remaining =
+            (total.saturating_sub(transferred) / 1000) / kilobytes_per_second.max(1);
+
+        app_emit!(
+            app_handle,
+            &format!("save_sync_progress/{}", self.game_id),
+            SaveSyncProgressEvent {
+                transferred,
+                total,
+                speed: kilobytes_per_second,
+                time: time_remaining,
+            }
+        );
+    }
+}
+
+pub struct ProgressWriter<'a, W: Write> {
+    inner: W,
+    progress: &'a SaveSyncProgress,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    pub fn new(inner: W, progress: &'a SaveSyncProgress) -> Self {
+        Self { inner, progress }
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.progress.add(written);
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct ProgressReader<'a, R: Read> {
+    inner: R,
+    progress: &'a SaveSyncProgress,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub fn new(inner: R, progress: &'a SaveSyncProgress) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.progress.add(read);
+        Ok(read)
+    }
+}
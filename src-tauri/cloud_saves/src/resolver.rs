@@ -1,127 +1,257 @@
 use std::{
     fs::{self, File, create_dir_all},
-    io::{self, Read, Write},
+    io::{self, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 
 use crate::error::BackupError;
 
 use super::{backup_manager::BackupHandler, placeholder::*};
-use database::GameVersion;
+use database::{GameVersion, SavePath, platform::Platform};
 use log::{debug, warn};
 use rustix::path::Arg;
 use tempfile::tempfile;
 
-use super::{backup_manager::BackupManager, metadata::CloudSaveMetadata, normalise::normalize};
+use super::{
+    backup_manager::{BackupManager, ExcludeFilter, walk_save_path},
+    metadata::{CloudSaveMetadata, GameFile, SavePathPreview, UnmappedSave},
+    normalise::normalize,
+    progress::{ProgressReader, ProgressWriter, SaveSyncProgress},
+};
+
+/// The first bytes of every zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const MAX_COMPRESSION_LEVEL: i32 = 22;
+
+/// Where `file` is on this machine, or `None` if it isn't for a platform this
+/// machine can back up
+pub fn local_path(
+    file: &GameFile,
+    manager: &BackupManager,
+    game: &GameVersion,
+) -> Option<Result<PathBuf, BackupError>> {
+    let Some(os) = file_os(file) else {
+        warn!("File {:?} has no OS, so it can't be located", &file);
+        return None;
+    };
+    let handler = *manager.sources.get(&(manager.current_platform, os))?;
+    let t_path = PathBuf::from(normalize(&file.path, os));
+    Some(parse_path(t_path, handler, game))
+}
+
+/// The platform a file's path is written for
+pub(crate) fn file_os(file: &GameFile) -> Option<Platform> {
+    file.conditions.iter().find_map(|p| match p {
+        super::conditions::Condition::Os(os) => Some(*os),
+        _ => None,
+    })
+}
 
-pub fn resolve(meta: &mut CloudSaveMetadata) -> File {
-    let f = File::create_new("save").unwrap();
-    let compressor = zstd::Encoder::new(f, 22).unwrap();
-    let mut tarball = tar::Builder::new(compressor);
+/// Packs the files in `meta` into an archive at `output`, giving each an id
+/// to find it by on restore. Files matching `exclude` are left out, and the
+/// archive is compressed with zstd at `compression` if set. Returns the size
+/// of the files packed.
+pub fn resolve(
+    meta: &mut CloudSaveMetadata,
+    exclude: &[String],
+    compression: Option<i32>,
+    progress: &SaveSyncProgress,
+    output: &Path,
+) -> Result<u64, BackupError> {
     let manager = BackupManager::new();
+    let mut entries = Vec::new();
     for file in meta.files.iter_mut() {
-        let id = uuid::Uuid::new_v4().to_string();
-        let os = match file
-            .conditions
-            .iter()
-            .find_map(|p| match p {
-                super::conditions::Condition::Os(os) => Some(os),
-                _ => None
-            })
-            .cloned()
-        {
-            Some(os) => os,
-            None => {
-                warn!(
-                    "File {:?} could not be backed up because it did not provide an OS",
-                    &file
-                );
-                continue;
-            }
-        };
-        let handler = match manager.sources.get(&(manager.current_platform, os)) {
-            Some(h) => *h,
+        let path = match local_path(file, &manager, &meta.game_version) {
+            Some(path) => path?,
             None => continue,
         };
-        let t_path = PathBuf::from(normalize(&file.path, os));
-        println!("{:?}", &t_path);
-        let path = parse_path(t_path, handler, &meta.game_version).unwrap();
-        let f = std::fs::metadata(&path).unwrap(); // TODO: Fix unwrap here
-        if f.is_dir() {
-            tarball.append_dir_all(&id, path).unwrap();
-        } else if f.is_file() {
-            tarball
-                .append_file(&id, &mut File::open(path).unwrap())
-                .unwrap();
+        if !path.exists() {
+            debug!("{} doesn't exist, nothing to back up", path.display());
+            continue;
+        }
+        let Some(os) = file_os(file) else {
+            continue;
+        };
+        let filter = ExcludeFilter::new(exclude, os)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        for (relative, full) in walk_save_path(&path, &filter)? {
+            let name = if relative.as_os_str().is_empty() {
+                PathBuf::from(&id)
+            } else {
+                Path::new(&id).join(relative)
+            };
+            entries.push((name, full));
         }
         file.id = Some(id);
     }
-    let binding = serde_json::to_string(meta).unwrap();
+
+    let total: u64 = entries
+        .iter()
+        .map(|(_, path)| fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+        .sum();
+    progress.set_total(total as usize);
+
+    let f = File::create_new(output)?;
+    match compression {
+        Some(level) => {
+            let level = level.clamp(1, MAX_COMPRESSION_LEVEL);
+            let compressor = zstd::Encoder::new(f, level)?;
+            let mut tarball = tar::Builder::new(ProgressWriter::new(compressor, progress));
+            append_entries(&mut tarball, entries, meta)?;
+            tarball.into_inner()?.into_inner().finish()?;
+        }
+        None => {
+            let mut tarball = tar::Builder::new(ProgressWriter::new(f, progress));
+            append_entries(&mut tarball, entries, meta)?;
+            tarball.into_inner()?;
+        }
+    }
+    progress.finish();
+    Ok(total)
+}
+
+fn append_entries<W: Write>(
+    tarball: &mut tar::Builder<W>,
+    entries: Vec<(PathBuf, PathBuf)>,
+    meta: &CloudSaveMetadata,
+) -> Result<(), BackupError> {
+    for (name, path) in entries {
+        tarball.append_path_with_name(path, name)?;
+    }
+    let binding = serde_json::to_string(meta).map_err(|_| BackupError::ParseError)?;
     let serialized = binding.as_bytes();
-    let mut file = tempfile().unwrap();
-    file.write_all(serialized).unwrap();
-    tarball.append_file("metadata", &mut file).unwrap();
-    tarball.into_inner().unwrap().finish().unwrap()
+    let mut file = tempfile()?;
+    file.write_all(serialized)?;
+    file.seek(SeekFrom::Start(0))?;
+    tarball.append_file("metadata", &mut file)?;
+    Ok(())
 }
 
-pub fn extract(file: PathBuf) -> Result<(), BackupError> {
-    let tmpdir = tempfile::tempdir().unwrap();
+/// Puts the files in the archive at `file` back where they belong on this
+/// machine. Paths are resolved against `game` rather than the version that
+/// was backed up, since the backup may come from another machine. Returns
+/// the saves that have nowhere to go here.
+pub fn extract(
+    file: PathBuf,
+    game: &GameVersion,
+    progress: &SaveSyncProgress,
+) -> Result<Vec<UnmappedSave>, BackupError> {
+    let tmpdir = tempfile::tempdir()?;
 
     // Reopen the file for reading
-    let file = File::open(file).unwrap();
+    let mut file = File::open(file)?;
+    progress.set_total(file.metadata().map(|m| m.len() as usize).unwrap_or(0));
+
+    // Backups can be plain tars, so check rather than trust the extension
+    let mut magic = [0u8; 4];
+    let compressed = file.read_exact(&mut magic).is_ok() && magic == ZSTD_MAGIC;
+    file.seek(SeekFrom::Start(0))?;
 
-    let decompressor = zstd::Decoder::new(file).unwrap();
-    let mut f = tar::Archive::new(decompressor);
-    f.unpack(tmpdir.path()).unwrap();
+    let reader = ProgressReader::new(file, progress);
+    if compressed {
+        tar::Archive::new(zstd::Decoder::new(reader)?).unpack(tmpdir.path())?;
+    } else {
+        tar::Archive::new(reader).unpack(tmpdir.path())?;
+    }
+    progress.finish();
 
     let path = tmpdir.path();
 
-    let mut manifest = File::open(path.join("metadata")).unwrap();
+    let mut manifest = File::open(path.join("metadata"))?;
 
     let mut manifest_slice = Vec::new();
-    manifest.read_to_end(&mut manifest_slice).unwrap();
-
-    let manifest: CloudSaveMetadata = serde_json::from_slice(&manifest_slice).unwrap();
-
-    for file in manifest.files {
-        let current_path = path.join(file.id.as_ref().unwrap());
-
-        let manager = BackupManager::new();
-        let os = match file
-            .conditions
-            .iter()
-            .find_map(|p| match p {
-                super::conditions::Condition::Os(os) => Some(os),
-                _ => None
-            })
-            .cloned()
-        {
-            Some(os) => os,
+    manifest.read_to_end(&mut manifest_slice)?;
+
+    let manifest: CloudSaveMetadata =
+        serde_json::from_slice(&manifest_slice).map_err(|_| BackupError::ParseError)?;
+
+    let manager = BackupManager::new();
+    let mut unmapped = Vec::new();
+    for file in &manifest.files {
+        // Files that didn't exist when backed up have no id
+        let Some(id) = &file.id else {
+            continue;
+        };
+        let current_path = path.join(id);
+        // Everything under it was excluded
+        if !current_path.exists() {
+            continue;
+        }
+
+        let new_path = match local_path(file, &manager, game) {
+            Some(Ok(new_path)) => new_path,
+            Some(Err(reason)) => {
+                warn!("can't restore {}: {reason}", file.path);
+                unmapped.push(UnmappedSave {
+                    path: file.path.clone(),
+                    platform: file_os(file),
+                    reason,
+                });
+                continue;
+            }
             None => {
-                warn!(
-                    "File {:?} could not be replaced up because it did not provide an OS",
-                    &file
-                );
+                warn!("can't restore {}, it's for another platform", file.path);
+                unmapped.push(UnmappedSave {
+                    path: file.path.clone(),
+                    platform: file_os(file),
+                    reason: BackupError::InvalidSystem,
+                });
                 continue;
             }
         };
-        let handler = match manager.sources.get(&(manager.current_platform, os)) {
-            Some(h) => *h,
-            None => continue,
-        };
-
-        let new_path = parse_path(file.path.into(), handler, &manifest.game_version)?;
-        create_dir_all(new_path.parent().unwrap()).unwrap();
+        if let Some(parent) = new_path.parent() {
+            create_dir_all(parent)?;
+        }
 
-        println!(
+        debug!(
             "Current path {:?} copying to {:?}",
             &current_path, &new_path
         );
 
-        copy_item(current_path, new_path).unwrap();
+        copy_item(current_path, new_path)?;
     }
 
-    Ok(())
+    Ok(unmapped)
+}
+
+/// Resolves each save path the way a backup would, so the user can check
+/// them before relying on it
+pub fn preview(game: &GameVersion, paths: &[SavePath]) -> Vec<SavePathPreview> {
+    let manager = BackupManager::new();
+    let metadata = CloudSaveMetadata::new(game.clone(), paths, String::new());
+    paths
+        .iter()
+        .zip(&metadata.files)
+        .map(|(save_path, file)| {
+            let resolved =
+                local_path(file, &manager, game).unwrap_or(Err(BackupError::InvalidSystem));
+            let (resolved, error) = match resolved {
+                Ok(path) => (Some(path), None),
+                Err(e) => (None, Some(e)),
+            };
+            SavePathPreview {
+                path: save_path.path.clone(),
+                platform: save_path.platform,
+                exists: resolved.as_ref().is_some_and(|path| path.exists()),
+                resolved,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Unix timestamp of the most recently modified file at or under `path`
+/// that isn't excluded
+pub fn newest_modification(path: &Path, filter: &ExcludeFilter) -> Option<i64> {
+    walk_save_path(path, filter)
+        .ok()?
+        .iter()
+        .filter_map(|(_, full)| fs::metadata(full).ok()?.modified().ok())
+        .max()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs() as i64)
 }
 
 pub fn copy_item<P: AsRef<Path>>(src: P, dest: P) -> io::Result<()> {
@@ -143,9 +273,10 @@ pub fn copy_item<P: AsRef<Path>>(src: P, dest: P) -> io::Result<()> {
     } else {
         // Handle other file types like symlinks if necessary,
         // for now, return an error or skip.
-        return Err(io::Error::other(
-            format!("Source {:?} is neither a file nor a directory", src_path),
-        ));
+        return Err(io::Error::other(format!(
+            "Source {:?} is neither a file nor a directory",
+            src_path
+        )));
     }
 
     Ok(())
@@ -207,6 +338,12 @@ pub fn parse_path(
             XDG_DATA => s.push(backup_handler.xdg_data_translate(&path, game)?),
             XDG_CONFIG => s.push(backup_handler.xdg_config_translate(&path, game)?),
             SKIP => s.push(backup_handler.skip_translate(&path, game)?),
+            other
+                if (other.starts_with('<') && other.ends_with('>'))
+                    || (other.starts_with('{') && other.ends_with('}')) =>
+            {
+                return Err(BackupError::UnknownPlaceholder(other.to_string()));
+            }
             _ => s.push(PathBuf::from(component.as_os_str())),
         }
     }
@@ -0,0 +1,415 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use database::{CloudSaveConfig, Database, GameVersion, borrow_db_checked, db::DATA_ROOT_DIR};
+use log::{debug, info, warn};
+
+use crate::{
+    backup_manager::{BackupManager, ExcludeFilter},
+    error::BackupError,
+    metadata::{
+        CloudSaveMetadata, ConflictResolution, SaveConflict, SnapshotInfo, SyncState, UnmappedSave,
+    },
+    progress::SaveSyncProgress,
+    resolver::{extract, file_os, local_path, newest_modification, resolve},
+};
+
+const SYNC_STATE_FILE: &str = "sync.json";
+
+/// Where a game's save backups are kept. There's no server-side save storage
+/// yet, so "cloud" saves live here until there is.
+pub fn snapshots_dir(game_id: &str) -> PathBuf {
+    DATA_ROOT_DIR.join("saves").join(game_id)
+}
+
+fn archive_path(dir: &Path, snapshot_id: &str, compressed: bool) -> PathBuf {
+    let extension = if compressed { "tar.zst" } else { "tar" };
+    dir.join(format!("{snapshot_id}.{extension}"))
+}
+
+fn info_path(dir: &Path, snapshot_id: &str) -> PathBuf {
+    dir.join(format!("{snapshot_id}.json"))
+}
+
+/// The zstd level new backups are compressed at, if they're compressed
+fn save_compression() -> Option<i32> {
+    let db_lock = borrow_db_checked();
+    db_lock
+        .settings
+        .compress_save_backups
+        .then_some(db_lock.settings.save_compression_level)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// A game's save config with the global exclude patterns added, or `None`
+/// if it has none
+pub fn effective_config(db: &Database, game_id: &str) -> Option<CloudSaveConfig> {
+    let mut config = db.applications.game_cloud_saves.get(game_id)?.clone();
+    config
+        .exclude
+        .extend(db.settings.save_exclude_patterns.iter().cloned());
+    Some(config)
+}
+
+/// All of a game's save backups, newest first
+pub fn list_snapshots(game_id: &str) -> Result<Vec<SnapshotInfo>, BackupError> {
+    list_snapshots_at(&snapshots_dir(game_id))
+}
+
+pub(crate) fn list_snapshots_at(dir: &Path) -> Result<Vec<SnapshotInfo>, BackupError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json")
+            || path.file_name().is_some_and(|name| name == SYNC_STATE_FILE)
+        {
+            continue;
+        }
+        match serde_json::from_slice::<SnapshotInfo>(&fs::read(&path)?) {
+            Ok(info) => snapshots.push(info),
+            Err(e) => warn!("skipping unreadable save backup {}: {e}", path.display()),
+        }
+    }
+    snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(snapshots)
+}
+
+pub fn latest_snapshot(game_id: &str) -> Result<Option<SnapshotInfo>, BackupError> {
+    latest_snapshot_at(&snapshots_dir(game_id))
+}
+
+fn latest_snapshot_at(dir: &Path) -> Result<Option<SnapshotInfo>, BackupError> {
+    Ok(list_snapshots_at(dir)?.into_iter().next())
+}
+
+/// Unix timestamp of the newest save file on this machine, if there are any
+pub fn local_modified(game_version: &GameVersion, config: &CloudSaveConfig) -> Option<i64> {
+    let manager = BackupManager::new();
+    let metadata = CloudSaveMetadata::new(game_version.clone(), &config.paths, String::new());
+    metadata
+        .files
+        .iter()
+        .filter_map(|file| {
+            let path = local_path(file, &manager, game_version)?.ok()?;
+            let filter = ExcludeFilter::new(&config.exclude, file_os(file)?).ok()?;
+            newest_modification(&path, &filter)
+        })
+        .max()
+}
+
+pub fn read_sync_state(game_id: &str) -> Option<SyncState> {
+    read_sync_state_at(&snapshots_dir(game_id))
+}
+
+pub(crate) fn read_sync_state_at(dir: &Path) -> Option<SyncState> {
+    let data = fs::read(dir.join(SYNC_STATE_FILE)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+pub(crate) fn write_sync_state(dir: &Path, state: &SyncState) -> Result<(), BackupError> {
+    let data = serde_json::to_vec(state).map_err(|_| BackupError::ParseError)?;
+    fs::write(dir.join(SYNC_STATE_FILE), data)?;
+    Ok(())
+}
+
+/// Backs up the game's current saves as a new snapshot
+pub fn create_snapshot(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    progress: &SaveSyncProgress,
+) -> Result<SnapshotInfo, BackupError> {
+    create_snapshot_at(
+        &snapshots_dir(&game_version.game_id),
+        game_version,
+        config,
+        save_compression(),
+        progress,
+    )
+}
+
+pub(crate) fn create_snapshot_at(
+    dir: &Path,
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    compression: Option<i32>,
+    progress: &SaveSyncProgress,
+) -> Result<SnapshotInfo, BackupError> {
+    let game_id = &game_version.game_id;
+    fs::create_dir_all(dir)?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let mut metadata = CloudSaveMetadata::new(game_version.clone(), &config.paths, id.clone());
+    let archive_path = archive_path(dir, &id, compression.is_some());
+    let uncompressed_size = match resolve(
+        &mut metadata,
+        &config.exclude,
+        compression,
+        progress,
+        &archive_path,
+    ) {
+        Ok(size) => size,
+        Err(e) => {
+            let _ = fs::remove_file(&archive_path);
+            return Err(e);
+        }
+    };
+
+    let info = SnapshotInfo {
+        id: id.clone(),
+        created_at: now(),
+        size: fs::metadata(&archive_path)?.len(),
+        uncompressed_size,
+        compressed: compression.is_some(),
+    };
+    let data = serde_json::to_vec(&info).map_err(|_| BackupError::ParseError)?;
+    fs::write(info_path(dir, &id), data)?;
+
+    write_sync_state(
+        dir,
+        &SyncState {
+            snapshot_id: id,
+            local_modified: local_modified(game_version, config).unwrap_or(0),
+        },
+    )?;
+    info!(
+        "backed up saves for {game_id} ({} bytes, {} before compression)",
+        info.size, info.uncompressed_size
+    );
+    Ok(info)
+}
+
+/// Puts a snapshot's saves back in place, overwriting what's there. Returns
+/// the saves that couldn't be mapped to a location on this machine.
+pub fn restore_snapshot(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    snapshot_id: &str,
+    progress: &SaveSyncProgress,
+) -> Result<Vec<UnmappedSave>, BackupError> {
+    restore_snapshot_at(
+        &snapshots_dir(&game_version.game_id),
+        game_version,
+        config,
+        snapshot_id,
+        progress,
+    )
+}
+
+pub(crate) fn restore_snapshot_at(
+    dir: &Path,
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    snapshot_id: &str,
+    progress: &SaveSyncProgress,
+) -> Result<Vec<UnmappedSave>, BackupError> {
+    let game_id = &game_version.game_id;
+    // Only ids we know about, so the id can't point outside the saves folder
+    let snapshot = list_snapshots_at(dir)?
+        .into_iter()
+        .find(|snapshot| snapshot.id == snapshot_id)
+        .ok_or_else(|| BackupError::SnapshotNotFound(snapshot_id.to_string()))?;
+    let archive_path = archive_path(dir, snapshot_id, snapshot.compressed);
+    // Make sure it's readable before we start overwriting anything
+    File::open(&archive_path)?;
+    let unmapped = extract(archive_path, game_version, progress)?;
+
+    write_sync_state(
+        dir,
+        &SyncState {
+            snapshot_id: snapshot_id.to_string(),
+            local_modified: local_modified(game_version, config).unwrap_or(0),
+        },
+    )?;
+    info!("restored saves for {game_id} from {snapshot_id}");
+    Ok(unmapped)
+}
+
+/// Checks whether the local saves and the latest snapshot have both changed
+/// since the last sync
+pub fn detect_conflict(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+) -> Result<Option<SaveConflict>, BackupError> {
+    detect_conflict_at(&snapshots_dir(&game_version.game_id), game_version, config)
+}
+
+pub(crate) fn detect_conflict_at(
+    dir: &Path,
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+) -> Result<Option<SaveConflict>, BackupError> {
+    let Some(latest) = latest_snapshot_at(dir)? else {
+        return Ok(None);
+    };
+    let Some(local) = local_modified(game_version, config) else {
+        return Ok(None);
+    };
+    let conflicting = match read_sync_state_at(dir) {
+        Some(state) => state.snapshot_id != latest.id && local > state.local_modified,
+        // Saves from before syncing was turned on, and a backup from elsewhere
+        None => true,
+    };
+    Ok(conflicting.then_some(SaveConflict {
+        local_modified: local,
+        remote_modified: latest.created_at,
+    }))
+}
+
+/// Restores the latest snapshot before a launch, if it's newer than what was
+/// last synced. Returns whether anything was restored.
+pub fn sync_before_launch(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    progress: &SaveSyncProgress,
+) -> Result<bool, BackupError> {
+    sync_before_launch_at(
+        &snapshots_dir(&game_version.game_id),
+        game_version,
+        config,
+        progress,
+    )
+}
+
+pub(crate) fn sync_before_launch_at(
+    dir: &Path,
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    progress: &SaveSyncProgress,
+) -> Result<bool, BackupError> {
+    let game_id = &game_version.game_id;
+    let Some(latest) = latest_snapshot_at(dir)? else {
+        debug!("no save backups for {game_id}, nothing to restore");
+        return Ok(false);
+    };
+    if let Some(conflict) = detect_conflict_at(dir, game_version, config)? {
+        return Err(BackupError::Conflict(conflict));
+    }
+    if read_sync_state_at(dir).is_some_and(|state| state.snapshot_id == latest.id) {
+        debug!("saves for {game_id} are already up to date");
+        return Ok(false);
+    }
+
+    restore_snapshot_at(dir, game_version, config, &latest.id, progress)?;
+    Ok(true)
+}
+
+/// Backs up the saves after a session, unless that would bury a snapshot
+/// the local saves were never synced with
+pub fn sync_after_exit(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    progress: &SaveSyncProgress,
+) -> Result<SnapshotInfo, BackupError> {
+    sync_after_exit_at(
+        &snapshots_dir(&game_version.game_id),
+        game_version,
+        config,
+        save_compression(),
+        progress,
+    )
+}
+
+pub(crate) fn sync_after_exit_at(
+    dir: &Path,
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    compression: Option<i32>,
+    progress: &SaveSyncProgress,
+) -> Result<SnapshotInfo, BackupError> {
+    if let Some(conflict) = detect_conflict_at(dir, game_version, config)? {
+        return Err(BackupError::Conflict(conflict));
+    }
+    create_snapshot_at(dir, game_version, config, compression, progress)
+}
+
+/// Renames the local saves to `<name>.<suffix>`, so a restore doesn't
+/// overwrite them
+fn move_local_saves_aside(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    suffix: &str,
+) -> Result<(), BackupError> {
+    let manager = BackupManager::new();
+    let metadata = CloudSaveMetadata::new(game_version.clone(), &config.paths, String::new());
+    for file in &metadata.files {
+        let Some(path) = local_path(file, &manager, game_version) else {
+            continue;
+        };
+        let path = path?;
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        if !path.exists() {
+            continue;
+        }
+        let mut new_name = name.to_os_string();
+        new_name.push(format!(".{suffix}"));
+        let new_path = path.with_file_name(new_name);
+        info!("moving {} to {}", path.display(), new_path.display());
+        fs::rename(&path, new_path)?;
+    }
+    Ok(())
+}
+
+/// Settles a conflict. With `KeepBoth` the newer saves win, and the older
+/// ones are kept: local saves are renamed with their modification time as a
+/// suffix, and an older snapshot stays in the backup history.
+pub fn resolve_conflict(
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    resolution: ConflictResolution,
+    progress: &SaveSyncProgress,
+) -> Result<(), BackupError> {
+    resolve_conflict_at(
+        &snapshots_dir(&game_version.game_id),
+        game_version,
+        config,
+        resolution,
+        save_compression(),
+        progress,
+    )
+}
+
+pub(crate) fn resolve_conflict_at(
+    dir: &Path,
+    game_version: &GameVersion,
+    config: &CloudSaveConfig,
+    resolution: ConflictResolution,
+    compression: Option<i32>,
+    progress: &SaveSyncProgress,
+) -> Result<(), BackupError> {
+    let game_id = &game_version.game_id;
+    let latest =
+        || latest_snapshot_at(dir)?.ok_or_else(|| BackupError::SnapshotNotFound(game_id.clone()));
+    match resolution {
+        ConflictResolution::KeepLocal => {
+            create_snapshot_at(dir, game_version, config, compression, progress)?;
+        }
+        ConflictResolution::KeepRemote => {
+            restore_snapshot_at(dir, game_version, config, &latest()?.id, progress)?;
+        }
+        ConflictResolution::KeepBoth => {
+            let latest = latest()?;
+            let local = local_modified(game_version, config).unwrap_or(0);
+            if local > latest.created_at {
+                create_snapshot_at(dir, game_version, config, compression, progress)?;
+            } else {
+                move_local_saves_aside(game_version, config, &local.to_string())?;
+                restore_snapshot_at(dir, game_version, config, &latest.id, progress)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,356 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use database::{CloudSaveConfig, GameVersion, SavePath, platform::Platform};
+use utils::test_support::test_dir;
+
+use crate::{
+    backup_manager::{ExcludeFilter, walk_save_path},
+    error::BackupError,
+    metadata::{ConflictResolution, SaveConflict, SnapshotInfo, SyncState},
+    progress::SaveSyncProgress,
+    snapshots::{
+        create_snapshot_at, detect_conflict_at, list_snapshots_at, read_sync_state_at,
+        resolve_conflict_at, sync_after_exit_at, sync_before_launch_at, write_sync_state,
+    },
+};
+
+#[test]
+fn excludes_cache_directories_at_any_depth() {
+    let filter = ExcludeFilter::new(&["**/cache/**".to_string()], Platform::Linux).unwrap();
+
+    assert!(filter.is_excluded(Path::new("cache/shaders.bin")));
+    assert!(filter.is_excluded(Path::new("profile/cache/deep/thumb.png")));
+    assert!(!filter.is_excluded(Path::new("profile/slot1.sav")));
+    assert!(!filter.is_excluded(Path::new("cachefile.sav")));
+}
+
+#[test]
+fn exclude_case_sensitivity_follows_platform() {
+    let patterns = ["**/Logs/**".to_string()];
+    let linux = ExcludeFilter::new(&patterns, Platform::Linux).unwrap();
+    let windows = ExcludeFilter::new(&patterns, Platform::Windows).unwrap();
+
+    assert!(linux.is_excluded(Path::new("Logs/latest.log")));
+    assert!(!linux.is_excluded(Path::new("logs/latest.log")));
+    assert!(windows.is_excluded(Path::new("Logs/latest.log")));
+    assert!(windows.is_excluded(Path::new("logs/latest.log")));
+}
+
+#[test]
+fn walk_skips_excluded_files() {
+    let dir = test_dir("walk-excludes");
+    fs::create_dir_all(dir.join("profile/cache")).unwrap();
+    fs::create_dir_all(dir.join("cache")).unwrap();
+    fs::write(dir.join("profile/slot1.sav"), b"save").unwrap();
+    fs::write(dir.join("profile/cache/thumb.png"), b"thumb").unwrap();
+    fs::write(dir.join("cache/shaders.bin"), b"shaders").unwrap();
+
+    let filter = ExcludeFilter::new(&["**/cache/**".to_string()], Platform::Linux).unwrap();
+    let files = walk_save_path(&dir, &filter).unwrap();
+
+    assert_eq!(
+        files,
+        vec![(
+            PathBuf::from("profile/slot1.sav"),
+            dir.join("profile/slot1.sav")
+        )]
+    );
+}
+
+#[test]
+fn invalid_exclude_pattern_is_an_error() {
+    assert!(ExcludeFilter::new(&["saves/[".to_string()], Platform::Linux).is_err());
+}
+
+/// A game with one save folder, `saves`, and its backups in `snapshots`
+struct SaveFixture {
+    saves: PathBuf,
+    snapshots: PathBuf,
+    game_version: GameVersion,
+    config: CloudSaveConfig,
+    progress: SaveSyncProgress,
+}
+
+impl SaveFixture {
+    fn new(name: &str) -> Self {
+        let dir = test_dir(name);
+        let saves = dir.join("saves");
+        fs::create_dir_all(&saves).unwrap();
+        Self {
+            snapshots: dir.join("snapshots"),
+            game_version: GameVersion {
+                game_id: name.to_owned(),
+                version_name: "1.0".to_owned(),
+                platform: Platform::HOST,
+                launch_command: "game".to_owned(),
+                launch_args: Vec::new(),
+                launch_command_template: "{}".to_owned(),
+                setup_command: String::new(),
+                setup_args: Vec::new(),
+                setup_command_template: "{}".to_owned(),
+                only_setup: false,
+                version_index: 0,
+                delta: false,
+                umu_id_override: None,
+                preferred_launcher: None,
+                executable_override: None,
+            },
+            config: CloudSaveConfig {
+                paths: vec![SavePath {
+                    path: saves.to_string_lossy().to_string(),
+                    platform: Platform::HOST,
+                }],
+                sync_on_launch: true,
+                sync_on_exit: true,
+                exclude: Vec::new(),
+            },
+            progress: SaveSyncProgress::detached(name.to_owned()),
+            saves,
+        }
+    }
+
+    /// Writes the save file, modified at `modified` seconds after the epoch
+    fn write_save(&self, contents: &str, modified: u64) {
+        let path = self.saves.join("slot1.sav");
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(modified))
+            .unwrap();
+    }
+
+    fn read_save(&self) -> String {
+        fs::read_to_string(self.saves.join("slot1.sav")).unwrap()
+    }
+
+    /// Backs the saves up as if it happened at `created_at`
+    fn snapshot(&self, created_at: i64) -> SnapshotInfo {
+        let mut info = create_snapshot_at(
+            &self.snapshots,
+            &self.game_version,
+            &self.config,
+            None,
+            &self.progress,
+        )
+        .unwrap();
+        info.created_at = created_at;
+        fs::write(
+            self.snapshots.join(format!("{}.json", info.id)),
+            serde_json::to_vec(&info).unwrap(),
+        )
+        .unwrap();
+        info
+    }
+
+    fn detect_conflict(&self) -> Option<SaveConflict> {
+        detect_conflict_at(&self.snapshots, &self.game_version, &self.config).unwrap()
+    }
+
+    fn resolve(&self, resolution: ConflictResolution) {
+        resolve_conflict_at(
+            &self.snapshots,
+            &self.game_version,
+            &self.config,
+            resolution,
+            None,
+            &self.progress,
+        )
+        .unwrap();
+    }
+
+    fn snapshot_count(&self) -> usize {
+        list_snapshots_at(&self.snapshots).unwrap().len()
+    }
+
+    /// Local saves last synced at `synced` with one backup, then a newer
+    /// backup from elsewhere. Returns the newer backup.
+    fn with_remote_backup(&self, synced: u64) -> SnapshotInfo {
+        self.write_save("first", synced);
+        let first = self.snapshot(1000);
+        self.write_save("remote", 1500);
+        let remote = self.snapshot(2000);
+
+        // Put this machine back to where it was before the other one synced
+        self.write_save("first", synced);
+        write_sync_state(
+            &self.snapshots,
+            &SyncState {
+                snapshot_id: first.id,
+                local_modified: synced as i64,
+            },
+        )
+        .unwrap();
+        remote
+    }
+
+    /// Both the local saves and the backups changed since the last sync
+    fn conflicted(name: &str, local_modified: u64) -> Self {
+        let fixture = Self::new(name);
+        fixture.with_remote_backup(1000);
+        fixture.write_save("local", local_modified);
+        assert!(fixture.detect_conflict().is_some());
+        fixture
+    }
+}
+
+#[test]
+fn saves_synced_from_here_never_conflict() {
+    let fixture = SaveFixture::new("sync-no-conflict");
+    let sync_before_launch = || {
+        sync_before_launch_at(
+            &fixture.snapshots,
+            &fixture.game_version,
+            &fixture.config,
+            &fixture.progress,
+        )
+        .unwrap()
+    };
+
+    // Nothing backed up yet
+    fixture.write_save("first", 1000);
+    assert_eq!(fixture.detect_conflict(), None);
+    assert!(!sync_before_launch());
+
+    let first = sync_after_exit_at(
+        &fixture.snapshots,
+        &fixture.game_version,
+        &fixture.config,
+        None,
+        &fixture.progress,
+    )
+    .unwrap();
+    assert_eq!(
+        read_sync_state_at(&fixture.snapshots).unwrap().snapshot_id,
+        first.id
+    );
+    // The latest backup is the one taken here, so there's nothing to restore
+    assert!(!sync_before_launch());
+
+    // Playing again only changes the local side
+    fixture.write_save("second", 2000);
+    assert_eq!(fixture.detect_conflict(), None);
+    sync_after_exit_at(
+        &fixture.snapshots,
+        &fixture.game_version,
+        &fixture.config,
+        None,
+        &fixture.progress,
+    )
+    .unwrap();
+    assert_eq!(fixture.snapshot_count(), 2);
+    assert_eq!(fixture.read_save(), "second");
+}
+
+#[test]
+fn newer_backup_is_restored_before_launch() {
+    let fixture = SaveFixture::new("sync-restore");
+    let remote = fixture.with_remote_backup(1000);
+
+    assert_eq!(fixture.detect_conflict(), None);
+    assert!(
+        sync_before_launch_at(
+            &fixture.snapshots,
+            &fixture.game_version,
+            &fixture.config,
+            &fixture.progress,
+        )
+        .unwrap()
+    );
+    assert_eq!(fixture.read_save(), "remote");
+    assert_eq!(
+        read_sync_state_at(&fixture.snapshots).unwrap().snapshot_id,
+        remote.id
+    );
+}
+
+#[test]
+fn changes_on_both_sides_conflict() {
+    let fixture = SaveFixture::conflicted("sync-conflict", 3000);
+    assert_eq!(
+        fixture.detect_conflict(),
+        Some(SaveConflict {
+            local_modified: 3000,
+            remote_modified: 2000,
+        })
+    );
+
+    let restored = sync_before_launch_at(
+        &fixture.snapshots,
+        &fixture.game_version,
+        &fixture.config,
+        &fixture.progress,
+    );
+    assert!(matches!(restored, Err(BackupError::Conflict(_))));
+    assert_eq!(fixture.read_save(), "local");
+
+    let backed_up = sync_after_exit_at(
+        &fixture.snapshots,
+        &fixture.game_version,
+        &fixture.config,
+        None,
+        &fixture.progress,
+    );
+    assert!(matches!(backed_up, Err(BackupError::Conflict(_))));
+    assert_eq!(fixture.snapshot_count(), 2);
+}
+
+#[test]
+fn unsynced_saves_conflict_with_any_backup() {
+    let fixture = SaveFixture::new("sync-no-state");
+    fixture.write_save("first", 1000);
+    fixture.snapshot(1000);
+    fs::remove_file(fixture.snapshots.join("sync.json")).unwrap();
+
+    assert!(fixture.detect_conflict().is_some());
+}
+
+#[test]
+fn keep_local_backs_up_the_local_saves() {
+    let fixture = SaveFixture::conflicted("resolve-keep-local", 3000);
+    fixture.resolve(ConflictResolution::KeepLocal);
+
+    assert_eq!(fixture.read_save(), "local");
+    assert_eq!(fixture.snapshot_count(), 3);
+    assert_eq!(fixture.detect_conflict(), None);
+}
+
+#[test]
+fn keep_remote_restores_the_latest_backup() {
+    let fixture = SaveFixture::conflicted("resolve-keep-remote", 3000);
+    fixture.resolve(ConflictResolution::KeepRemote);
+
+    assert_eq!(fixture.read_save(), "remote");
+    assert_eq!(fixture.snapshot_count(), 2);
+    assert_eq!(fixture.detect_conflict(), None);
+}
+
+#[test]
+fn keep_both_backs_up_newer_local_saves() {
+    let fixture = SaveFixture::conflicted("resolve-keep-both-local", 3000);
+    fixture.resolve(ConflictResolution::KeepBoth);
+
+    assert_eq!(fixture.read_save(), "local");
+    // The remote backup is still in the history
+    assert_eq!(fixture.snapshot_count(), 3);
+    assert_eq!(fixture.detect_conflict(), None);
+}
+
+#[test]
+fn keep_both_moves_older_local_saves_aside() {
+    let fixture = SaveFixture::conflicted("resolve-keep-both-remote", 1800);
+    fixture.resolve(ConflictResolution::KeepBoth);
+
+    assert_eq!(fixture.read_save(), "remote");
+    assert_eq!(
+        fs::read_to_string(fixture.saves.with_file_name("saves.1800").join("slot1.sav")).unwrap(),
+        "local"
+    );
+    assert_eq!(fixture.snapshot_count(), 2);
+    assert_eq!(fixture.detect_conflict(), None);
+}
@@ -0,0 +1,50 @@
+use chrono::{Datelike, Local, Months, NaiveDate};
+
+use crate::BandwidthUsage;
+
+const PERIOD_FORMAT: &str = "%Y-%m-%d";
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// The day the quota period `today` falls in started, for a quota that
+/// starts over on `reset_day` of every month
+pub fn period_start(today: NaiveDate, reset_day: u32) -> NaiveDate {
+    // Settings keep it in 1..=28, so it's a day every month has
+    let start = today
+        .with_day(reset_day.clamp(1, 28))
+        .expect("every month has the first 28 days");
+    if today.day() >= start.day() {
+        start
+    } else {
+        start - Months::new(1)
+    }
+}
+
+fn period_key(today: NaiveDate, reset_day: u32) -> String {
+    period_start(today, reset_day)
+        .format(PERIOD_FORMAT)
+        .to_string()
+}
+
+impl BandwidthUsage {
+    /// Bytes downloaded in the period `today` falls in
+    pub fn current(&self, today: NaiveDate, reset_day: u32) -> u64 {
+        if self.period_start == period_key(today, reset_day) {
+            self.bytes
+        } else {
+            0
+        }
+    }
+
+    /// Counts `bytes`, starting a new period first if the last one is over
+    pub fn add(&mut self, bytes: u64, today: NaiveDate, reset_day: u32) {
+        let start = period_key(today, reset_day);
+        if self.period_start != start {
+            self.period_start = start;
+            self.bytes = 0;
+        }
+        self.bytes += bytes;
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::{Database, DownloadHistoryEntry};
+
+/// Older entries are dropped past this, so the history can't grow forever
+pub const MAX_DOWNLOAD_HISTORY: usize = 1000;
+
+/// Everything downloaded in one calendar month, in local time
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthlyDownloadUsage {
+    // Formatted as YYYY-MM
+    pub month: String,
+    pub bytes_downloaded: u64,
+    pub duration_secs: u64,
+    pub downloads: usize,
+}
+
+impl DownloadHistoryEntry {
+    /// The month this download completed in, formatted as YYYY-MM
+    pub fn month(&self) -> String {
+        DateTime::from_timestamp(self.completed_at, 0)
+            .unwrap_or_default()
+            .with_timezone(&Local)
+            .format("%Y-%m")
+            .to_string()
+    }
+}
+
+/// Adds a finished download to the history, dropping the oldest entries
+/// past [`MAX_DOWNLOAD_HISTORY`]
+pub fn record_download(db: &mut Database, entry: DownloadHistoryEntry) {
+    db.download_history.push(entry);
+    let excess = db
+        .download_history
+        .len()
+        .saturating_sub(MAX_DOWNLOAD_HISTORY);
+    db.download_history.drain(..excess);
+}
+
+/// Adds up `history` per month, oldest month first
+pub fn monthly_rollup(history: &[DownloadHistoryEntry]) -> Vec<MonthlyDownloadUsage> {
+    let mut months = BTreeMap::<String, MonthlyDownloadUsage>::new();
+    for entry in history {
+        let month = entry.month();
+        let usage = months
+            .entry(month.clone())
+            .or_insert_with(|| MonthlyDownloadUsage {
+                month,
+                ..Default::default()
+            });
+        usage.bytes_downloaded += entry.bytes_downloaded;
+        usage.duration_secs += entry.duration_secs;
+        usage.downloads += 1;
+    }
+    months.into_values().collect()
+}
@@ -1,18 +1,20 @@
 use std::{
-    fs::{self, create_dir_all},
+    fs::{self, File, create_dir_all},
+    io,
     mem::ManuallyDrop,
     ops::{Deref, DerefMut},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{RwLockReadGuard, RwLockWriteGuard},
 };
 
 use chrono::Utc;
 use log::{debug, error, info, warn};
-use rustbreak::{PathDatabase, RustbreakError};
+use rustbreak::{DeSerializer, PathDatabase, RustbreakError};
 use url::Url;
 
 use crate::{
     db::{DATA_ROOT_DIR, DB, DropDatabaseSerializer},
+    migrations::run_migrations,
     models::data::Database,
 };
 
@@ -78,7 +80,10 @@ impl DatabaseImpls for DatabaseInterface {
         });
 
         if exists {
-            match PathDatabase::load_from_path(db_path.clone()) {
+            match PathDatabase::load_from_path(db_path.clone()).and_then(|db| {
+                migrate_database(&db)?;
+                Ok(db)
+            }) {
                 Ok(db) => db,
                 Err(e) => handle_invalid_database(e, db_path, games_base_dir, cache_dir),
             }
@@ -100,6 +105,13 @@ impl DatabaseImpls for DatabaseInterface {
     }
 }
 
+fn migrate_database(db: &DatabaseInterface) -> Result<(), RustbreakError> {
+    if db.write(run_migrations)? {
+        db.save()?;
+    }
+    Ok(())
+}
+
 // TODO: Make the error relelvant rather than just assume that it's a Deserialize error
 fn handle_invalid_database(
     _e: RustbreakError,
@@ -129,6 +141,48 @@ fn handle_invalid_database(
     PathDatabase::create_at_path(db_path, db).expect("Database could not be created")
 }
 
+/// Writes the database to `path` in the same format as `drop.db`, e.g. to
+/// move it to another machine
+pub fn export_database(path: &Path) -> io::Result<()> {
+    write_database(&borrow_db_checked(), path)
+}
+
+pub fn write_database(db: &Database, path: &Path) -> io::Result<()> {
+    let data = DropDatabaseSerializer
+        .serialize(db)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    fs::write(path, data)
+}
+
+/// Reads a database written by [`write_database`], migrated to the current
+/// schema
+pub fn read_database(path: &Path) -> io::Result<Database> {
+    let mut db: Database = DropDatabaseSerializer
+        .deserialize(File::open(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    run_migrations(&mut db);
+    Ok(db)
+}
+
+/// Replaces the database with one written by [`export_database`]. The
+/// current one is kept next to `drop.db` as a backup, and paths that only
+/// make sense on this machine are kept as they are. Returns where the backup
+/// went.
+pub fn import_database(path: &Path) -> io::Result<PathBuf> {
+    let mut imported = read_database(path)?;
+
+    let backup_path = DATA_ROOT_DIR.join(format!("drop.db.backup-{}", Utc::now().timestamp()));
+    export_database(&backup_path)?;
+    info!("current database backed up to {}", backup_path.display());
+
+    let mut db_lock = borrow_db_mut_checked();
+    // Not prev_database, that's only for databases we couldn't read
+    imported.cache_dir = db_lock.cache_dir.clone();
+    *db_lock = imported;
+    info!("imported database from {}", path.display());
+    Ok(backup_path)
+}
+
 // To automatically save the database upon drop
 pub struct DBRead<'a>(RwLockReadGuard<'a, Database>);
 pub struct DBWrite<'a>(ManuallyDrop<RwLockWriteGuard<'a, Database>>);
@@ -0,0 +1,102 @@
+use crate::LaunchConfig;
+
+/// The template for a version that doesn't change how the game is launched
+pub const DEFAULT_LAUNCH_TEMPLATE: &str = "{}";
+
+/// Whether `key` is a name both sh and cmd can set, i.e. `[A-Za-z_][A-Za-z0-9_]*`
+pub fn is_valid_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl LaunchConfig {
+    /// The first environment variable name that can't be set, if any
+    pub fn invalid_env_key(&self) -> Option<&str> {
+        self.env
+            .keys()
+            .find(|key| !is_valid_env_key(key))
+            .map(String::as_str)
+    }
+
+    /// The wrapper set for this game, if it isn't blank
+    pub fn wrapper(&self) -> Option<&str> {
+        self.wrapper
+            .as_deref()
+            .map(str::trim)
+            .filter(|wrapper| !wrapper.is_empty())
+    }
+
+    /// The wrapper the whole launch command runs through: this game's own,
+    /// or the default for every game. They're never stacked.
+    pub fn merged_wrapper<'a>(&'a self, defaults: &'a LaunchConfig) -> Option<&'a str> {
+        self.wrapper().or_else(|| defaults.wrapper())
+    }
+
+    /// Builds the launch command template for these options. Values can use
+    /// the same `{dir}`, `{exe}` and `{abs_exe}` placeholders as the template.
+    /// The wrapper isn't part of it, as it wraps the whole command at launch
+    /// along with the default one.
+    pub fn template(&self) -> String {
+        let mut template = String::new();
+
+        if let Some(working_dir) = self.working_dir.as_ref().filter(|d| !d.is_empty()) {
+            #[cfg(target_os = "windows")]
+            template.push_str(&format!("cd /d {} && ", quote(working_dir)));
+            #[cfg(not(target_os = "windows"))]
+            template.push_str(&format!("cd {} && ", quote(working_dir)));
+        }
+
+        // Rejected when saved, but older configs may still have them
+        for (key, value) in self.env.iter().filter(|(key, _)| is_valid_env_key(key)) {
+            // Unquoted so the value can be escaped, and && straight after so
+            // no trailing space ends up in it
+            #[cfg(target_os = "windows")]
+            template.push_str(&format!("set {key}={}&& ", escape_cmd(value)));
+            #[cfg(not(target_os = "windows"))]
+            template.push_str(&format!("{key}={} ", quote(value)));
+        }
+
+        template.push_str(DEFAULT_LAUNCH_TEMPLATE);
+
+        for arg in &self.args {
+            template.push(' ');
+            template.push_str(&quote(arg));
+        }
+
+        template
+    }
+}
+
+/// Quotes `value` for the shell the launch command runs in, unless it is a
+/// plain word that doesn't need it
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c));
+    if plain {
+        return value.to_owned();
+    }
+
+    #[cfg(target_os = "windows")]
+    return format!("\"{}\"", value.replace('"', "\"\""));
+    #[cfg(not(target_os = "windows"))]
+    return format!("'{}'", value.replace('\'', "'\\''"));
+}
+
+/// Escapes every character cmd treats specially outside quotes with `^`.
+/// `%` too, which leaves `%NAME^%` to expand a variable called `NAME^` that
+/// won't exist.
+pub fn escape_cmd(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "^&|<>()\"%!".contains(c) {
+            escaped.push('^');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -1,14 +1,25 @@
 #![feature(nonpoison_rwlock)]
 
+pub mod bandwidth;
 pub mod db;
 pub mod debug;
+pub mod download_history;
 pub mod interface;
+pub mod launch_config;
+pub mod migrations;
 pub mod models;
 pub mod platform;
+pub mod prefix;
+pub mod settings;
 
 pub use db::DB;
 pub use interface::{borrow_db_checked, borrow_db_mut_checked};
 pub use models::data::{
-    ApplicationTransientStatus, Database, DatabaseApplications, DatabaseAuth, DownloadType,
-    DownloadableMetadata, GameDownloadStatus, GameVersion, Settings,
+    ApplicationTransientStatus, BandwidthUsage, CloudSaveConfig, Database, DatabaseApplications,
+    DatabaseAuth, DefaultVersionPolicy, DownloadHistoryEntry, DownloadType, DownloadableMetadata,
+    GameDownloadStatus, GamePlaytime, GameVersion, LaunchConfig, LauncherKind, QueuedDownload,
+    SavePath, Settings,
 };
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,47 @@
+use log::{info, warn};
+
+use crate::{launch_config::is_valid_env_key, models::data::Database};
+
+type Migration = fn(&mut Database);
+
+/// Fixes applied to the data after it's been decoded. Changes to a struct's
+/// shape get a new native_model version with a `From` the previous one
+/// instead; these are for what's in it, e.g. values that an older client
+/// could leave in a bad state. The nth migration takes the database from
+/// schema version n to n + 1, so only ever append to this.
+const MIGRATIONS: &[(&str, Migration)] = &[(
+    "drop invalid environment variable names from launch configs",
+    drop_invalid_env_keys,
+)];
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Brings the database up to [`SCHEMA_VERSION`]. Returns whether anything
+/// was applied, i.e. whether it needs saving.
+pub fn run_migrations(db: &mut Database) -> bool {
+    if db.schema_version > SCHEMA_VERSION {
+        warn!(
+            "database is at schema version {}, newer than this client's {SCHEMA_VERSION}",
+            db.schema_version
+        );
+        return false;
+    }
+    let pending = &MIGRATIONS[db.schema_version as usize..];
+    for (name, migration) in pending {
+        info!(
+            "migrating database to schema version {}: {name}",
+            db.schema_version + 1
+        );
+        migration(db);
+        db.schema_version += 1;
+    }
+    !pending.is_empty()
+}
+
+/// Launch configs saved before names were checked can have ones the shell
+/// can't set
+fn drop_invalid_env_keys(db: &mut Database) {
+    for config in db.applications.game_launch_configs.values_mut() {
+        config.env.retain(|key, _| is_valid_env_key(key));
+    }
+}
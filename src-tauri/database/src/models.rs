@@ -4,12 +4,16 @@ pub mod data {
     use native_model::native_model;
     use serde::{Deserialize, Serialize};
 
+    use crate::migrations::SCHEMA_VERSION;
+
     // NOTE: Within each version, you should NEVER use these types.
     // Declare it using the actual version that it is from, i.e. v1::Settings rather than just Settings from here
 
-    pub type GameVersion = v1::GameVersion;
-    pub type Database = v3::Database;
-    pub type Settings = v1::Settings;
+    pub type GameVersion = v4::GameVersion;
+    pub type Database = v4::Database;
+    pub type Settings = v4::Settings;
+    pub type DefaultVersionPolicy = v4::DefaultVersionPolicy;
+    pub type LauncherKind = v4::LauncherKind;
     pub type DatabaseAuth = v1::DatabaseAuth;
 
     pub type GameDownloadStatus = v2::GameDownloadStatus;
@@ -19,10 +23,17 @@ pub mod data {
      */
     pub type DownloadableMetadata = v1::DownloadableMetadata;
     pub type DownloadType = v1::DownloadType;
-    pub type DatabaseApplications = v2::DatabaseApplications;
+    pub type DatabaseApplications = v4::DatabaseApplications;
+    pub type LaunchConfig = v4::LaunchConfig;
+    pub type QueuedDownload = v4::QueuedDownload;
+    pub type DownloadHistoryEntry = v4::DownloadHistoryEntry;
+    pub type BandwidthUsage = v4::BandwidthUsage;
+    pub type GamePlaytime = v4::GamePlaytime;
+    pub type CloudSaveConfig = v4::CloudSaveConfig;
+    pub type SavePath = v4::SavePath;
     // pub type DatabaseCompatInfo = v2::DatabaseCompatInfo;
 
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
 
     impl PartialEq for DownloadableMetadata {
         fn eq(&self, other: &Self) -> bool {
@@ -36,7 +47,7 @@ pub mod data {
         }
     }
 
-    mod v1 {
+    pub(crate) mod v1 {
         use serde_with::serde_as;
         use std::{collections::HashMap, path::PathBuf};
 
@@ -44,7 +55,7 @@ pub mod data {
 
         use super::{Deserialize, Serialize, native_model};
 
-        fn default_template() -> String {
+        pub(super) fn default_template() -> String {
             "{}".to_owned()
         }
 
@@ -331,6 +342,473 @@ pub mod data {
             }
         }
     }
+    mod v4 {
+        use std::{
+            collections::{BTreeMap, HashMap, HashSet},
+            path::PathBuf,
+        };
+
+        use serde_with::serde_as;
+
+        use crate::platform::Platform;
+
+        use super::{Deserialize, Serialize, native_model, v1, v2, v3};
+
+        fn default_speed_window_secs() -> usize {
+            20
+        }
+
+        fn default_io_buffer_size() -> usize {
+            1024 * 1024
+        }
+
+        fn default_max_concurrent_downloads() -> usize {
+            1
+        }
+
+        fn default_download_retry_attempts() -> usize {
+            3
+        }
+
+        fn default_download_retry_delay_ms() -> u64 {
+            1000
+        }
+
+        fn default_gamescope_width() -> u32 {
+            1280
+        }
+
+        fn default_gamescope_height() -> u32 {
+            800
+        }
+
+        fn default_max_game_logs() -> usize {
+            10
+        }
+
+        fn default_object_cache_ttl_hours() -> u64 {
+            24
+        }
+
+        fn default_max_cache_bytes() -> u64 {
+            // 1 GiB
+            1024 * 1024 * 1024
+        }
+
+        fn default_save_compression_level() -> i32 {
+            22
+        }
+
+        fn default_bucket_target_size() -> usize {
+            63 * 1000 * 1000
+        }
+
+        // Every file in a bucket is open while it downloads, and each download
+        // thread has its own bucket, so this times the thread count has to fit
+        // under the fd limit. 1024 is the usual soft limit on Linux, and 4 the
+        // default thread count.
+        fn default_max_files_per_bucket() -> usize {
+            (1024 / 4) - 1
+        }
+
+        fn default_healthcheck_interval_secs() -> u64 {
+            30
+        }
+
+        // Versions are also parsed from the server's JSON, which doesn't have
+        // the fields that are only set locally
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+        #[serde(rename_all = "camelCase")]
+        #[native_model(id = 2, version = 2, with = native_model::rmp_serde_1_3::RmpSerde, from = v1::GameVersion)]
+        pub struct GameVersion {
+            pub game_id: String,
+            pub version_name: String,
+
+            pub platform: Platform,
+
+            pub launch_command: String,
+            pub launch_args: Vec<String>,
+            #[serde(default = "v1::default_template")]
+            pub launch_command_template: String,
+
+            pub setup_command: String,
+            pub setup_args: Vec<String>,
+            #[serde(default = "v1::default_template")]
+            pub setup_command_template: String,
+
+            pub only_setup: bool,
+
+            pub version_index: usize,
+            pub delta: bool,
+
+            pub umu_id_override: Option<String>,
+
+            // Picked by the user when more than one launcher can run the
+            // game. Auto-detected if unset.
+            #[serde(default)]
+            pub preferred_launcher: Option<LauncherKind>,
+            // Launched instead of `launch_command`, relative to the install dir
+            #[serde(default)]
+            pub executable_override: Option<String>,
+        }
+        impl From<v1::GameVersion> for GameVersion {
+            fn from(value: v1::GameVersion) -> Self {
+                Self {
+                    game_id: value.game_id,
+                    version_name: value.version_name,
+                    platform: value.platform,
+                    launch_command: value.launch_command,
+                    launch_args: value.launch_args,
+                    launch_command_template: value.launch_command_template,
+                    setup_command: value.setup_command,
+                    setup_args: value.setup_args,
+                    setup_command_template: value.setup_command_template,
+                    only_setup: value.only_setup,
+                    version_index: value.version_index,
+                    delta: value.delta,
+                    umu_id_override: value.umu_id_override,
+                    preferred_launcher: None,
+                    executable_override: None,
+                }
+            }
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[serde(rename_all = "camelCase")]
+        #[native_model(id = 4, version = 2, with = native_model::rmp_serde_1_3::RmpSerde, from = v1::Settings)]
+        pub struct Settings {
+            pub autostart: bool,
+            pub max_download_threads: usize,
+            pub force_offline: bool,
+            pub speed_window_secs: usize,
+            // Sent with every request to the server, for auth proxies
+            pub extra_headers: HashMap<String, String>,
+            // Bytes read or written at a time when downloading and validating
+            pub io_buffer_size: usize,
+            // Pause downloads while the window is hidden in the tray
+            pub pause_on_tray: bool,
+            // Which version one-click installs pick
+            pub default_version_policy: DefaultVersionPolicy,
+            // How many games download at once, each with its own threads
+            pub max_concurrent_downloads: usize,
+            // Attempts per bucket before a download fails
+            pub download_retry_attempts: usize,
+            // Wait before the first retry, doubled for each one after
+            pub download_retry_delay_ms: u64,
+            // Desktop notification when a download finishes or fails
+            pub notify_on_download_complete: bool,
+            // Bytes requested at a time from the server, per download thread
+            pub bucket_target_size: usize,
+            pub max_files_per_bucket: usize,
+            // Re-check chunks saved as complete before resuming a download
+            pub verify_on_resume: bool,
+            // Wrapper and environment for every game, under each game's own
+            // launch config. Its args and working dir aren't used.
+            pub default_launch_config: LaunchConfig,
+            // Runs Linux and Proton games inside gamescope, when it's installed
+            pub gamescope_enabled: bool,
+            pub gamescope_width: u32,
+            pub gamescope_height: u32,
+            // Shows the running game on the user's Discord profile
+            pub discord_rich_presence: bool,
+            // Launches to keep logs for, per game
+            pub max_game_logs: usize,
+            // Glob patterns left out of every game's save backups
+            pub save_exclude_patterns: Vec<String>,
+            // Packs save backups into a zstd archive rather than a plain tar
+            pub compress_save_backups: bool,
+            pub save_compression_level: i32,
+            // How long fetched objects are cached when the server doesn't say
+            pub object_cache_ttl_hours: u64,
+            // Older cache entries are evicted past this size, 0 for no limit
+            pub max_cache_bytes: u64,
+            // HTTP or SOCKS proxy for every request to the server. Only read
+            // when the clients are built, so changes need a restart.
+            pub proxy_url: Option<String>,
+            // Hosts that skip the proxy, as in `NO_PROXY`
+            pub no_proxy: Vec<String>,
+            // Downloads pause once this much has been downloaded in a month,
+            // 0 for no limit
+            pub monthly_quota_bytes: u64,
+            // Day of the month the quota starts over on
+            pub quota_reset_day: u32,
+            // Opens store links to games in the library on the game's own
+            // page rather than the web store
+            pub native_store_pages: bool,
+            // How often the server is pinged to notice it going down or
+            // coming back
+            pub healthcheck_interval_secs: u64,
+        }
+        impl Default for Settings {
+            fn default() -> Self {
+                v1::Settings::default().into()
+            }
+        }
+        impl From<v1::Settings> for Settings {
+            fn from(value: v1::Settings) -> Self {
+                Self {
+                    autostart: value.autostart,
+                    max_download_threads: value.max_download_threads,
+                    force_offline: value.force_offline,
+                    speed_window_secs: default_speed_window_secs(),
+                    extra_headers: HashMap::new(),
+                    io_buffer_size: default_io_buffer_size(),
+                    pause_on_tray: false,
+                    default_version_policy: DefaultVersionPolicy::default(),
+                    max_concurrent_downloads: default_max_concurrent_downloads(),
+                    download_retry_attempts: default_download_retry_attempts(),
+                    download_retry_delay_ms: default_download_retry_delay_ms(),
+                    notify_on_download_complete: true,
+                    bucket_target_size: default_bucket_target_size(),
+                    max_files_per_bucket: default_max_files_per_bucket(),
+                    verify_on_resume: false,
+                    default_launch_config: LaunchConfig::default(),
+                    gamescope_enabled: false,
+                    gamescope_width: default_gamescope_width(),
+                    gamescope_height: default_gamescope_height(),
+                    discord_rich_presence: false,
+                    max_game_logs: default_max_game_logs(),
+                    save_exclude_patterns: Vec::new(),
+                    compress_save_backups: true,
+                    save_compression_level: default_save_compression_level(),
+                    object_cache_ttl_hours: default_object_cache_ttl_hours(),
+                    max_cache_bytes: default_max_cache_bytes(),
+                    proxy_url: None,
+                    no_proxy: Vec::new(),
+                    monthly_quota_bytes: 0,
+                    quota_reset_day: 1,
+                    native_store_pages: false,
+                    healthcheck_interval_secs: default_healthcheck_interval_secs(),
+                }
+            }
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub enum DefaultVersionPolicy {
+            #[default]
+            Latest,
+            /// Skips versions named like pre-releases (beta, rc, ...)
+            LatestStable,
+        }
+
+        /// The ways Drop knows to start a game's process
+        #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum LauncherKind {
+            Native,
+            #[serde(rename = "UMU")]
+            Umu,
+            AsahiMuvm,
+            Gamescope,
+        }
+
+        /// Launch options a user set for a game, turned into the version's
+        /// launch command template. `{}` in that template is the game's own
+        /// launch command.
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+        #[serde(rename_all = "camelCase")]
+        pub struct LaunchConfig {
+            // Runs the whole launch command through another program, e.g.
+            // gamemoderun. Falls back to the default in `Settings` if unset.
+            #[serde(default)]
+            pub wrapper: Option<String>,
+            // Appended after the game's own arguments
+            #[serde(default)]
+            pub args: Vec<String>,
+            // Set on top of the global ones in `Settings`, which these take
+            // precedence over
+            #[serde(default)]
+            pub env: BTreeMap<String, String>,
+            // Relative to the install dir
+            #[serde(default)]
+            pub working_dir: Option<String>,
+        }
+
+        /// Time spent playing a game, added up over every session
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+        #[serde(rename_all = "camelCase")]
+        pub struct GamePlaytime {
+            pub playtime_seconds: u64,
+            // Unix timestamp, in seconds, of when the last counted session ended
+            pub last_played: Option<i64>,
+        }
+
+        /// Where a game keeps its saves, and when they're backed up
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+        #[serde(rename_all = "camelCase")]
+        pub struct CloudSaveConfig {
+            #[serde(default)]
+            pub paths: Vec<SavePath>,
+            // Restore the latest backup before the game starts
+            #[serde(default)]
+            pub sync_on_launch: bool,
+            // Back the saves up after the game exits cleanly
+            #[serde(default)]
+            pub sync_on_exit: bool,
+            // Glob patterns, relative to each save path, to leave out of backups
+            #[serde(default)]
+            pub exclude: Vec<String>,
+        }
+
+        /// A save location, which can use placeholders like `<winAppData>`
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+        #[serde(rename_all = "camelCase")]
+        pub struct SavePath {
+            pub path: String,
+            // The platform the path is for
+            pub platform: Platform,
+        }
+
+        /// A download that was waiting in the queue, saved so it can be
+        /// queued again after a restart
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        #[serde(rename_all = "camelCase")]
+        pub struct QueuedDownload {
+            pub meta: v1::DownloadableMetadata,
+            // The install dir the game is going into, not the game's own folder
+            pub install_dir: PathBuf,
+            pub paused: bool,
+        }
+
+        #[serde_as]
+        #[derive(Serialize, Clone, Deserialize, Default)]
+        #[serde(rename_all = "camelCase")]
+        #[native_model(id = 3, version = 3, with = native_model::rmp_serde_1_3::RmpSerde, from = v2::DatabaseApplications)]
+        pub struct DatabaseApplications {
+            pub install_dirs: Vec<PathBuf>,
+            // Guaranteed to exist if the game also exists in the app state map
+            pub game_statuses: HashMap<String, v2::GameDownloadStatus>,
+
+            pub game_versions: HashMap<String, HashMap<String, GameVersion>>,
+            pub installed_game_version: HashMap<String, v1::DownloadableMetadata>,
+
+            // Local-only, never touched by library refreshes
+            pub game_notes: HashMap<String, String>,
+            // Unix timestamp of when each game first showed up in the library
+            pub game_first_seen: HashMap<String, u64>,
+            // Optional soft limit, in bytes, on what Drop installs into each dir
+            pub install_dir_quotas: HashMap<PathBuf, u64>,
+            // Folder each game is installed into, when it isn't the game id
+            pub install_folder_names: HashMap<String, String>,
+            pub game_launch_configs: HashMap<String, LaunchConfig>,
+            // Optional manifest components each game was installed without
+            pub game_excluded_components: HashMap<String, Vec<String>>,
+            // The download queue in order, as of the last queue update
+            pub download_queue: Vec<QueuedDownload>,
+            pub game_playtime: HashMap<String, GamePlaytime>,
+            // Wine prefix for each game that doesn't use its own under pfx/
+            pub game_prefix_overrides: HashMap<String, PathBuf>,
+            pub game_cloud_saves: HashMap<String, CloudSaveConfig>,
+            // Listed first in the library
+            pub favorites: HashSet<String>,
+            // Left out of the library unless asked for. Only ever games that
+            // aren't installed.
+            pub hidden_games: HashSet<String>,
+            // Local labels for organising the library, never sent to the server
+            pub game_tags: HashMap<String, Vec<String>>,
+
+            // Never saved, so nothing in progress when Drop exits or crashes
+            // can outlive it. Interrupted downloads are picked up again from
+            // `game_statuses` and `download_queue` instead.
+            #[serde(skip)]
+            pub transient_statuses:
+                HashMap<v1::DownloadableMetadata, v1::ApplicationTransientStatus>,
+        }
+        impl From<v2::DatabaseApplications> for DatabaseApplications {
+            fn from(value: v2::DatabaseApplications) -> Self {
+                Self {
+                    install_dirs: value.install_dirs,
+                    game_statuses: value.game_statuses,
+                    game_versions: value
+                        .game_versions
+                        .into_iter()
+                        .map(|(game_id, versions)| {
+                            let versions = versions
+                                .into_iter()
+                                .map(|(name, version)| (name, version.into()))
+                                .collect();
+                            (game_id, versions)
+                        })
+                        .collect(),
+                    installed_game_version: value.installed_game_version,
+                    game_notes: HashMap::new(),
+                    game_first_seen: HashMap::new(),
+                    install_dir_quotas: HashMap::new(),
+                    install_folder_names: HashMap::new(),
+                    game_launch_configs: HashMap::new(),
+                    game_excluded_components: HashMap::new(),
+                    download_queue: Vec::new(),
+                    game_playtime: HashMap::new(),
+                    game_prefix_overrides: HashMap::new(),
+                    game_cloud_saves: HashMap::new(),
+                    favorites: HashSet::new(),
+                    hidden_games: HashSet::new(),
+                    game_tags: HashMap::new(),
+                    transient_statuses: value.transient_statuses,
+                }
+            }
+        }
+
+        #[native_model(id = 1, version = 4, with = native_model::rmp_serde_1_3::RmpSerde, from = v3::Database)]
+        #[derive(Serialize, Deserialize, Clone, Default)]
+        pub struct Database {
+            #[serde(default)]
+            pub settings: Settings,
+            pub auth: Option<v1::DatabaseAuth>,
+            pub base_url: String,
+            pub applications: DatabaseApplications,
+            #[serde(skip)]
+            pub prev_database: Option<PathBuf>,
+            pub cache_dir: PathBuf,
+            pub compat_info: Option<v2::DatabaseCompatInfo>,
+            pub download_history: Vec<DownloadHistoryEntry>,
+            // Version the server reported when we connected, if it did
+            pub server_version: Option<String>,
+            // How many of `migrations::MIGRATIONS` have been applied
+            pub schema_version: u32,
+            pub bandwidth_usage: BandwidthUsage,
+        }
+
+        /// A finished download, kept so users can see how much they've
+        /// downloaded over time
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[serde(rename_all = "camelCase")]
+        pub struct DownloadHistoryEntry {
+            pub game_id: String,
+            pub version: String,
+            pub bytes_downloaded: u64,
+            pub duration_secs: u64,
+            // Unix timestamp, in seconds
+            pub completed_at: i64,
+        }
+
+        /// Bytes downloaded towards the monthly quota
+        #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+        #[serde(rename_all = "camelCase")]
+        pub struct BandwidthUsage {
+            pub bytes: u64,
+            // First day of the period `bytes` was counted in, as YYYY-MM-DD
+            pub period_start: String,
+        }
+
+        impl From<v3::Database> for Database {
+            fn from(value: v3::Database) -> Self {
+                Self {
+                    settings: value.settings.into(),
+                    auth: value.auth,
+                    base_url: value.base_url,
+                    applications: value.applications.into(),
+                    prev_database: value.prev_database,
+                    cache_dir: value.cache_dir,
+                    compat_info: value.compat_info,
+                    download_history: Vec::new(),
+                    server_version: None,
+                    // Counted from before any migration, so they all run
+                    schema_version: 0,
+                    bandwidth_usage: BandwidthUsage::default(),
+                }
+            }
+        }
+    }
 
     impl Database {
         pub fn new<T: Into<PathBuf>>(
@@ -344,6 +822,19 @@ pub mod data {
                     game_statuses: HashMap::new(),
                     game_versions: HashMap::new(),
                     installed_game_version: HashMap::new(),
+                    game_notes: HashMap::new(),
+                    game_first_seen: HashMap::new(),
+                    install_dir_quotas: HashMap::new(),
+                    install_folder_names: HashMap::new(),
+                    game_launch_configs: HashMap::new(),
+                    game_excluded_components: HashMap::new(),
+                    download_queue: Vec::new(),
+                    game_playtime: HashMap::new(),
+                    game_prefix_overrides: HashMap::new(),
+                    game_cloud_saves: HashMap::new(),
+                    favorites: HashSet::new(),
+                    hidden_games: HashSet::new(),
+                    game_tags: HashMap::new(),
                     transient_statuses: HashMap::new(),
                 },
                 prev_database,
@@ -352,6 +843,10 @@ pub mod data {
                 settings: Settings::default(),
                 cache_dir,
                 compat_info: None,
+                download_history: Vec::new(),
+                server_version: None,
+                schema_version: SCHEMA_VERSION,
+                bandwidth_usage: BandwidthUsage::default(),
             }
         }
     }
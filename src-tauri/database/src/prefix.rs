@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+use crate::{Database, db::DATA_ROOT_DIR};
+
+/// The Wine prefix `game_id` runs in. Each game gets its own under `pfx/`
+/// unless the user picked another.
+pub fn game_prefix_dir(db: &Database, game_id: &str) -> PathBuf {
+    match db.applications.game_prefix_overrides.get(game_id) {
+        Some(prefix) => prefix.clone(),
+        None => DATA_ROOT_DIR.join("pfx").join(game_id),
+    }
+}
+
+/// The other installed games that run in the same Wine prefix as `game_id`
+pub fn games_sharing_prefix(db: &Database, game_id: &str) -> Vec<String> {
+    let prefix = game_prefix_dir(db, game_id);
+    db.applications
+        .installed_game_version
+        .keys()
+        .filter(|other| *other != game_id && game_prefix_dir(db, other) == prefix)
+        .cloned()
+        .collect()
+}
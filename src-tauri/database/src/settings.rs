@@ -0,0 +1,232 @@
+use std::{
+    fmt::{Display, Formatter},
+    ops::RangeInclusive,
+    thread::available_parallelism,
+};
+
+use serde_with::SerializeDisplay;
+use url::Url;
+
+use crate::{Settings, launch_config::is_valid_env_key};
+
+pub const MIN_IO_BUFFER_SIZE: usize = 4 * 1024;
+pub const MAX_IO_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+/// Smallest cache limit that isn't 0 (no limit), below which hardly anything
+/// would stay cached
+pub const MIN_CACHE_BYTES: u64 = 16 * 1024 * 1024;
+const MAX_CONCURRENT_DOWNLOADS: usize = 8;
+const MAX_SPEED_WINDOW_SECS: usize = 60 * 60;
+/// Smaller buckets cost more in requests than they save
+const MIN_BUCKET_TARGET_SIZE: usize = 1000 * 1000;
+const MAX_BUCKET_TARGET_SIZE: usize = 1024 * 1024 * 1024;
+const SAVE_COMPRESSION_LEVELS: RangeInclusive<i32> = 1..=22;
+// Every month has these days
+const QUOTA_RESET_DAYS: RangeInclusive<u32> = 1..=28;
+const HEALTHCHECK_INTERVAL_SECS: RangeInclusive<u64> = 5..=60 * 60;
+
+/// Download threads can go up to twice the CPU count
+pub fn max_download_threads() -> usize {
+    available_parallelism().map(|n| n.get()).unwrap_or(4) * 2
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidSetting {
+    // As the frontend knows it, in camelCase
+    pub field: &'static str,
+    pub reason: String,
+}
+
+#[derive(Debug, SerializeDisplay)]
+pub enum SettingsError {
+    Unparseable(String),
+    Invalid(Vec<InvalidSetting>),
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Unparseable(error) => write!(f, "could not parse settings: {error}"),
+            SettingsError::Invalid(invalid) => {
+                write!(f, "invalid settings: ")?;
+                for (i, setting) in invalid.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} {}", setting.field, setting.reason)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+fn check_range<T: PartialOrd + Copy + Display>(
+    field: &'static str,
+    value: &mut T,
+    range: RangeInclusive<T>,
+    clamp: bool,
+    invalid: &mut Vec<InvalidSetting>,
+) {
+    if range.contains(value) {
+        return;
+    }
+    invalid.push(InvalidSetting {
+        field,
+        reason: format!(
+            "must be between {} and {}, not {value}",
+            range.start(),
+            range.end()
+        ),
+    });
+    if clamp {
+        *value = if *value < *range.start() {
+            *range.start()
+        } else {
+            *range.end()
+        };
+    }
+}
+
+impl Settings {
+    /// Checks every value is one the rest of Drop can work with
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let invalid = self.clone().check(false);
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid(invalid))
+        }
+    }
+
+    /// Pulls invalid values back into range, e.g. ones saved before they
+    /// were validated. Returns what had to change.
+    pub fn clamp(&mut self) -> Vec<InvalidSetting> {
+        self.check(true)
+    }
+
+    fn check(&mut self, clamp: bool) -> Vec<InvalidSetting> {
+        let mut invalid = Vec::new();
+        check_range(
+            "maxDownloadThreads",
+            &mut self.max_download_threads,
+            1..=max_download_threads(),
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "maxConcurrentDownloads",
+            &mut self.max_concurrent_downloads,
+            1..=MAX_CONCURRENT_DOWNLOADS,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "speedWindowSecs",
+            &mut self.speed_window_secs,
+            1..=MAX_SPEED_WINDOW_SECS,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "ioBufferSize",
+            &mut self.io_buffer_size,
+            MIN_IO_BUFFER_SIZE..=MAX_IO_BUFFER_SIZE,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "downloadRetryAttempts",
+            &mut self.download_retry_attempts,
+            1..=usize::MAX,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "bucketTargetSize",
+            &mut self.bucket_target_size,
+            MIN_BUCKET_TARGET_SIZE..=MAX_BUCKET_TARGET_SIZE,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "maxFilesPerBucket",
+            &mut self.max_files_per_bucket,
+            1..=usize::MAX,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "gamescopeWidth",
+            &mut self.gamescope_width,
+            1..=u32::MAX,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "gamescopeHeight",
+            &mut self.gamescope_height,
+            1..=u32::MAX,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "saveCompressionLevel",
+            &mut self.save_compression_level,
+            SAVE_COMPRESSION_LEVELS,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "quotaResetDay",
+            &mut self.quota_reset_day,
+            QUOTA_RESET_DAYS,
+            clamp,
+            &mut invalid,
+        );
+        check_range(
+            "healthcheckIntervalSecs",
+            &mut self.healthcheck_interval_secs,
+            HEALTHCHECK_INTERVAL_SECS,
+            clamp,
+            &mut invalid,
+        );
+        // 0 turns the limit off
+        if self.max_cache_bytes != 0 {
+            check_range(
+                "maxCacheBytes",
+                &mut self.max_cache_bytes,
+                MIN_CACHE_BYTES..=u64::MAX,
+                clamp,
+                &mut invalid,
+            );
+        }
+        if let Some(key) = self.default_launch_config.invalid_env_key() {
+            invalid.push(InvalidSetting {
+                field: "defaultLaunchConfig",
+                reason: format!("has an invalid environment variable name {key:?}"),
+            });
+            if clamp {
+                self.default_launch_config
+                    .env
+                    .retain(|key, _| is_valid_env_key(key));
+            }
+        }
+        let proxy_error = self
+            .proxy_url
+            .as_ref()
+            .filter(|url| !url.trim().is_empty())
+            .and_then(|url| Url::parse(url.trim()).err());
+        if let Some(e) = proxy_error {
+            invalid.push(InvalidSetting {
+                field: "proxyUrl",
+                reason: format!("is not a valid URL: {e}"),
+            });
+            if clamp {
+                self.proxy_url = None;
+            }
+        }
+        invalid
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use chrono::NaiveDate;
+use rustbreak::DeSerializer;
+
+use crate::{
+    ApplicationTransientStatus, BandwidthUsage, Database, DownloadHistoryEntry, DownloadType,
+    DownloadableMetadata, LaunchConfig, Settings,
+    bandwidth::period_start,
+    db::DropDatabaseSerializer,
+    download_history::{MAX_DOWNLOAD_HISTORY, record_download},
+    interface::{read_database, write_database},
+    launch_config::{escape_cmd, is_valid_env_key},
+    migrations::{SCHEMA_VERSION, run_migrations},
+    models::data::v1,
+    platform::Platform,
+    settings::{MIN_CACHE_BYTES, SettingsError},
+};
+
+#[test]
+fn migrates_v1_database_to_current() {
+    let mut fixture = v1::Database {
+        base_url: "https://drop.example.com/".to_string(),
+        ..Default::default()
+    };
+    fixture.settings.max_download_threads = 8;
+    fixture.applications.install_dirs = vec![PathBuf::from("/games")];
+    fixture.applications.game_versions.insert(
+        "game".to_string(),
+        HashMap::from([(
+            "1.0".to_string(),
+            v1::GameVersion {
+                game_id: "game".to_string(),
+                version_name: "1.0".to_string(),
+                platform: Platform::Linux,
+                launch_command: "game.sh".to_string(),
+                launch_args: Vec::new(),
+                launch_command_template: "{}".to_string(),
+                setup_command: String::new(),
+                setup_args: Vec::new(),
+                setup_command_template: "{}".to_string(),
+                only_setup: false,
+                version_index: 0,
+                delta: false,
+                umu_id_override: None,
+            },
+        )]),
+    );
+    let encoded = native_model::encode(&fixture).unwrap();
+
+    let mut db: Database = DropDatabaseSerializer.deserialize(&encoded[..]).unwrap();
+    assert_eq!(db.base_url, "https://drop.example.com/");
+    assert_eq!(db.applications.install_dirs, vec![PathBuf::from("/games")]);
+    assert_eq!(db.settings.max_download_threads, 8);
+    assert_eq!(
+        db.settings.max_concurrent_downloads,
+        Settings::default().max_concurrent_downloads
+    );
+    let version = &db.applications.game_versions["game"]["1.0"];
+    assert_eq!(version.launch_command, "game.sh");
+    assert!(version.preferred_launcher.is_none());
+    assert!(db.applications.download_queue.is_empty());
+    assert_eq!(db.schema_version, 0);
+
+    run_migrations(&mut db);
+    assert_eq!(db.schema_version, SCHEMA_VERSION);
+
+    // Already current, so there's nothing left to apply
+    assert!(!run_migrations(&mut db));
+}
+
+#[test]
+fn clamps_invalid_settings() {
+    assert!(Settings::default().validate().is_ok());
+
+    let mut settings = Settings {
+        max_download_threads: 0,
+        max_cache_bytes: 1,
+        proxy_url: Some("not a url".to_string()),
+        ..Default::default()
+    };
+    let Err(SettingsError::Invalid(invalid)) = settings.validate() else {
+        panic!("expected invalid settings");
+    };
+    let fields = invalid.iter().map(|s| s.field).collect::<Vec<_>>();
+    assert_eq!(fields, ["maxDownloadThreads", "maxCacheBytes", "proxyUrl"]);
+
+    assert_eq!(settings.clamp().len(), 3);
+    assert_eq!(settings.max_download_threads, 1);
+    assert_eq!(settings.max_cache_bytes, MIN_CACHE_BYTES);
+    assert_eq!(settings.proxy_url, None);
+    assert!(settings.validate().is_ok());
+}
+
+#[test]
+fn transient_statuses_are_not_saved() {
+    let mut db = Database::new("/games", None, PathBuf::from("/cache"));
+    db.applications.transient_statuses.insert(
+        DownloadableMetadata::new("game".to_string(), None, DownloadType::Game),
+        ApplicationTransientStatus::Downloading {
+            version_name: "1.0".to_string(),
+        },
+    );
+
+    // As if Drop crashed mid-download and started again
+    let saved = DropDatabaseSerializer.serialize(&db).unwrap();
+    let loaded: Database = DropDatabaseSerializer.deserialize(&saved[..]).unwrap();
+    assert!(loaded.applications.transient_statuses.is_empty());
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn quota_period_starts_on_reset_day() {
+    assert_eq!(period_start(date(2026, 3, 15), 10), date(2026, 3, 10));
+    assert_eq!(period_start(date(2026, 3, 10), 10), date(2026, 3, 10));
+    assert_eq!(period_start(date(2026, 3, 9), 10), date(2026, 2, 10));
+    assert_eq!(period_start(date(2026, 1, 5), 10), date(2025, 12, 10));
+}
+
+#[test]
+fn bandwidth_usage_starts_over_each_period() {
+    let mut usage = BandwidthUsage::default();
+    usage.add(100, date(2026, 3, 15), 10);
+    usage.add(50, date(2026, 4, 9), 10);
+    assert_eq!(usage.current(date(2026, 4, 9), 10), 150);
+    assert_eq!(usage.current(date(2026, 4, 10), 10), 0);
+
+    usage.add(25, date(2026, 4, 10), 10);
+    assert_eq!(usage.current(date(2026, 4, 10), 10), 25);
+}
+
+#[test]
+fn download_history_drops_the_oldest_entries() {
+    let mut db = Database::new("/games", None, PathBuf::from("/cache"));
+    for completed_at in 0..MAX_DOWNLOAD_HISTORY as i64 + 5 {
+        record_download(
+            &mut db,
+            DownloadHistoryEntry {
+                game_id: "game".to_string(),
+                version: "1.0".to_string(),
+                bytes_downloaded: 1,
+                duration_secs: 1,
+                completed_at,
+            },
+        );
+    }
+    assert_eq!(db.download_history.len(), MAX_DOWNLOAD_HISTORY);
+    assert_eq!(db.download_history[0].completed_at, 5);
+}
+
+#[test]
+fn exported_database_imports_the_same() {
+    let mut db = Database::new("/games", None, PathBuf::from("/cache"));
+    db.base_url = "https://drop.example.com/".to_string();
+    db.settings.max_download_threads = 8;
+    db.applications
+        .install_dirs
+        .push(PathBuf::from("/more-games"));
+
+    let path = std::env::temp_dir().join("drop-database-test-export.db");
+    write_database(&db, &path).unwrap();
+    let imported = read_database(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(imported.base_url, db.base_url);
+    assert_eq!(imported.settings.max_download_threads, 8);
+    assert_eq!(
+        imported.applications.install_dirs,
+        db.applications.install_dirs
+    );
+    assert_eq!(imported.schema_version, SCHEMA_VERSION);
+    assert!(imported.prev_database.is_none());
+}
+
+#[test]
+fn launch_config_rejects_invalid_env_keys() {
+    for key in ["DXVK_HUD", "_private", "__GL_THREADED_OPTIMIZATIONS", "a1"] {
+        assert!(is_valid_env_key(key), "{key}");
+    }
+    for key in ["", "1ST", "WITH SPACE", "A-B", "A=B", "$(reboot)"] {
+        assert!(!is_valid_env_key(key), "{key}");
+    }
+
+    let mut config = LaunchConfig::default();
+    config.env.insert("DXVK_HUD".to_string(), "fps".to_string());
+    assert_eq!(config.invalid_env_key(), None);
+    config.env.insert("X; rm -rf ~".to_string(), String::new());
+    assert_eq!(config.invalid_env_key(), Some("X; rm -rf ~"));
+    // Left out of the template rather than breaking it
+    assert!(!config.template().contains("rm -rf"));
+}
+
+#[test]
+fn launch_config_escapes_env_values() {
+    assert_eq!(escape_cmd("fps"), "fps");
+    assert_eq!(
+        escape_cmd(r#"a & "b" | 100% (c)"#),
+        r#"a ^& ^"b^" ^| 100^% ^(c^)"#
+    );
+
+    let mut config = LaunchConfig::default();
+    config
+        .env
+        .insert("DXVK_HUD".to_string(), "it's & more".to_string());
+    #[cfg(not(target_os = "windows"))]
+    assert_eq!(config.template(), r#"DXVK_HUD='it'\''s & more' {}"#);
+    #[cfg(target_os = "windows")]
+    assert_eq!(config.template(), "set DXVK_HUD=it's ^& more&& {}");
+}
+
+#[test]
+fn game_wrapper_replaces_the_default() {
+    let defaults = LaunchConfig {
+        wrapper: Some("gamemoderun".to_string()),
+        ..Default::default()
+    };
+    let mut config = LaunchConfig::default();
+    assert_eq!(config.merged_wrapper(&defaults), Some("gamemoderun"));
+
+    config.wrapper = Some("  ".to_string());
+    assert_eq!(config.merged_wrapper(&defaults), Some("gamemoderun"));
+
+    config.wrapper = Some(" mangohud ".to_string());
+    assert_eq!(config.merged_wrapper(&defaults), Some("mangohud"));
+}
+
+#[test]
+fn migrations_drop_invalid_env_keys() {
+    let mut db = Database::new("/games", None, PathBuf::from("/cache"));
+    db.schema_version = 0;
+    let mut config = LaunchConfig::default();
+    config.env.insert("DXVK_HUD".to_string(), "fps".to_string());
+    config.env.insert("NOT VALID".to_string(), "x".to_string());
+    db.applications
+        .game_launch_configs
+        .insert("game".to_string(), config);
+
+    assert!(run_migrations(&mut db));
+    let env = &db.applications.game_launch_configs["game"].env;
+    assert_eq!(env.keys().collect::<Vec<_>>(), ["DXVK_HUD"]);
+}
@@ -0,0 +1,39 @@
+use database::DownloadableMetadata;
+use serde::Serialize;
+
+use crate::{
+    download_manager_frontend::DownloadStatus,
+    util::download_thread_control_flag::DownloadThreadControlFlag,
+};
+
+/// Internals of a single `Downloadable`. Fields are `None` if they were
+/// locked by a running download when the snapshot was taken.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DownloadableDebugState {
+    pub completed_contexts: Option<usize>,
+    pub total_contexts: Option<usize>,
+    pub buckets: Option<usize>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AgentDebugState {
+    pub meta: DownloadableMetadata,
+    pub status: DownloadStatus,
+    pub control_flag: DownloadThreadControlFlag,
+    /// False means the agent is in the registry but not the queue
+    pub in_queue: bool,
+    pub state: DownloadableDebugState,
+}
+
+/// Snapshot of the download manager, for diagnosing desyncs between the
+/// queue and the agent registry
+#[derive(Serialize, Clone, Debug)]
+pub struct DownloadManagerDebugState {
+    pub status: String,
+    pub queue: Vec<DownloadableMetadata>,
+    /// Queue entries that have no agent in the registry
+    pub orphaned_queue_entries: Vec<DownloadableMetadata>,
+    pub agents: Vec<AgentDebugState>,
+    /// Downloads that have a thread running, or stopped and not yet reaped
+    pub active_downloads: Vec<DownloadableMetadata>,
+}
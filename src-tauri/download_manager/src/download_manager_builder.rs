@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{
         Arc, Mutex,
         mpsc::{Receiver, Sender, channel},
@@ -7,12 +7,16 @@ use std::{
     thread::{JoinHandle, spawn},
 };
 
-use database::DownloadableMetadata;
+use database::{
+    DownloadableMetadata, QueuedDownload, borrow_db_checked, borrow_db_mut_checked,
+    download_history::record_download,
+};
 use log::{debug, error, info, warn};
 use tauri::AppHandle;
 use utils::{app_emit, lock, send};
 
 use crate::{
+    debug_state::{AgentDebugState, DownloadManagerDebugState},
     download_manager_frontend::DownloadStatus,
     error::ApplicationDownloadError,
     frontend_updates::{QueueUpdateEvent, QueueUpdateEventQueueData, StatsUpdateEvent},
@@ -22,9 +26,12 @@ use super::{
     download_manager_frontend::{DownloadManager, DownloadManagerSignal, DownloadManagerStatus},
     downloadable::Downloadable,
     util::{
+        bandwidth,
         download_thread_control_flag::{DownloadThreadControl, DownloadThreadControlFlag},
-        progress_object::ProgressObject,
+        notification::show_notification,
+        progress_object::{HISTORY_SIZE, ProgressObject, combined_stats},
         queue::Queue,
+        rolling_progress_updates::RollingProgressWindow,
     },
 };
 
@@ -36,8 +43,9 @@ pub type CurrentProgressObject = Arc<Mutex<Option<Arc<ProgressObject>>>>;
 Welcome to the download manager, the most overengineered, glorious piece of bullshit.
 
 The download manager takes a queue of ids and their associated
-DownloadAgents, and then, one-by-one, executes them. It provides an interface
-to interact with the currently downloading agent, and manage the queue.
+DownloadAgents, and then executes them, up to max_concurrent_downloads at a
+time from the front of the queue. It provides an interface to interact with
+the currently downloading agents, and manage the queue.
 
 When the DownloadManager is initialised, it is designed to provide a reference
 which can be used to provide some instructions (the DownloadManagerInterface),
@@ -77,9 +85,18 @@ pub struct DownloadManagerBuilder {
     status: Arc<Mutex<DownloadManagerStatus>>,
     app_handle: AppHandle,
 
-    current_download_thread: Mutex<Option<JoinHandle<()>>>,
-    active_control_flag: Option<DownloadThreadControl>,
+    active_downloads: HashMap<DownloadableMetadata, ActiveDownload>,
+    /// The combined speed at every UI update over the last minute
+    speed_history: RollingProgressWindow,
+    // Saved downloads that couldn't be rebuilt yet, kept in the saved queue
+    unrestored: Vec<QueuedDownload>,
+}
+
+struct ActiveDownload {
+    thread: JoinHandle<()>,
+    control_flag: DownloadThreadControl,
 }
+
 impl DownloadManagerBuilder {
     pub fn build(app_handle: AppHandle) -> DownloadManager {
         let queue = Queue::new();
@@ -96,8 +113,9 @@ impl DownloadManagerBuilder {
             progress: active_progress.clone(),
             app_handle,
 
-            current_download_thread: Mutex::new(None),
-            active_control_flag: None,
+            active_downloads: HashMap::new(),
+            speed_history: RollingProgressWindow::new(HISTORY_SIZE),
+            unrestored: Vec::new(),
         };
 
         let terminator = spawn(|| manager.manage_queue());
@@ -109,41 +127,74 @@ impl DownloadManagerBuilder {
         *lock!(self.status) = status;
     }
 
-    fn remove_and_cleanup_front_download(&mut self, meta: &DownloadableMetadata) -> DownloadAgent {
-        self.download_queue.pop_front();
-        let download_agent = self.download_agent_registry.remove(meta).unwrap();
-        self.cleanup_current_download();
+    fn remove_and_cleanup_download(
+        &mut self,
+        meta: &DownloadableMetadata,
+    ) -> Option<DownloadAgent> {
+        if let Some(index) = self.download_queue.get_by_meta(meta) {
+            let _ = self.download_queue.edit().remove(index);
+        }
+        let download_agent = self.download_agent_registry.remove(meta);
+        self.cleanup_download(meta);
         download_agent
     }
 
     // CAREFUL WITH THIS FUNCTION
     // Make sure the download thread is terminated
-    fn cleanup_current_download(&mut self) {
-        self.active_control_flag = None;
-        *lock!(self.progress) = None;
-
-        let mut download_thread_lock = lock!(self.current_download_thread);
-
-        if let Some(unfinished_thread) = download_thread_lock.take()
-            && !unfinished_thread.is_finished()
+    fn cleanup_download(&mut self, meta: &DownloadableMetadata) {
+        if let Some(active_download) = self.active_downloads.remove(meta)
+            && !active_download.thread.is_finished()
         {
-            unfinished_thread.join().unwrap();
+            active_download.thread.join().unwrap();
+        }
+
+        if self.active_downloads.is_empty() {
+            *lock!(self.progress) = None;
         }
-        drop(download_thread_lock);
     }
 
-    fn stop_and_wait_current_download(&self) -> bool {
+    fn stop_and_wait_download(&mut self, meta: &DownloadableMetadata) -> bool {
+        let Some(active_download) = self.active_downloads.remove(meta) else {
+            return true;
+        };
+        active_download
+            .control_flag
+            .set(DownloadThreadControlFlag::Stop);
+        active_download.thread.join().is_ok()
+    }
+
+    fn stop_and_wait_all_downloads(&mut self) -> bool {
         self.set_status(DownloadManagerStatus::Paused);
-        if let Some(current_flag) = &self.active_control_flag {
-            current_flag.set(DownloadThreadControlFlag::Stop);
+        // Stop everything first, so the downloads wind down together
+        for active_download in self.active_downloads.values() {
+            active_download
+                .control_flag
+                .set(DownloadThreadControlFlag::Stop);
         }
 
-        let mut download_thread_lock = lock!(self.current_download_thread);
-        if let Some(current_download_thread) = download_thread_lock.take() {
-            return current_download_thread.join().is_ok();
-        };
+        self.active_downloads
+            .drain()
+            .fold(true, |all_ok, (_, active_download)| {
+                active_download.thread.join().is_ok() && all_ok
+            })
+    }
 
-        true
+    /// Forgets threads that exited because their download was stopped, so
+    /// the next Go can start them again. Threads that exited for any other
+    /// reason are waiting on their Completed or Error signal.
+    fn reap_stopped_downloads(&mut self) {
+        let stopped = self
+            .active_downloads
+            .iter()
+            .filter(|(_, active_download)| {
+                active_download.thread.is_finished()
+                    && active_download.control_flag.get() == DownloadThreadControlFlag::Stop
+            })
+            .map(|(meta, _)| meta.clone())
+            .collect::<Vec<DownloadableMetadata>>();
+        for meta in stopped {
+            self.cleanup_download(&meta);
+        }
     }
 
     fn manage_queue(mut self) -> Result<(), ()> {
@@ -157,35 +208,57 @@ impl DownloadManagerBuilder {
                 DownloadManagerSignal::Go => {
                     self.manage_go_signal();
                 }
-                DownloadManagerSignal::Stop => {
-                    self.manage_stop_signal();
+                DownloadManagerSignal::Stop(reply) => {
+                    self.manage_stop_signal(reply);
                 }
                 DownloadManagerSignal::Completed(meta) => {
                     self.manage_completed_signal(meta);
                 }
                 DownloadManagerSignal::Queue(download_agent) => {
-                    self.manage_queue_signal(download_agent);
+                    self.manage_queue_signal(download_agent, false);
+                }
+                DownloadManagerSignal::QueuePaused(download_agent) => {
+                    self.manage_queue_signal(download_agent, true);
+                }
+                DownloadManagerSignal::KeepUnrestored(queued) => {
+                    self.manage_keep_unrestored_signal(queued);
                 }
-                DownloadManagerSignal::Error(e) => {
-                    self.manage_error_signal(e);
+                DownloadManagerSignal::Error(meta, e) => {
+                    self.manage_error_signal(meta, e);
                 }
                 DownloadManagerSignal::UpdateUIQueue => {
                     self.push_ui_queue_update();
                 }
-                DownloadManagerSignal::UpdateUIStats(kbs, time) => {
-                    self.push_ui_stats_update(kbs, time);
+                DownloadManagerSignal::UpdateUIStats => {
+                    self.push_ui_stats_update();
                 }
                 DownloadManagerSignal::Finish => {
-                    self.stop_and_wait_current_download();
+                    self.stop_and_wait_all_downloads();
+                    bandwidth::save();
                     return Ok(());
                 }
                 DownloadManagerSignal::Cancel(meta) => {
                     self.manage_cancel_signal(&meta);
                 }
+                DownloadManagerSignal::CancelQueued(reply) => {
+                    self.manage_cancel_queued_signal(reply);
+                }
+                DownloadManagerSignal::Pause(meta) => {
+                    self.manage_pause_signal(&meta);
+                }
+                DownloadManagerSignal::QuotaExceeded => {
+                    self.manage_quota_exceeded_signal();
+                }
+                DownloadManagerSignal::Resume(meta) => {
+                    self.manage_resume_signal(&meta);
+                }
+                DownloadManagerSignal::DumpState(reply) => {
+                    self.manage_dump_state_signal(reply);
+                }
             }
         }
     }
-    fn manage_queue_signal(&mut self, download_agent: DownloadAgent) {
+    fn manage_queue_signal(&mut self, download_agent: DownloadAgent, paused: bool) {
         debug!("got signal Queue");
         let meta = download_agent.metadata();
 
@@ -196,12 +269,27 @@ impl DownloadManagerBuilder {
             return;
         }
 
-        download_agent.on_queued(&self.app_handle);
+        if paused {
+            download_agent.on_paused(&self.app_handle);
+        } else {
+            download_agent.on_queued(&self.app_handle);
+        }
+        self.unrestored.retain(|queued| queued.meta != meta);
         self.download_queue.append(meta.clone());
         self.download_agent_registry.insert(meta, download_agent);
 
         send!(self.sender, DownloadManagerSignal::UpdateUIQueue);
     }
+    fn manage_keep_unrestored_signal(&mut self, queued: QueuedDownload) {
+        debug!("got signal KeepUnrestored");
+
+        if self.download_queue.exists(queued.meta.clone())
+            || self.unrestored.iter().any(|q| q.meta == queued.meta)
+        {
+            return;
+        }
+        self.unrestored.push(queued);
+    }
 
     fn manage_go_signal(&mut self) {
         debug!("got signal Go");
@@ -215,41 +303,73 @@ impl DownloadManagerBuilder {
 
         debug!("current download queue: {:?}", self.download_queue.read());
 
-        let agent_data = if let Some(agent_data) = self.download_queue.read().front() {
-            agent_data.clone()
-        } else {
-            return;
-        };
-
-        let download_agent = self
-            .download_agent_registry
-            .get(&agent_data)
-            .unwrap()
-            .clone();
-
-        let status = download_agent.status();
-
-        // This download is already going
-        if status != DownloadStatus::Queued {
+        if bandwidth::quota_exceeded() {
+            info!("monthly download quota used up, not starting any downloads");
+            self.push_quota_exceeded();
             return;
         }
 
-        // Ensure all others are marked as queued
-        for agent in self.download_agent_registry.values() {
-            if agent.metadata() != agent_data && agent.status() != DownloadStatus::Queued {
+        self.reap_stopped_downloads();
+
+        let max_concurrent_downloads = borrow_db_checked().settings.max_concurrent_downloads.max(1);
+        // Paused downloads keep their place, but don't take up a slot
+        let queue = self
+            .download_queue
+            .read()
+            .into_iter()
+            .filter(|meta| {
+                self.download_agent_registry
+                    .get(meta)
+                    .is_none_or(|agent| agent.status() != DownloadStatus::Paused)
+            })
+            .collect::<Vec<DownloadableMetadata>>();
+
+        // Anything outside the first slots, e.g. after a rearrange, goes
+        // back to waiting
+        for meta in queue.iter().skip(max_concurrent_downloads) {
+            if self.active_downloads.contains_key(meta) {
+                info!("pausing {meta:?} to make room for downloads ahead of it");
+                self.stop_and_wait_download(meta);
+            }
+            if let Some(agent) = self.download_agent_registry.get(meta)
+                && agent.status() != DownloadStatus::Queued
+            {
                 agent.on_queued(&self.app_handle);
             }
         }
 
-        info!("starting download for {agent_data:?}");
-        self.active_control_flag = Some(download_agent.control_flag());
+        for meta in queue.iter().take(max_concurrent_downloads) {
+            // This download is already going
+            if self.active_downloads.contains_key(meta) {
+                continue;
+            }
+            let Some(download_agent) = self.download_agent_registry.get(meta).cloned() else {
+                continue;
+            };
+            // Only start downloads that are waiting or were paused, anything
+            // else has finished and is waiting on its signal
+            if download_agent.status() != DownloadStatus::Queued
+                && download_agent.control_flag().get() != DownloadThreadControlFlag::Stop
+            {
+                continue;
+            }
+            self.start_download(meta.clone(), download_agent);
+        }
 
-        let sender = self.sender.clone();
+        if self.active_downloads.is_empty() {
+            self.speed_history.reset();
+        } else {
+            self.set_status(DownloadManagerStatus::Downloading);
+        }
+    }
+    fn start_download(&mut self, meta: DownloadableMetadata, download_agent: DownloadAgent) {
+        info!("starting download for {meta:?}");
+        let control_flag = download_agent.control_flag();
 
-        let mut download_thread_lock = lock!(self.current_download_thread);
+        let sender = self.sender.clone();
         let app_handle = self.app_handle.clone();
 
-        *download_thread_lock = Some(spawn(move || {
+        let thread = spawn(move || {
             loop {
                 let download_result = match download_agent.download(&app_handle) {
                     // Ok(true) is for completed and exited properly
@@ -257,7 +377,10 @@ impl DownloadManagerBuilder {
                     Err(e) => {
                         error!("download {:?} has error {}", download_agent.metadata(), &e);
                         download_agent.on_error(&app_handle, &e);
-                        send!(sender, DownloadManagerSignal::Error(e));
+                        send!(
+                            sender,
+                            DownloadManagerSignal::Error(download_agent.metadata(), e)
+                        );
                         return;
                     }
                 };
@@ -281,7 +404,10 @@ impl DownloadManagerBuilder {
                             &e
                         );
                         download_agent.on_error(&app_handle, &e);
-                        send!(sender, DownloadManagerSignal::Error(e));
+                        send!(
+                            sender,
+                            DownloadManagerSignal::Error(download_agent.metadata(), e)
+                        );
                         return;
                     }
                 };
@@ -300,60 +426,126 @@ impl DownloadManagerBuilder {
                     return;
                 }
             }
-        }));
-
-        self.set_status(DownloadManagerStatus::Downloading);
-        let active_control_flag = self.active_control_flag.clone().unwrap();
-        active_control_flag.set(DownloadThreadControlFlag::Go);
+        });
+
+        control_flag.set(DownloadThreadControlFlag::Go);
+        self.active_downloads.insert(
+            meta,
+            ActiveDownload {
+                thread,
+                control_flag,
+            },
+        );
     }
-    fn manage_stop_signal(&mut self) {
+    fn manage_stop_signal(&mut self, reply: Option<Sender<bool>>) {
         debug!("got signal Stop");
 
-        if let Some(active_control_flag) = self.active_control_flag.clone() {
-            self.set_status(DownloadManagerStatus::Paused);
-            active_control_flag.set(DownloadThreadControlFlag::Stop);
+        if let Some(reply) = reply {
+            let _ = reply.send(!self.active_downloads.is_empty());
+        }
+        if self.active_downloads.is_empty() {
+            return;
+        }
+        self.set_status(DownloadManagerStatus::Paused);
+        for active_download in self.active_downloads.values() {
+            active_download
+                .control_flag
+                .set(DownloadThreadControlFlag::Stop);
+        }
+    }
+    fn manage_quota_exceeded_signal(&mut self) {
+        debug!("got signal QuotaExceeded");
+
+        // Every running download sends this until they've all stopped
+        if self.active_downloads.is_empty() {
+            return;
         }
+        info!("monthly download quota used up, pausing downloads");
+        self.stop_and_wait_all_downloads();
+        bandwidth::save();
+
+        self.push_quota_exceeded();
+        self.notify(
+            "Downloads paused",
+            Some("You've reached your monthly download limit."),
+        );
+        self.push_ui_queue_update();
     }
     fn manage_completed_signal(&mut self, meta: DownloadableMetadata) {
         debug!("got signal Completed");
-        if let Some(interface) = self.download_queue.read().front()
-            && interface == &meta
+        if self.download_agent_registry.contains_key(&meta)
+            && let Some(agent) = self.remove_and_cleanup_download(&meta)
         {
-            self.remove_and_cleanup_front_download(&meta);
+            if let Some(entry) = agent.history_entry() {
+                record_download(&mut borrow_db_mut_checked(), entry);
+            }
+            bandwidth::save();
+            self.notify_download_result(
+                &format!("{} finished downloading", agent.display_name()),
+                None,
+            );
         }
 
         self.push_ui_queue_update();
         send!(self.sender, DownloadManagerSignal::Go);
     }
-    fn manage_error_signal(&mut self, error: ApplicationDownloadError) {
+    fn manage_error_signal(&mut self, meta: DownloadableMetadata, error: ApplicationDownloadError) {
         debug!("got signal Error");
-        if let Some(metadata) = self.download_queue.read().front()
-            && let Some(current_agent) = self.download_agent_registry.get(metadata)
-        {
-            current_agent.on_error(&self.app_handle, &error);
+        if let ApplicationDownloadError::InstallDirUnavailable(_) = error {
+            self.manage_unavailable_signal(&meta, &error);
+            return;
+        }
+        if let Some(agent) = self.download_agent_registry.get(&meta).cloned() {
+            agent.on_error(&self.app_handle, &error);
+            let title = match error {
+                ApplicationDownloadError::Corrupted(_) => {
+                    format!("{} failed to verify", agent.display_name())
+                }
+                _ => format!("{} failed to download", agent.display_name()),
+            };
+            self.notify_download_result(&title, Some(&error.to_string()));
 
-            self.stop_and_wait_current_download();
-            self.remove_and_cleanup_front_download(metadata);
+            self.stop_and_wait_download(&meta);
+            self.remove_and_cleanup_download(&meta);
         }
+        // The error stays with the download, the others carry on
         self.push_ui_queue_update();
-        self.set_status(DownloadManagerStatus::Error);
+    }
+    /// The install directory went away (unplugged drive, unmounted share), so
+    /// the download is paused rather than failed, and can be resumed later
+    fn manage_unavailable_signal(
+        &mut self,
+        meta: &DownloadableMetadata,
+        error: &ApplicationDownloadError,
+    ) {
+        let Some(agent) = self.download_agent_registry.get(meta).cloned() else {
+            return;
+        };
+        // Every running bucket hits this at once, only handle the first
+        if agent.status() == DownloadStatus::Paused {
+            return;
+        }
+        self.stop_and_wait_download(meta);
+        agent.on_error(&self.app_handle, error);
+        self.notify(
+            &format!("{} paused", agent.display_name()),
+            Some(&error.to_string()),
+        );
+
+        self.push_ui_queue_update();
+        send!(self.sender, DownloadManagerSignal::Go);
     }
     fn manage_cancel_signal(&mut self, meta: &DownloadableMetadata) {
         debug!("got signal Cancel");
 
-        // If the current download is the one we're tryna cancel
-        if let Some(current_metadata) = self.download_queue.read().front()
-            && current_metadata == meta
-            && let Some(current_download) = self.download_agent_registry.get(current_metadata)
+        // If it's one of the active downloads, stop it first
+        if self.active_downloads.contains_key(meta)
+            && let Some(current_download) = self.download_agent_registry.get(meta).cloned()
         {
-            self.set_status(DownloadManagerStatus::Paused);
             current_download.on_cancelled(&self.app_handle);
-            self.stop_and_wait_current_download();
+            self.stop_and_wait_download(meta);
 
-            self.download_queue.pop_front();
-
-            self.cleanup_current_download();
-            self.download_agent_registry.remove(meta);
+            self.remove_and_cleanup_download(meta);
             debug!("current download queue: {:?}", self.download_queue.read());
         }
         // else just cancel it
@@ -373,11 +565,124 @@ impl DownloadManagerBuilder {
         self.sender.send(DownloadManagerSignal::Go).unwrap();
         self.push_ui_queue_update();
     }
-    fn push_ui_stats_update(&self, kbs: usize, time: usize) {
-        let event_data = StatsUpdateEvent { speed: kbs, time };
+    fn manage_cancel_queued_signal(&mut self, reply: Sender<Vec<DownloadableMetadata>>) {
+        debug!("got signal CancelQueued");
+
+        // Everything not downloading right now, including paused downloads
+        let queued = self
+            .download_queue
+            .read()
+            .into_iter()
+            .filter(|meta| !self.active_downloads.contains_key(meta))
+            .collect::<Vec<DownloadableMetadata>>();
+
+        let mut cancelled = Vec::new();
+        for meta in queued {
+            let Some(download_agent) = self.download_agent_registry.remove(&meta) else {
+                continue;
+            };
+            download_agent.on_cancelled(&self.app_handle);
+            if let Some(index) = self.download_queue.get_by_meta(&meta) {
+                let _ = self.download_queue.edit().remove(index);
+            }
+            cancelled.push(meta);
+        }
+        debug!("current download queue: {:?}", self.download_queue.read());
+
+        if !cancelled.is_empty() {
+            self.push_ui_queue_update();
+        }
+        // The caller may have given up waiting
+        let _ = reply.send(cancelled);
+    }
+    fn manage_pause_signal(&mut self, meta: &DownloadableMetadata) {
+        debug!("got signal Pause");
+
+        let Some(download_agent) = self.download_agent_registry.get(meta).cloned() else {
+            return;
+        };
+        self.stop_and_wait_download(meta);
+        download_agent.on_paused(&self.app_handle);
+
+        self.push_ui_queue_update();
+        // Something else can have its slot
+        send!(self.sender, DownloadManagerSignal::Go);
+    }
+    fn manage_resume_signal(&mut self, meta: &DownloadableMetadata) {
+        debug!("got signal Resume");
+
+        if let Some(download_agent) = self.download_agent_registry.get(meta)
+            && download_agent.status() == DownloadStatus::Paused
+        {
+            download_agent.on_queued(&self.app_handle);
+        }
+
+        self.push_ui_queue_update();
+        send!(self.sender, DownloadManagerSignal::Go);
+    }
+    fn manage_dump_state_signal(&self, reply: Sender<DownloadManagerDebugState>) {
+        debug!("got signal DumpState");
+
+        let queue = self.download_queue.read();
+        let agents = self
+            .download_agent_registry
+            .iter()
+            .map(|(meta, agent)| AgentDebugState {
+                meta: meta.clone(),
+                status: agent.status(),
+                control_flag: agent.control_flag().get(),
+                in_queue: queue.contains(meta),
+                state: agent.debug_state(),
+            })
+            .collect();
+        let orphaned_queue_entries = queue
+            .iter()
+            .filter(|meta| !self.download_agent_registry.contains_key(*meta))
+            .cloned()
+            .collect();
+
+        let state = DownloadManagerDebugState {
+            status: format!("{:?}", *lock!(self.status)),
+            queue: queue.into_iter().collect(),
+            orphaned_queue_entries,
+            agents,
+            active_downloads: self.active_downloads.keys().cloned().collect(),
+        };
+        // The caller may have given up waiting
+        let _ = reply.send(state);
+    }
+    fn notify(&self, title: &str, body: Option<&str>) {
+        show_notification(&self.app_handle, title, body);
+    }
+    /// Downloads finishing or failing can be turned off in settings
+    fn notify_download_result(&self, title: &str, body: Option<&str>) {
+        if borrow_db_checked().settings.notify_on_download_complete {
+            self.notify(title, body);
+        }
+    }
+    fn push_ui_stats_update(&self) {
+        let (speed, time) = combined_stats(
+            self.active_downloads
+                .keys()
+                .filter_map(|meta| self.download_agent_registry.get(meta))
+                .map(|agent| {
+                    let progress = agent.progress();
+                    (progress.speed(), progress.bytes_remaining())
+                }),
+        );
+        self.speed_history.update(speed);
+
+        let event_data = StatsUpdateEvent {
+            speed,
+            time,
+            history: self.speed_history.summary(),
+        };
 
         app_emit!(&self.app_handle, "update_stats", event_data);
     }
+    fn push_quota_exceeded(&self) {
+        app_emit!(&self.app_handle, "quota_exceeded", bandwidth::usage());
+    }
     fn push_ui_queue_update(&self) {
         let queue = &self.download_queue.read();
         let queue_objs = queue
@@ -396,5 +701,29 @@ impl DownloadManagerBuilder {
 
         let event_data = QueueUpdateEvent { queue: queue_objs };
         app_emit!(&self.app_handle, "update_queue", event_data);
+
+        self.persist_queue(queue);
+    }
+    /// Saves the queue so it can be restored after a restart. Progress
+    /// updates push the queue constantly, so it's only written on changes.
+    fn persist_queue(&self, queue: &VecDeque<DownloadableMetadata>) {
+        let saved_queue = queue
+            .iter()
+            .filter_map(|meta| {
+                let agent = self.download_agent_registry.get(meta)?;
+                Some(QueuedDownload {
+                    meta: meta.clone(),
+                    install_dir: agent.install_dir(),
+                    paused: agent.status() == DownloadStatus::Paused,
+                })
+            })
+            // Kept at the back until they can be rebuilt
+            .chain(self.unrestored.iter().cloned())
+            .collect::<Vec<QueuedDownload>>();
+
+        if borrow_db_checked().applications.download_queue == saved_queue {
+            return;
+        }
+        borrow_db_mut_checked().applications.download_queue = saved_queue;
     }
 }
@@ -4,17 +4,21 @@ use std::{
     fmt::Debug,
     sync::{
         Mutex, MutexGuard,
-        mpsc::{SendError, Sender},
+        mpsc::{SendError, Sender, channel},
     },
     thread::JoinHandle,
+    time::Duration,
 };
 
-use database::DownloadableMetadata;
+use database::{DownloadableMetadata, QueuedDownload};
 use log::{debug, info};
 use serde::Serialize;
 use utils::{lock, send};
 
-use crate::error::ApplicationDownloadError;
+use crate::{
+    debug_state::DownloadManagerDebugState,
+    error::{ApplicationDownloadError, DownloadManagerError},
+};
 
 use super::{
     download_manager_builder::{CurrentProgressObject, DownloadAgent},
@@ -24,24 +28,46 @@ use super::{
 pub enum DownloadManagerSignal {
     /// Resumes (or starts) the `DownloadManager`
     Go,
-    /// Pauses the `DownloadManager`
-    Stop,
+    /// Pauses the `DownloadManager`, and sends back
+    /// whether there were any downloads to pause
+    Stop(Option<Sender<bool>>),
     /// Called when a `DownloadAgent` has fully completed a download.
     Completed(DownloadableMetadata),
     /// Generates and appends a `DownloadAgent`
     /// to the registry and queue
     Queue(DownloadAgent),
+    /// Appends a `DownloadAgent` that stays paused
+    /// until it's resumed, for restored downloads
+    QueuePaused(DownloadAgent),
+    /// Keeps a saved download that couldn't be rebuilt
+    /// in the saved queue, so it can be retried later
+    KeepUnrestored(QueuedDownload),
     /// Tells the Manager to stop the current
     /// download, sync everything to disk, and
     /// then exit
     Finish,
     /// Stops, removes, and tells a download to cleanup
     Cancel(DownloadableMetadata),
+    /// Removes and cleans up every download that isn't
+    /// currently active, with a single UI update, and
+    /// sends back the ones it cancelled
+    CancelQueued(Sender<Vec<DownloadableMetadata>>),
+    /// Stops one download and keeps it out of the running
+    /// downloads, without losing its place in the queue
+    Pause(DownloadableMetadata),
+    /// Lets a paused download run again
+    Resume(DownloadableMetadata),
     /// Any error which occurs in the agent
-    Error(ApplicationDownloadError),
+    Error(DownloadableMetadata, ApplicationDownloadError),
     /// Pushes UI update
     UpdateUIQueue,
-    UpdateUIStats(usize, usize), //kb/s and seconds
+    /// Pushes the stats of every running download together,
+    /// so concurrent downloads don't overwrite each other's
+    UpdateUIStats,
+    /// The monthly download quota is used up, so every download stops
+    QuotaExceeded,
+    /// Sends back a snapshot of the manager's internal state
+    DumpState(Sender<DownloadManagerDebugState>),
 }
 
 #[derive(Debug)]
@@ -49,7 +75,6 @@ pub enum DownloadManagerStatus {
     Downloading,
     Paused,
     Empty,
-    Error,
 }
 
 impl Serialize for DownloadManagerStatus {
@@ -67,6 +92,7 @@ pub enum DownloadStatus {
     Downloading,
     Validating,
     Error,
+    Paused,
 }
 
 /// Accessible front-end for the `DownloadManager`
@@ -112,6 +138,24 @@ impl DownloadManager {
             .send(DownloadManagerSignal::Queue(download))?;
         self.command_sender.send(DownloadManagerSignal::Go)
     }
+    /// Queues a download as paused, for downloads restored after a restart.
+    /// It only starts once it's resumed.
+    pub fn queue_download_paused(
+        &self,
+        download: DownloadAgent,
+    ) -> Result<(), SendError<DownloadManagerSignal>> {
+        info!("restoring download with meta {:?}", download.metadata());
+        self.command_sender
+            .send(DownloadManagerSignal::QueuePaused(download))
+    }
+    /// Keeps a saved download that couldn't be rebuilt, e.g. while offline,
+    /// in the saved queue so restoring can try it again
+    pub fn keep_unrestored(&self, queued: QueuedDownload) {
+        send!(
+            self.command_sender,
+            DownloadManagerSignal::KeepUnrestored(queued)
+        );
+    }
     pub fn edit(&self) -> MutexGuard<'_, VecDeque<DownloadableMetadata>> {
         self.download_queue.edit()
     }
@@ -135,31 +179,59 @@ impl DownloadManager {
     pub fn cancel(&self, meta: DownloadableMetadata) {
         send!(self.command_sender, DownloadManagerSignal::Cancel(meta));
     }
-    pub fn rearrange(&self, current_index: usize, new_index: usize) {
+    /// Cancels everything waiting behind the active downloads, leaving the
+    /// active ones running. Returns the downloads that were cancelled.
+    ///
+    /// Blocks until the manager gets to it, so the answer matches the queue
+    /// it actually cancelled from.
+    pub fn cancel_queued(&self) -> Vec<DownloadableMetadata> {
+        let (reply_sender, reply_receiver) = channel();
+        send!(
+            self.command_sender,
+            DownloadManagerSignal::CancelQueued(reply_sender)
+        );
+        reply_receiver.recv().unwrap_or_default()
+    }
+    pub fn rearrange(
+        &self,
+        current_index: usize,
+        new_index: usize,
+    ) -> Result<(), DownloadManagerError<()>> {
+        // Validated even when it's a no-op, so bad indices are still errors
+        self.download_queue.rearrange(current_index, new_index)?;
         if current_index == new_index {
-            return;
+            return Ok(());
         }
 
-        let needs_pause = current_index == 0 || new_index == 0;
-        if needs_pause {
-            send!(self.command_sender, DownloadManagerSignal::Stop);
-        }
-
-        debug!("moving download at index {current_index} to index {new_index}");
-
-        let mut queue = self.edit();
-        let to_move = queue.remove(current_index).expect("Failed to get");
-        queue.insert(new_index, to_move);
-        drop(queue);
+        debug!("moved download at index {current_index} to index {new_index}");
 
-        if needs_pause {
-            send!(self.command_sender, DownloadManagerSignal::Go);
-        }
+        // Go pauses anything pushed out of the active slots and starts
+        // whatever moved into them
         send!(self.command_sender, DownloadManagerSignal::UpdateUIQueue);
         send!(self.command_sender, DownloadManagerSignal::Go);
+        Ok(())
+    }
+    pub fn pause_download(&self, meta: DownloadableMetadata) {
+        send!(self.command_sender, DownloadManagerSignal::Pause(meta));
+    }
+    pub fn resume_download(&self, meta: DownloadableMetadata) {
+        send!(self.command_sender, DownloadManagerSignal::Resume(meta));
     }
     pub fn pause_downloads(&self) {
-        send!(self.command_sender, DownloadManagerSignal::Stop);
+        send!(self.command_sender, DownloadManagerSignal::Stop(None));
+    }
+    /// Like [`Self::pause_downloads`], but waits to hear whether anything
+    /// was actually running. Gives up and returns false if the manager is too
+    /// busy to answer.
+    pub fn pause_active_downloads(&self) -> bool {
+        let (reply_sender, reply_receiver) = channel();
+        send!(
+            self.command_sender,
+            DownloadManagerSignal::Stop(Some(reply_sender))
+        );
+        reply_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap_or(false)
     }
     pub fn resume_downloads(&self) {
         send!(self.command_sender, DownloadManagerSignal::Go);
@@ -169,6 +241,16 @@ impl DownloadManager {
         let terminator = lock!(self.terminator).take();
         terminator.unwrap().join()
     }
+    /// Returns `None` if the manager is too busy to answer, e.g. while it
+    /// waits for a download thread to stop
+    pub fn dump_state(&self) -> Option<DownloadManagerDebugState> {
+        let (reply_sender, reply_receiver) = channel();
+        send!(
+            self.command_sender,
+            DownloadManagerSignal::DumpState(reply_sender)
+        );
+        reply_receiver.recv_timeout(Duration::from_secs(5)).ok()
+    }
     pub fn get_sender(&self) -> Sender<DownloadManagerSignal> {
         self.command_sender.clone()
     }
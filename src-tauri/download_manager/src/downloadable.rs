@@ -1,11 +1,12 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
-use database::DownloadableMetadata;
+use database::{DownloadHistoryEntry, DownloadableMetadata};
 use tauri::AppHandle;
 
 use crate::error::ApplicationDownloadError;
 
 use super::{
+    debug_state::DownloadableDebugState,
     download_manager_frontend::DownloadStatus,
     util::{download_thread_control_flag::DownloadThreadControl, progress_object::ProgressObject},
 };
@@ -24,8 +25,18 @@ pub trait Downloadable: Send + Sync {
     fn control_flag(&self) -> DownloadThreadControl;
     fn status(&self) -> DownloadStatus;
     fn metadata(&self) -> DownloadableMetadata;
+    /// What to call this in messages to the user
+    fn display_name(&self) -> String;
+    /// The install dir this downloads into, used to queue it again after a
+    /// restart
+    fn install_dir(&self) -> PathBuf;
+    /// What to record in the download history once this has completed, if
+    /// anything
+    fn history_entry(&self) -> Option<DownloadHistoryEntry>;
     fn on_queued(&self, app_handle: &AppHandle);
+    fn on_paused(&self, app_handle: &AppHandle);
     fn on_error(&self, app_handle: &AppHandle, error: &ApplicationDownloadError);
     fn on_complete(&self, app_handle: &AppHandle);
     fn on_cancelled(&self, app_handle: &AppHandle);
+    fn debug_state(&self) -> DownloadableDebugState;
 }
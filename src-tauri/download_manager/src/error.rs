@@ -2,6 +2,7 @@ use humansize::{BINARY, format_size};
 use std::{
     fmt::{Display, Formatter},
     io,
+    path::PathBuf,
     sync::{Arc, mpsc::SendError},
 };
 
@@ -12,12 +13,17 @@ use serde_with::SerializeDisplay;
 pub enum DownloadManagerError<T> {
     IOError(io::Error),
     SignalError(SendError<T>),
+    InvalidQueueIndex(usize, usize),
 }
 impl<T> Display for DownloadManagerError<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DownloadManagerError::IOError(error) => write!(f, "{error}"),
             DownloadManagerError::SignalError(send_error) => write!(f, "{send_error}"),
+            DownloadManagerError::InvalidQueueIndex(index, len) => write!(
+                f,
+                "no download at position {index}, the queue only has {len}"
+            ),
         }
     }
 }
@@ -38,11 +44,18 @@ pub enum ApplicationDownloadError {
     NotInitialized,
     Communication(RemoteAccessError),
     DiskFull(u64, u64),
+    QuotaExceeded(PathBuf, u64, u64),
     #[allow(dead_code)]
     Checksum,
     Lock,
     IoError(Arc<io::Error>),
     DownloadError(RemoteAccessError),
+    ContextExpired,
+    NotInstalled(String),
+    RepeatedChecksum(String),
+    InstallDirUnavailable(PathBuf),
+    UnsupportedChecksum(String),
+    Corrupted(String),
 }
 
 impl Display for ApplicationDownloadError {
@@ -57,6 +70,13 @@ impl Display for ApplicationDownloadError {
                 format_size(*required, BINARY),
                 format_size(*available, BINARY),
             ),
+            ApplicationDownloadError::QuotaExceeded(dir, quota, required) => write!(
+                f,
+                "Installing here would use {} of the {} quota set for {}.",
+                format_size(*required, BINARY),
+                format_size(*quota, BINARY),
+                dir.display(),
+            ),
             ApplicationDownloadError::Communication(error) => write!(f, "{error}"),
             ApplicationDownloadError::Lock => write!(
                 f,
@@ -69,6 +89,29 @@ impl Display for ApplicationDownloadError {
             ApplicationDownloadError::DownloadError(error) => {
                 write!(f, "Download failed with error {error:?}")
             }
+            ApplicationDownloadError::ContextExpired => {
+                write!(f, "the server no longer accepts this download's context")
+            }
+            ApplicationDownloadError::NotInstalled(game_id) => {
+                write!(f, "{game_id} isn't installed")
+            }
+            ApplicationDownloadError::RepeatedChecksum(filename) => write!(
+                f,
+                "{filename} failed to validate again after being downloaded twice. This can mean the disk is failing."
+            ),
+            ApplicationDownloadError::InstallDirUnavailable(dir) => write!(
+                f,
+                "install directory {} is no longer available. The download has been paused, resume it once the directory is back.",
+                dir.display()
+            ),
+            ApplicationDownloadError::UnsupportedChecksum(algorithm) => write!(
+                f,
+                "the manifest uses the {algorithm} checksum algorithm, which this version of Drop doesn't support"
+            ),
+            ApplicationDownloadError::Corrupted(game_id) => write!(
+                f,
+                "some files of {game_id} are corrupted. Resume the download to repair them"
+            ),
         }
     }
 }
@@ -1,7 +1,9 @@
 use database::DownloadableMetadata;
 use serde::Serialize;
 
-use crate::download_manager_frontend::DownloadStatus;
+use crate::{
+    download_manager_frontend::DownloadStatus, util::rolling_progress_updates::SpeedSummary,
+};
 
 #[derive(Serialize, Clone)]
 pub struct QueueUpdateEventQueueData {
@@ -21,4 +23,6 @@ pub struct QueueUpdateEvent {
 pub struct StatsUpdateEvent {
     pub speed: usize,
     pub time: usize,
+    /// Speeds over the last minute
+    pub history: SpeedSummary,
 }
@@ -10,6 +10,7 @@ use crate::{
     download_manager_builder::DownloadManagerBuilder, download_manager_frontend::DownloadManager,
 };
 
+pub mod debug_state;
 pub mod download_manager_builder;
 pub mod download_manager_frontend;
 pub mod downloadable;
@@ -17,6 +18,9 @@ pub mod error;
 pub mod frontend_updates;
 pub mod util;
 
+#[cfg(test)]
+mod test;
+
 pub static DOWNLOAD_MANAGER: DownloadManagerWrapper = DownloadManagerWrapper::new();
 
 pub struct DownloadManagerWrapper(OnceLock<DownloadManager>);
@@ -0,0 +1,94 @@
+use database::{DownloadType, DownloadableMetadata};
+
+use crate::{
+    error::DownloadManagerError,
+    util::{
+        progress_object::combined_stats,
+        queue::Queue,
+        rolling_progress_updates::{RollingProgressWindow, SpeedSummary},
+    },
+};
+
+fn queue_of(ids: &[&str]) -> Queue {
+    let queue = Queue::new();
+    for id in ids {
+        queue.append(DownloadableMetadata::new(
+            id.to_string(),
+            Some("1.0".to_owned()),
+            DownloadType::Game,
+        ));
+    }
+    queue
+}
+
+fn ids(queue: &Queue) -> Vec<String> {
+    queue.read().into_iter().map(|meta| meta.id).collect()
+}
+
+#[test]
+fn rearrange_moves_within_queue() {
+    let queue = queue_of(&["a", "b", "c"]);
+
+    queue.rearrange(2, 0).unwrap();
+    assert_eq!(ids(&queue), ["c", "a", "b"]);
+
+    queue.rearrange(0, 2).unwrap();
+    assert_eq!(ids(&queue), ["a", "b", "c"]);
+}
+
+#[test]
+fn rearrange_rejects_out_of_range_indices() {
+    let queue = queue_of(&["a", "b"]);
+
+    for (current_index, new_index) in [(2, 0), (0, 2), (5, 7), (3, 3), (usize::MAX, 0)] {
+        let result = queue.rearrange(current_index, new_index);
+        assert!(
+            matches!(result, Err(DownloadManagerError::InvalidQueueIndex(_, 2))),
+            "moving {current_index} to {new_index} should fail"
+        );
+        assert_eq!(ids(&queue), ["a", "b"]);
+    }
+
+    // A stale index from after the queue emptied
+    let empty = queue_of(&[]);
+    assert!(empty.rearrange(0, 0).is_err());
+}
+
+#[test]
+fn rolling_window_is_empty_after_reset() {
+    let window = RollingProgressWindow::new(4);
+    window.update(100);
+    window.reset();
+
+    assert_eq!(window.get_average(), 0);
+    assert_eq!(window.summary(), SpeedSummary::default());
+}
+
+#[test]
+fn rolling_window_summarises_latest_samples() {
+    let window = RollingProgressWindow::new(3);
+    for speed in [1000, 30, 60, 90] {
+        window.update(speed);
+    }
+
+    assert_eq!(
+        window.summary(),
+        SpeedSummary {
+            min: 30,
+            max: 90,
+            average: 60,
+        }
+    );
+}
+
+#[test]
+fn concurrent_downloads_share_one_set_of_stats() {
+    // 2MB at 1000kb/s and 4MB at 500kb/s
+    let (speed, time) = combined_stats([(1000, 2_000_000), (500, 4_000_000)]);
+    assert_eq!(speed, 1500);
+    assert_eq!(time, 4);
+
+    // Neither download has sampled a speed yet
+    assert_eq!(combined_stats([(0, 1_000_000), (0, 1_000_000)]), (0, 2000));
+    assert_eq!(combined_stats([]), (0, 0));
+}
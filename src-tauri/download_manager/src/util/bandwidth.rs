@@ -0,0 +1,56 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use database::{bandwidth::today, borrow_db_checked, borrow_db_mut_checked};
+use serde::Serialize;
+use throttle_my_fn::throttle;
+
+/// Counted but not yet written to the database, which is too slow to save
+/// on every progress update
+static UNSAVED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QuotaUsage {
+    pub used: u64,
+    // 0 for no limit
+    pub quota: u64,
+}
+
+/// Counts bytes downloaded from the server towards the monthly quota
+pub fn record(bytes: u64) {
+    UNSAVED_BYTES.fetch_add(bytes, Ordering::AcqRel);
+}
+
+/// Writes everything counted so far to the database
+pub fn save() {
+    let bytes = UNSAVED_BYTES.swap(0, Ordering::AcqRel);
+    if bytes == 0 {
+        return;
+    }
+    let mut db_lock = borrow_db_mut_checked();
+    let reset_day = db_lock.settings.quota_reset_day;
+    db_lock.bandwidth_usage.add(bytes, today(), reset_day);
+}
+
+#[throttle(1, Duration::from_secs(30))]
+pub fn save_periodically() {
+    save();
+}
+
+pub fn usage() -> QuotaUsage {
+    let db_lock = borrow_db_checked();
+    let saved = db_lock
+        .bandwidth_usage
+        .current(today(), db_lock.settings.quota_reset_day);
+    QuotaUsage {
+        used: saved + UNSAVED_BYTES.load(Ordering::Acquire),
+        quota: db_lock.settings.monthly_quota_bytes,
+    }
+}
+
+pub fn quota_exceeded() -> bool {
+    let usage = usage();
+    usage.quota != 0 && usage.used >= usage.quota
+}
@@ -3,7 +3,9 @@ use std::sync::{
     atomic::{AtomicBool, Ordering},
 };
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+use serde::Serialize;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Clone, Copy, Debug)]
 pub enum DownloadThreadControlFlag {
     Stop,
     Go,
@@ -1,4 +1,6 @@
+pub mod bandwidth;
 pub mod download_thread_control_flag;
+pub mod notification;
 pub mod progress_object;
 pub mod queue;
 pub mod rolling_progress_updates;
@@ -0,0 +1,80 @@
+use log::warn;
+use tauri::{AppHandle, Manager};
+
+/// Brings the main window forward, for when a notification is clicked
+#[cfg(not(target_os = "macos"))]
+fn focus_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+/// Shows a desktop notification that brings Drop forward when clicked.
+///
+/// The notification plugin can't tell us about clicks on desktop, so on
+/// Linux this goes through the notification server directly and waits for
+/// its default action.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn show_notification(app_handle: &AppHandle, title: &str, body: Option<&str>) {
+    let mut notification = notify_rust::Notification::new();
+    notification
+        .appname("Drop")
+        .summary(title)
+        .action("default", "Show Drop");
+    if let Some(body) = body {
+        notification.body(body);
+    }
+    let handle = match notification.show() {
+        Ok(handle) => handle,
+        Err(e) => {
+            warn!("failed to show notification: {e}");
+            return;
+        }
+    };
+
+    let app_handle = app_handle.clone();
+    // Waits until the notification is clicked or closed, on the runtime's
+    // pool of blocking threads rather than a new thread each time
+    tauri::async_runtime::spawn_blocking(move || {
+        handle.wait_for_action(|action| {
+            if action == "default" {
+                focus_main_window(&app_handle);
+            }
+        });
+    });
+}
+
+/// Shows a toast that brings Drop forward when clicked
+#[cfg(windows)]
+pub fn show_notification(app_handle: &AppHandle, title: &str, body: Option<&str>) {
+    let app_id = app_handle.config().identifier.clone();
+    let focus_handle = app_handle.clone();
+    let mut toast = tauri_winrt_notification::Toast::new(&app_id)
+        .title(title)
+        .on_activated(move |_action| {
+            focus_main_window(&focus_handle);
+            Ok(())
+        });
+    if let Some(body) = body {
+        toast = toast.text1(body);
+    }
+    if let Err(e) = toast.show() {
+        warn!("failed to show notification: {e}");
+    }
+}
+
+/// macOS activates the app that sent a notification when it's clicked
+#[cfg(target_os = "macos")]
+pub fn show_notification(app_handle: &AppHandle, title: &str, body: Option<&str>) {
+    use tauri_plugin_notification::NotificationExt;
+
+    let mut notification = app_handle.notification().builder().title(title);
+    if let Some(body) = body {
+        notification = notification.body(body);
+    }
+    if let Err(e) = notification.show() {
+        warn!("failed to show notification: {e}");
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
     sync::{
         Arc, Mutex,
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         mpsc::Sender,
     },
     time::{Duration, Instant},
@@ -13,7 +13,7 @@ use utils::{lock, send};
 
 use crate::download_manager_frontend::DownloadManagerSignal;
 
-use super::rolling_progress_updates::RollingProgressWindow;
+use super::{bandwidth, rolling_progress_updates::RollingProgressWindow};
 
 #[derive(Clone, Debug)]
 pub struct ProgressObject {
@@ -24,9 +24,25 @@ pub struct ProgressObject {
     //last_update: Arc<RwLock<Instant>>,
     last_update_time: Arc<AtomicInstant>,
     bytes_last_update: Arc<AtomicUsize>,
-    rolling: RollingProgressWindow<1000>,
+    rolling: RollingProgressWindow,
+    /// Whether progress is coming from the network and counts towards the
+    /// quota, rather than e.g. from validation reading the disk
+    metered: Arc<AtomicBool>,
 }
 
+/// How often `calculate_update` samples the download speed, matching its throttle
+const SAMPLE_INTERVAL_MILLIS: usize = 20;
+
+/// Number of speed samples needed to average over `seconds`
+pub fn rolling_window_size(seconds: usize) -> usize {
+    (seconds * 1000 / SAMPLE_INTERVAL_MILLIS).max(1)
+}
+
+/// How often `push_update` sends stats to the UI, matching its throttle
+const PUSH_INTERVAL_MILLIS: usize = 250;
+/// Number of UI updates in the speed history sent along with the stats
+pub(crate) const HISTORY_SIZE: usize = 60 * 1000 / PUSH_INTERVAL_MILLIS;
+
 #[derive(Clone)]
 pub struct ProgressHandle {
     progress: Arc<AtomicUsize>,
@@ -60,7 +76,12 @@ impl ProgressHandle {
 }
 
 impl ProgressObject {
-    pub fn new(max: usize, length: usize, sender: Sender<DownloadManagerSignal>) -> Self {
+    pub fn new(
+        max: usize,
+        length: usize,
+        rolling_window_size: usize,
+        sender: Sender<DownloadManagerSignal>,
+    ) -> Self {
         let arr = Mutex::new((0..length).map(|_| Arc::new(AtomicUsize::new(0))).collect());
         Self {
             max: Arc::new(Mutex::new(max)),
@@ -70,7 +91,8 @@ impl ProgressObject {
 
             last_update_time: Arc::new(AtomicInstant::now()),
             bytes_last_update: Arc::new(AtomicUsize::new(0)),
-            rolling: RollingProgressWindow::new(),
+            rolling: RollingProgressWindow::new(rolling_window_size),
+            metered: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -91,6 +113,9 @@ impl ProgressObject {
             .iter()
             .for_each(|x| x.store(0, Ordering::SeqCst));
     }
+    pub fn set_metered(&self, metered: bool) {
+        self.metered.store(metered, Ordering::Release);
+    }
     pub fn get_max(&self) -> usize {
         *lock!(self.max)
     }
@@ -107,6 +132,13 @@ impl ProgressObject {
     pub fn get(&self, index: usize) -> Arc<AtomicUsize> {
         lock!(self.progress_instances)[index].clone()
     }
+    /// The smoothed speed, in kilobytes per second
+    pub fn speed(&self) -> usize {
+        self.rolling.get_average()
+    }
+    pub fn bytes_remaining(&self) -> usize {
+        self.get_max().saturating_sub(self.sum())
+    }
     fn update_window(&self, kilobytes_per_second: usize) {
         self.rolling.update(kilobytes_per_second);
     }
@@ -122,36 +154,48 @@ pub fn calculate_update(progress: &ProgressObject) {
         .as_millis_f64();
 
     let current_bytes_downloaded = progress.sum();
-    let max = progress.get_max();
     let bytes_at_last_update = progress
         .bytes_last_update
         .swap(current_bytes_downloaded, Ordering::Acquire);
 
-    let bytes_since_last_update =
-        current_bytes_downloaded.saturating_sub(bytes_at_last_update) as f64;
-
-    let kilobytes_per_second = bytes_since_last_update / time_since_last_update;
+    let bytes_since_last_update = current_bytes_downloaded.saturating_sub(bytes_at_last_update);
+    if progress.metered.load(Ordering::Acquire) {
+        bandwidth::record(bytes_since_last_update as u64);
+    }
 
-    let bytes_remaining = max.saturating_sub(current_bytes_downloaded); // bytes
+    let kilobytes_per_second = bytes_since_last_update as f64 / time_since_last_update;
 
     progress.update_window(kilobytes_per_second as usize);
-    push_update(progress, bytes_remaining);
+    push_update(progress);
 }
 
 #[throttle(1, Duration::from_millis(250))]
-pub fn push_update(progress: &ProgressObject, bytes_remaining: usize) {
-    let average_speed = progress.rolling.get_average();
-    let time_remaining = (bytes_remaining / 1000) / average_speed.max(1);
-
-    update_ui(progress, average_speed, time_remaining);
+pub fn push_update(progress: &ProgressObject) {
+    update_ui(progress);
     update_queue(progress);
+
+    if progress.metered.load(Ordering::Acquire) {
+        bandwidth::save_periodically();
+        if bandwidth::quota_exceeded() {
+            send!(progress.sender, DownloadManagerSignal::QuotaExceeded);
+        }
+    }
+}
+
+fn update_ui(progress_object: &ProgressObject) {
+    send!(progress_object.sender, DownloadManagerSignal::UpdateUIStats);
 }
 
-fn update_ui(progress_object: &ProgressObject, kilobytes_per_second: usize, time_remaining: usize) {
-    send!(
-        progress_object.sender,
-        DownloadManagerSignal::UpdateUIStats(kilobytes_per_second, time_remaining)
+/// Adds up the speed (kb/s) and bytes left of every running download, and
+/// works out how long they'll all take, in seconds
+pub fn combined_stats(downloads: impl IntoIterator<Item = (usize, usize)>) -> (usize, usize) {
+    let (speed, bytes_remaining) = downloads.into_iter().fold(
+        (0, 0),
+        |(speed, remaining), (agent_speed, agent_remaining)| {
+            (speed + agent_speed, remaining + agent_remaining)
+        },
     );
+    (speed, (bytes_remaining / 1000) / speed.max(1))
 }
 
 fn update_queue(progress: &ProgressObject) {
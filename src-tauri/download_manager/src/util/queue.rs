@@ -6,6 +6,8 @@ use std::{
 use database::DownloadableMetadata;
 use utils::lock;
 
+use crate::error::DownloadManagerError;
+
 #[derive(Clone, Debug)]
 pub struct Queue {
     inner: Arc<Mutex<VecDeque<DownloadableMetadata>>>,
@@ -42,4 +44,24 @@ impl Queue {
     pub fn get_by_meta(&self, meta: &DownloadableMetadata) -> Option<usize> {
         self.read().iter().position(|data| data == meta)
     }
+    /// Moves the download at `current_index` to `new_index`. Either index can
+    /// be stale if the frontend's copy of the queue is behind, so both are
+    /// checked against the queue as it is now.
+    pub fn rearrange(
+        &self,
+        current_index: usize,
+        new_index: usize,
+    ) -> Result<(), DownloadManagerError<()>> {
+        let mut queue = self.edit();
+        let len = queue.len();
+        for index in [current_index, new_index] {
+            if index >= len {
+                return Err(DownloadManagerError::InvalidQueueIndex(index, len));
+            }
+        }
+        if let Some(to_move) = queue.remove(current_index) {
+            queue.insert(new_index, to_move);
+        }
+        Ok(())
+    }
 }
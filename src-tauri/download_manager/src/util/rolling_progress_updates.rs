@@ -3,42 +3,60 @@ use std::sync::{
     atomic::{AtomicUsize, Ordering},
 };
 
+use serde::Serialize;
+
+/// Lowest, highest and average of the samples in a `RollingProgressWindow`
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpeedSummary {
+    pub min: usize,
+    pub max: usize,
+    pub average: usize,
+}
+
 #[derive(Clone, Debug)]
-pub struct RollingProgressWindow<const S: usize> {
-    window: Arc<[AtomicUsize; S]>,
+pub struct RollingProgressWindow {
+    window: Arc<[AtomicUsize]>,
     current: Arc<AtomicUsize>,
 }
-impl<const S: usize> Default for RollingProgressWindow<S> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
 
-impl<const S: usize> RollingProgressWindow<S> {
-    pub fn new() -> Self {
+impl RollingProgressWindow {
+    pub fn new(size: usize) -> Self {
         Self {
-            window: Arc::new([(); S].map(|()| AtomicUsize::new(0))),
+            window: (0..size.max(1)).map(|_| AtomicUsize::new(0)).collect(),
             current: Arc::new(AtomicUsize::new(0)),
         }
     }
     pub fn update(&self, kilobytes_per_second: usize) {
         let index = self.current.fetch_add(1, Ordering::SeqCst);
-        let current = &self.window[index % S];
+        let current = &self.window[index % self.window.len()];
         current.store(kilobytes_per_second, Ordering::SeqCst);
     }
-    pub fn get_average(&self) -> usize {
+    fn samples(&self) -> Vec<usize> {
         let current = self.current.load(Ordering::SeqCst);
-        let valid = self
-            .window
+        self.window
             .iter()
-            .enumerate()
-            .filter(|(i, _)| i < &current)
-            .map(|(_, x)| x.load(Ordering::Acquire))
-            .collect::<Vec<usize>>();
-        let amount = valid.len();
-        let sum = valid.into_iter().sum::<usize>();
-
-        sum / amount
+            .take(current)
+            .map(|x| x.load(Ordering::Acquire))
+            .collect()
+    }
+    pub fn get_average(&self) -> usize {
+        let valid = self.samples();
+        // Nothing sampled since the last reset
+        if valid.is_empty() {
+            return 0;
+        }
+        valid.iter().sum::<usize>() / valid.len()
+    }
+    pub fn summary(&self) -> SpeedSummary {
+        let valid = self.samples();
+        if valid.is_empty() {
+            return SpeedSummary::default();
+        }
+        SpeedSummary {
+            min: *valid.iter().min().unwrap(),
+            max: *valid.iter().max().unwrap(),
+            average: valid.iter().sum::<usize>() / valid.len(),
+        }
     }
     pub fn reset(&self) {
         self.window
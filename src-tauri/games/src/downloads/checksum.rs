@@ -0,0 +1,55 @@
+use std::io::{self, Write};
+
+use download_manager::error::ApplicationDownloadError;
+use md5::Context;
+use sha2::{Digest, Sha256};
+
+/// What manifests from before the tag was added were checksummed with
+pub const DEFAULT_CHECKSUM_ALGORITHM: &str = "md5";
+
+/// Hashes chunks with whichever algorithm their manifest says it used
+pub enum ChunkHasher {
+    Md5(Context),
+    Sha256(Sha256),
+}
+
+impl ChunkHasher {
+    /// Errors on algorithms we don't know, rather than reporting every chunk
+    /// as a mismatch
+    pub fn new(algorithm: Option<&str>) -> Result<Self, ApplicationDownloadError> {
+        let algorithm = algorithm.unwrap_or(DEFAULT_CHECKSUM_ALGORITHM);
+        match algorithm.to_ascii_lowercase().as_str() {
+            "md5" => Ok(ChunkHasher::Md5(Context::new())),
+            "sha256" => Ok(ChunkHasher::Sha256(Sha256::new())),
+            _ => Err(ApplicationDownloadError::UnsupportedChecksum(
+                algorithm.to_owned(),
+            )),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            ChunkHasher::Md5(context) => context.consume(data),
+            ChunkHasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The checksum as the manifest has it, in lowercase hex
+    pub fn finalize(self) -> String {
+        match self {
+            ChunkHasher::Md5(context) => hex::encode(context.finalize().0),
+            ChunkHasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+        }
+    }
+}
+
+impl Write for ChunkHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
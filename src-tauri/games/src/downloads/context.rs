@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use remote::{
+    error::RemoteAccessError,
+    requests::{generate_authenticated_headers, generate_url},
+    utils::DROP_CLIENT_ASYNC,
+};
+use utils::lock;
+
+use super::manifest::{DownloadContext, ManifestBody};
+
+/// Contexts older than this are replaced before the next bucket uses them, so
+/// very long downloads don't wait for the server to reject one
+const CONTEXT_REFRESH_AFTER: Duration = Duration::from_secs(30 * 60);
+
+struct FreshContext {
+    context: DownloadContext,
+    fetched_at: Instant,
+}
+
+/// The download contexts for every version a game download touches, kept
+/// fresh for the buckets that use them
+pub struct DownloadContexts {
+    game_id: String,
+    contexts: Mutex<HashMap<String, FreshContext>>,
+}
+
+impl DownloadContexts {
+    /// Fetches every version's context at once, as they're independent.
+    /// Blocks, so it must be called from a download thread rather than the
+    /// async runtime. Fails if any of them does.
+    pub fn fetch_all(game_id: String, versions: Vec<String>) -> Result<Self, RemoteAccessError> {
+        let requests = versions
+            .into_iter()
+            .map(|version| {
+                let game_id = game_id.clone();
+                tauri::async_runtime::spawn(async move {
+                    let context = fetch_context_async(&game_id, &version).await;
+                    (version, context)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let contexts = tauri::async_runtime::block_on(async move {
+            let mut contexts = HashMap::new();
+            for request in requests {
+                let (version, context) = request.await.map_err(|e| {
+                    warn!("download context request failed to complete: {e}");
+                    RemoteAccessError::Cancelled
+                })?;
+                contexts.insert(
+                    version,
+                    FreshContext {
+                        context: context?,
+                        fetched_at: Instant::now(),
+                    },
+                );
+            }
+            Ok::<_, RemoteAccessError>(contexts)
+        })?;
+
+        Ok(Self {
+            game_id,
+            contexts: Mutex::new(contexts),
+        })
+    }
+
+    /// The context to use for a bucket of `version`, refetched first if it is
+    /// getting old
+    pub fn get(&self, version: &str) -> Result<DownloadContext, RemoteAccessError> {
+        let fresh = lock!(self.contexts)
+            .get(version)
+            .filter(|c| c.fetched_at.elapsed() < CONTEXT_REFRESH_AFTER)
+            .map(|fresh| fresh.context.clone());
+        match fresh {
+            Some(context) => Ok(context),
+            None => self.refetch(version),
+        }
+    }
+
+    /// Replaces a context the server rejected. If another bucket already
+    /// replaced `expired`, the replacement is reused instead of fetching again.
+    pub fn refresh(
+        &self,
+        version: &str,
+        expired: &DownloadContext,
+    ) -> Result<DownloadContext, RemoteAccessError> {
+        if let Some(current) = lock!(self.contexts).get(version)
+            && current.context.context != expired.context
+        {
+            return Ok(current.context.clone());
+        }
+        self.refetch(version)
+    }
+
+    /// Not locked during the request, so buckets of other versions aren't
+    /// held up by it. Buckets that find the same context stale at once each
+    /// fetch a new one, and the last to finish is kept.
+    fn refetch(&self, version: &str) -> Result<DownloadContext, RemoteAccessError> {
+        let context = fetch_context(&self.game_id, version)?;
+        lock!(self.contexts).insert(
+            version.to_owned(),
+            FreshContext {
+                context: context.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(context)
+    }
+}
+
+async fn fetch_context_async(
+    game_id: &str,
+    version: &str,
+) -> Result<DownloadContext, RemoteAccessError> {
+    let response = DROP_CLIENT_ASYNC
+        .post(generate_url(&["/api/v2/client/context"], &[])?)
+        .json(&ManifestBody {
+            game: game_id.to_owned(),
+            version: version.to_owned(),
+        })
+        .headers(generate_authenticated_headers()?)
+        .send()
+        .await?;
+
+    if response.status() != 200 {
+        return Err(RemoteAccessError::InvalidResponse(response.json().await?));
+    }
+
+    let context = response.json::<DownloadContext>().await?;
+    info!("download context: ({version}) {}", context.context);
+    Ok(context)
+}
+
+/// Blocks, like [`DownloadContexts::fetch_all`], so it's only for download
+/// threads
+fn fetch_context(game_id: &str, version: &str) -> Result<DownloadContext, RemoteAccessError> {
+    tauri::async_runtime::block_on(fetch_context_async(game_id, version))
+}
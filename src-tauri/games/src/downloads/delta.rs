@@ -0,0 +1,165 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use log::{info, warn};
+
+use super::manifest::DropManifest;
+
+/// Chunks that moved are copied here first, so copying one into place can't
+/// overwrite another that still has to be moved
+const STAGING_DIR: &str = ".drop-delta";
+
+struct ChunkLocation<'a> {
+    path: &'a str,
+    offset: usize,
+    length: usize,
+}
+
+/// Every chunk in `manifest` by checksum, with where it sits in its file
+fn chunk_locations(manifest: &DropManifest) -> HashMap<&str, Vec<ChunkLocation<'_>>> {
+    let mut locations = HashMap::<&str, Vec<ChunkLocation<'_>>>::new();
+    for (path, chunk) in manifest {
+        let mut offset = 0;
+        for (checksum, length) in chunk.checksums.iter().zip(chunk.lengths.iter()) {
+            locations.entry(checksum).or_default().push(ChunkLocation {
+                path,
+                offset,
+                length: *length,
+            });
+            offset += *length;
+        }
+    }
+    locations
+}
+
+/// Prepares an in-place update from the version described by `previous`.
+/// Chunks the new manifest shares with it are already marked complete by
+/// checksum, so the ones that moved within or between files are copied from
+/// their old offset to the new one.
+///
+/// Returns the checksums marked complete that couldn't be put in place, which
+/// have to be downloaded after all.
+pub(crate) fn reuse_previous_chunks(
+    base_path: &Path,
+    previous: &DropManifest,
+    manifest: &DropManifest,
+    contexts: &HashMap<String, bool>,
+) -> Vec<String> {
+    let previous_locations = chunk_locations(previous);
+    let mut moves = Vec::new();
+    let mut missing = HashSet::new();
+
+    for (checksum, locations) in chunk_locations(manifest) {
+        if !*contexts.get(checksum).unwrap_or(&false) {
+            continue;
+        }
+        let Some(sources) = previous_locations.get(checksum) else {
+            // Complete, but we don't know where it is
+            missing.insert(checksum.to_owned());
+            continue;
+        };
+        for location in locations {
+            let in_place = sources
+                .iter()
+                .any(|source| source.path == location.path && source.offset == location.offset);
+            if !in_place {
+                moves.push((checksum, &sources[0], location));
+            }
+        }
+    }
+
+    if moves.is_empty() {
+        return missing.into_iter().collect();
+    }
+    info!("reusing {} chunks from the previous version", moves.len());
+
+    let staging_dir = base_path.join(STAGING_DIR);
+    if let Err(e) = fs::create_dir_all(&staging_dir) {
+        warn!("couldn't create {}: {e}", staging_dir.display());
+        missing.extend(moves.iter().map(|(checksum, _, _)| checksum.to_string()));
+        return missing.into_iter().collect();
+    }
+
+    let mut staged = HashSet::new();
+    for (checksum, source, _) in &moves {
+        if staged.contains(checksum) || missing.contains(*checksum) {
+            continue;
+        }
+        let result = copy_range(
+            &base_path.join(source.path),
+            source.offset,
+            source.length,
+            &staging_dir.join(checksum),
+            0,
+        );
+        match result {
+            Ok(()) => {
+                staged.insert(*checksum);
+            }
+            Err(e) => {
+                warn!("couldn't stage chunk {checksum} from {}: {e}", source.path);
+                missing.insert(checksum.to_string());
+            }
+        }
+    }
+
+    for (checksum, _, destination) in &moves {
+        if !staged.contains(checksum) {
+            continue;
+        }
+        let result = copy_range(
+            &staging_dir.join(checksum),
+            0,
+            destination.length,
+            &base_path.join(destination.path),
+            destination.offset,
+        );
+        if let Err(e) = result {
+            warn!(
+                "couldn't move chunk {checksum} into {}: {e}",
+                destination.path
+            );
+            missing.insert(checksum.to_string());
+        }
+    }
+
+    if let Err(e) = fs::remove_dir_all(&staging_dir) {
+        warn!("couldn't remove {}: {e}", staging_dir.display());
+    }
+
+    missing.into_iter().collect()
+}
+
+fn copy_range(
+    source: &Path,
+    source_offset: usize,
+    length: usize,
+    destination: &Path,
+    destination_offset: usize,
+) -> io::Result<()> {
+    let mut source = File::open(source)?;
+    source.seek(SeekFrom::Start(source_offset as u64))?;
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut destination = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(destination)?;
+    destination.seek(SeekFrom::Start(destination_offset as u64))?;
+
+    let copied = io::copy(&mut source.take(length as u64), &mut destination)?;
+    if copied != length as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("expected {length} bytes, found {copied}"),
+        ));
+    }
+    Ok(())
+}
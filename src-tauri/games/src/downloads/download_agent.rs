@@ -1,48 +1,68 @@
 use database::{
-    ApplicationTransientStatus, DownloadType, DownloadableMetadata, borrow_db_checked,
-    borrow_db_mut_checked,
+    ApplicationTransientStatus, DownloadHistoryEntry, DownloadType, DownloadableMetadata,
+    borrow_db_checked, borrow_db_mut_checked,
 };
+use download_manager::debug_state::DownloadableDebugState;
 use download_manager::download_manager_frontend::{DownloadManagerSignal, DownloadStatus};
 use download_manager::downloadable::Downloadable;
 use download_manager::error::ApplicationDownloadError;
 use download_manager::util::download_thread_control_flag::{
     DownloadThreadControl, DownloadThreadControlFlag,
 };
-use download_manager::util::progress_object::{ProgressHandle, ProgressObject};
+use download_manager::util::progress_object::{
+    ProgressHandle, ProgressObject, rolling_window_size,
+};
 use log::{debug, error, info, warn};
 use rayon::ThreadPoolBuilder;
-use remote::auth::generate_authorization_header;
+use remote::cache::get_cached_object;
 use remote::error::RemoteAccessError;
-use remote::requests::generate_url;
-use remote::utils::{DROP_CLIENT_ASYNC, DROP_CLIENT_SYNC};
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
-use std::fs::{OpenOptions, create_dir_all};
+use std::fs::{OpenOptions, create_dir_all, remove_file};
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
 use tauri::AppHandle;
 use utils::{app_emit, lock, send};
 
-#[cfg(target_os = "linux")]
-use rustix::fs::{FallocateFlags, fallocate};
-
-use crate::downloads::manifest::{
-    DownloadBucket, DownloadContext, DownloadDrop, DropManifest, DropValidateContext, ManifestBody,
+use crate::downloads::manifest::{DownloadBucket, DownloadDrop, DropManifest, DropValidateContext};
+use crate::downloads::summary::{DownloadStatistics, record_download_summary};
+use crate::downloads::update::{fetch_manifest, forget_manifest};
+use crate::downloads::utils::{
+    get_disk_available, preallocate_file, remaining_size, soft_fd_limit,
 };
-use crate::downloads::utils::get_disk_available;
 use crate::downloads::validate::validate_game_chunk;
-use crate::library::{on_game_complete, push_game_update, set_partially_installed};
+use crate::library::{Game, on_game_complete, push_game_update, set_partially_installed};
 use crate::state::GameStatusManager;
 
+use super::context::DownloadContexts;
+use super::delta::reuse_previous_chunks;
 use super::download_logic::download_game_bucket;
 use super::drop_data::DropData;
+use super::file_progress::FileProgress;
+
+/// Connection failures usually mean the server is down, so they back off
+/// this many times longer than other errors
+const CONNECT_RETRY_MULTIPLIER: u32 = 4;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+const RETRY_WAIT_STEP: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub enum DownloadPhase {
+    FetchingManifest,
+    Preallocating,
+    FetchingContexts,
+    Downloading,
+    Validating,
+}
 
-static RETRY_COUNT: usize = 3;
-
-const TARGET_BUCKET_SIZE: usize = 63 * 1000 * 1000;
-const MAX_FILES_PER_BUCKET: usize = (1024 / 4) - 1;
+pub fn push_download_phase(app_handle: &AppHandle, game_id: &str, phase: DownloadPhase) {
+    app_emit!(app_handle, &format!("download_phase/{game_id}"), phase);
+}
 
 pub struct GameDownloadAgent {
     pub id: String,
@@ -55,6 +75,36 @@ pub struct GameDownloadAgent {
     sender: Sender<DownloadManagerSignal>,
     pub dropdata: DropData,
     status: Mutex<DownloadStatus>,
+    statistics: DownloadStatistics,
+    dropdata_reset: AtomicBool,
+    excluded_components: Vec<String>,
+    // Only checks the files already on disk, see `verify`
+    verify_only: bool,
+    verified: AtomicBool,
+    // Whether the chunks loaded as complete have been checked, see
+    // `verify_completed_chunks`
+    resume_checked: AtomicBool,
+    // How many validation passes each chunk has failed, by checksum
+    validation_failures: Mutex<HashMap<String, usize>>,
+}
+
+/// After this many failed validations of the same chunk, the problem is
+/// probably the disk rather than the download
+const MAX_VALIDATION_FAILURES: usize = 2;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidChunk {
+    pub filename: String,
+    pub offset: usize,
+    pub checksum: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationFailedEvent {
+    pub game_id: String,
+    pub chunks: Vec<InvalidChunk>,
 }
 
 impl GameDownloadAgent {
@@ -64,11 +114,15 @@ impl GameDownloadAgent {
         target_download_dir: usize,
         sender: Sender<DownloadManagerSignal>,
     ) -> Result<Self, ApplicationDownloadError> {
-        let base_dir = {
-            let db_lock = borrow_db_checked();
-
-            db_lock.applications.install_dirs[target_download_dir].clone()
-        };
+        let base_dir = borrow_db_checked()
+            .applications
+            .install_dirs
+            .get(target_download_dir)
+            .cloned()
+            .ok_or(ApplicationDownloadError::IoError(Arc::new(io::Error::new(
+                io::ErrorKind::NotFound,
+                "install directory no longer exists",
+            ))))?;
 
         Self::new(id, version, base_dir, sender).await
     }
@@ -77,15 +131,52 @@ impl GameDownloadAgent {
         version: String,
         base_dir: PathBuf,
         sender: Sender<DownloadManagerSignal>,
+    ) -> Result<Self, ApplicationDownloadError> {
+        Self::build(id, version, base_dir, sender, false).await
+    }
+
+    /// An agent that checks an installed game's files instead of downloading
+    /// them
+    pub async fn new_for_verify(
+        id: String,
+        version: String,
+        base_dir: PathBuf,
+        sender: Sender<DownloadManagerSignal>,
+    ) -> Result<Self, ApplicationDownloadError> {
+        Self::build(id, version, base_dir, sender, true).await
+    }
+
+    async fn build(
+        id: String,
+        version: String,
+        base_dir: PathBuf,
+        sender: Sender<DownloadManagerSignal>,
+        verify_only: bool,
     ) -> Result<Self, ApplicationDownloadError> {
         // Don't run by default
         let control_flag = DownloadThreadControl::new(DownloadThreadControlFlag::Stop);
 
+        let (folder_name, excluded_components) = {
+            let db_lock = borrow_db_checked();
+            let folder_name = db_lock
+                .applications
+                .install_folder_names
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| id.clone());
+            let excluded_components = db_lock
+                .applications
+                .game_excluded_components
+                .get(&id)
+                .cloned()
+                .unwrap_or_default();
+            (folder_name, excluded_components)
+        };
         let base_dir_path = Path::new(&base_dir);
-        let data_base_dir_path = base_dir_path.join(id.clone());
+        let data_base_dir_path = base_dir_path.join(folder_name);
 
-        let stored_manifest =
-            DropData::generate(id.clone(), version.clone(), data_base_dir_path.clone());
+        let (stored_manifest, dropdata_reset) =
+            DropData::load(id.clone(), version.clone(), data_base_dir_path.clone());
 
         let context_lock = stored_manifest.contexts.lock().unwrap().clone();
 
@@ -96,27 +187,33 @@ impl GameDownloadAgent {
             manifest: Mutex::new(None),
             buckets: Mutex::new(Vec::new()),
             context_map: Mutex::new(HashMap::new()),
-            progress: Arc::new(ProgressObject::new(0, 0, sender.clone())),
+            progress: Arc::new(ProgressObject::new(
+                0,
+                0,
+                rolling_window_size(borrow_db_checked().settings.speed_window_secs),
+                sender.clone(),
+            )),
             sender,
             dropdata: stored_manifest,
             status: Mutex::new(DownloadStatus::Queued),
+            statistics: DownloadStatistics::default(),
+            dropdata_reset: AtomicBool::new(dropdata_reset),
+            excluded_components,
+            verify_only,
+            verified: AtomicBool::new(false),
+            resume_checked: AtomicBool::new(false),
+            validation_failures: Mutex::new(HashMap::new()),
         };
 
         result.ensure_manifest_exists().await?;
 
-        let required_space = lock!(result.manifest)
-            .as_ref()
-            .unwrap()
-            .values()
-            .map(|e| {
-                e.lengths
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| *context_lock.get(&e.checksums[*i]).unwrap_or(&false))
-                    .map(|(_, v)| v)
-                    .sum::<usize>()
-            })
-            .sum::<usize>() as u64;
+        // Verifying doesn't download anything, so doesn't need the space
+        if verify_only {
+            return Ok(result);
+        }
+
+        let required_space =
+            remaining_size(lock!(result.manifest).as_ref().unwrap(), &context_lock);
 
         let available_space = get_disk_available(data_base_dir_path)? as u64;
 
@@ -131,37 +228,166 @@ impl GameDownloadAgent {
     }
 
     // Blocking
-    pub fn setup_download(&self, app_handle: &AppHandle) -> Result<(), ApplicationDownloadError> {
-        let mut db_lock = borrow_db_mut_checked();
+    pub fn setup_download(&self, app_handle: &AppHandle) -> Result<bool, ApplicationDownloadError> {
         let status = ApplicationTransientStatus::Downloading {
             version_name: self.version.clone(),
         };
-        db_lock
+        // Released straight away, generating buckets reads the settings
+        borrow_db_mut_checked()
             .applications
             .transient_statuses
             .insert(self.metadata(), status.clone());
         // Don't use GameStatusManager because this game isn't installed
         push_game_update(app_handle, &self.metadata().id, None, (None, Some(status)));
 
+        if self.dropdata_reset.swap(false, Ordering::AcqRel) {
+            app_emit!(
+                app_handle,
+                "download_warning",
+                format!(
+                    "Saved download progress for {} was corrupted, so it will be re-checked from scratch",
+                    self.id
+                )
+            );
+        }
+
         if !self.check_manifest_exists() {
             return Err(ApplicationDownloadError::NotInitialized);
         }
 
-        self.ensure_buckets()?;
-
+        // Set before generating buckets so a cancel during preallocation is seen
         self.control_flag.set(DownloadThreadControlFlag::Go);
 
-        Ok(())
+        push_download_phase(app_handle, &self.id, DownloadPhase::Preallocating);
+
+        if !self.ensure_buckets()? {
+            return Ok(false);
+        }
+
+        let verify_on_resume = borrow_db_checked().settings.verify_on_resume;
+        if verify_on_resume && !self.verify_only && !self.resume_checked.load(Ordering::Acquire) {
+            if !self.verify_completed_chunks(app_handle)? {
+                return Ok(false);
+            }
+            self.resume_checked.store(true, Ordering::Release);
+            push_download_phase(app_handle, &self.id, DownloadPhase::Downloading);
+        }
+
+        Ok(true)
+    }
+
+    /// Checksums the chunks saved progress says are complete, and marks any
+    /// that fail to be downloaded again. A chunk that was being written when
+    /// the app was killed can be marked complete without all of its data, and
+    /// the zeroes preallocated in its place won't match.
+    fn verify_completed_chunks(
+        &self,
+        app_handle: &AppHandle,
+    ) -> Result<bool, ApplicationDownloadError> {
+        // Still set if this download ran before being paused, but reading
+        // back from disk doesn't count towards the quota
+        self.progress.set_metered(false);
+        let contexts = self.dropdata.get_contexts();
+        let completed = lock!(self.buckets)
+            .clone()
+            .into_iter()
+            .flat_map(|e| -> Vec<DropValidateContext> { e.into() })
+            .filter(|context| *contexts.get(&context.checksum).unwrap_or(&false))
+            .collect::<Vec<DropValidateContext>>();
+        if completed.is_empty() {
+            return Ok(true);
+        }
+
+        info!(
+            "checking {} chunks saved as complete for {}",
+            completed.len(),
+            self.id
+        );
+        push_download_phase(app_handle, &self.id, DownloadPhase::Validating);
+        self.progress
+            .set_max(completed.iter().map(|context| context.length).sum());
+        self.progress.set_size(completed.len());
+        self.progress.reset();
+
+        let (max_download_threads, io_buffer_size) = {
+            let db_lock = borrow_db_checked();
+            (
+                db_lock.settings.max_download_threads,
+                db_lock.settings.io_buffer_size,
+            )
+        };
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(max_download_threads)
+            .build()
+            .unwrap_or_else(|_| {
+                panic!("failed to build thread pool with {max_download_threads} threads")
+            });
+
+        let invalid_chunks = boxcar::Vec::new();
+        let error = Mutex::new(None);
+        pool.scope(|scope| {
+            for (index, context) in completed.iter().enumerate() {
+                let progress_handle =
+                    ProgressHandle::new(self.progress.get(index), self.progress.clone());
+                let invalid_chunks = &invalid_chunks;
+                let error = &error;
+
+                scope.spawn(move |_| {
+                    match validate_game_chunk(
+                        context,
+                        &self.control_flag,
+                        progress_handle,
+                        io_buffer_size,
+                    ) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            invalid_chunks.push(context);
+                        }
+                        Err(e) => {
+                            lock!(error).get_or_insert(e);
+                        }
+                    }
+                });
+            }
+        });
+
+        if let Some(e) = lock!(error).take() {
+            return Err(e);
+        }
+        // Stopped chunks look invalid, so don't trust any of it
+        if self.control_flag.get() == DownloadThreadControlFlag::Stop {
+            return Ok(false);
+        }
+
+        if !invalid_chunks.is_empty() {
+            for (_, context) in invalid_chunks.iter() {
+                warn!(
+                    "{} was saved as complete but failed validation: {} at offset {}",
+                    context.checksum, context.filename, context.offset
+                );
+                self.dropdata.set_context(context.checksum.clone(), false);
+            }
+            self.dropdata.write();
+            *lock!(self.context_map) = self.dropdata.get_contexts();
+        }
+
+        Ok(true)
     }
 
     // Blocking
     pub fn download(&self, app_handle: &AppHandle) -> Result<bool, ApplicationDownloadError> {
-        self.setup_download(app_handle)?;
+        if !self.setup_download(app_handle)? {
+            return Ok(false);
+        }
+        self.statistics.mark_started();
         let timer = Instant::now();
 
         info!("beginning download for {}...", self.metadata().id);
 
-        let res = self.run().map_err(ApplicationDownloadError::Communication);
+        let res = self
+            .run(app_handle)
+            .map_err(ApplicationDownloadError::Communication);
+        self.statistics.add_download_time(timer.elapsed());
 
         debug!(
             "{} took {}ms to download",
@@ -171,6 +397,19 @@ impl GameDownloadAgent {
         res
     }
 
+    /// Stands in for `download` when verifying. The first call only prepares
+    /// the buckets so validation can run. Being called again means validation
+    /// found bad chunks, which is reported as a `Corrupted` error rather than
+    /// downloading here. See `on_error` for what's done with the game.
+    fn verify(&self, app_handle: &AppHandle) -> Result<bool, ApplicationDownloadError> {
+        if self.verified.swap(true, Ordering::AcqRel) {
+            info!("verification of {} found corrupted files", self.id);
+            return Err(ApplicationDownloadError::Corrupted(self.id.clone()));
+        }
+
+        self.setup_download(app_handle)
+    }
+
     pub fn check_manifest_exists(&self) -> bool {
         lock!(self.manifest).is_some()
     }
@@ -184,33 +423,27 @@ impl GameDownloadAgent {
     }
 
     async fn download_manifest(&self) -> Result<(), ApplicationDownloadError> {
-        let client = DROP_CLIENT_ASYNC.clone();
-        let url = generate_url(
-            &["/api/v1/client/game/manifest"],
-            &[("id", &self.id), ("version", &self.version)],
-        )
-        .map_err(ApplicationDownloadError::Communication)?;
-
-        let response = client
-            .get(url)
-            .header("Authorization", generate_authorization_header())
-            .send()
-            .await
-            .map_err(|e| ApplicationDownloadError::Communication(e.into()))?;
-
-        if response.status() != 200 {
-            return Err(ApplicationDownloadError::Communication(
-                RemoteAccessError::ManifestDownloadFailed(
-                    response.status(),
-                    response.text().await.unwrap(),
-                ),
-            ));
-        }
+        let mut manifest_download = match self.dropdata.get_manifest() {
+            Some(manifest) => manifest,
+            None => {
+                let manifest = fetch_manifest(&self.id, &self.version)
+                    .await
+                    .map_err(ApplicationDownloadError::Communication)?;
+                forget_manifest(&self.id, &self.version);
+                // Stored before excluding components, so they can be added later
+                self.dropdata.set_manifest(manifest.clone());
+                manifest
+            }
+        };
 
-        let manifest_download: DropManifest = response
-            .json()
-            .await
-            .map_err(|e| ApplicationDownloadError::Communication(e.into()))?;
+        // Excluded files are left out of preallocation, buckets and
+        // validation entirely, so they never count as missing
+        manifest_download.retain(|_, chunk| {
+            chunk
+                .component
+                .as_ref()
+                .is_none_or(|component| !self.excluded_components.contains(component))
+        });
 
         if let Ok(mut manifest) = self.manifest.lock() {
             *manifest = Some(manifest_download);
@@ -236,17 +469,17 @@ impl GameDownloadAgent {
         self.progress.reset();
     }
 
-    pub fn ensure_buckets(&self) -> Result<(), ApplicationDownloadError> {
-        if lock!(self.buckets).is_empty() {
-            self.generate_buckets()?;
+    pub fn ensure_buckets(&self) -> Result<bool, ApplicationDownloadError> {
+        if lock!(self.buckets).is_empty() && !self.generate_buckets()? {
+            return Ok(false);
         }
 
         *lock!(self.context_map) = self.dropdata.get_contexts();
 
-        Ok(())
+        Ok(true)
     }
 
-    pub fn generate_buckets(&self) -> Result<(), ApplicationDownloadError> {
+    pub fn generate_buckets(&self) -> Result<bool, ApplicationDownloadError> {
         let manifest = lock!(self.manifest)
             .clone()
             .ok_or(ApplicationDownloadError::NotInitialized)?;
@@ -255,6 +488,30 @@ impl GameDownloadAgent {
         let base_path = Path::new(&self.dropdata.base_path);
         create_dir_all(base_path)?;
 
+        // Updating in place, so move the chunks this version shares with the
+        // previous one to where they now belong before anything is written
+        let previous_manifest = lock!(self.dropdata.previous_manifest).take();
+        if let Some(previous_manifest) = previous_manifest {
+            let missing = reuse_previous_chunks(
+                base_path,
+                &previous_manifest,
+                &manifest,
+                &self.dropdata.get_contexts(),
+            );
+            for checksum in missing {
+                self.dropdata.set_context(checksum, false);
+            }
+        }
+
+        if !preallocate_files(base_path, &manifest, || {
+            self.control_flag.get() == DownloadThreadControlFlag::Stop
+        })? {
+            info!("stopped preparing files for {}", self.id);
+            return Ok(false);
+        }
+
+        let (target_bucket_size, max_files_per_bucket) = bucket_limits();
+
         let mut buckets = Vec::new();
 
         let mut current_buckets = HashMap::<String, DownloadBucket>::new();
@@ -262,22 +519,6 @@ impl GameDownloadAgent {
 
         for (raw_path, chunk) in manifest {
             let path = base_path.join(Path::new(&raw_path));
-
-            let container = path
-                .parent()
-                .ok_or(ApplicationDownloadError::IoError(Arc::new(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "no parent directory",
-                ))))?;
-            create_dir_all(container)?;
-
-            let already_exists = path.exists();
-            let file = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .truncate(false)
-                .open(&path)?;
             let mut file_running_offset = 0;
 
             for (index, length) in chunk.lengths.iter().enumerate() {
@@ -286,13 +527,14 @@ impl GameDownloadAgent {
                     start: file_running_offset,
                     length: *length,
                     checksum: chunk.checksums[index].clone(),
+                    checksum_algorithm: chunk.checksum_algorithm.clone(),
                     permissions: chunk.permissions,
                     path: path.clone(),
                     index,
                 };
                 file_running_offset += *length;
 
-                if *length >= TARGET_BUCKET_SIZE {
+                if *length >= target_bucket_size {
                     // They get their own bucket
 
                     buckets.push(DownloadBucket {
@@ -317,8 +559,8 @@ impl GameDownloadAgent {
                         drops: vec![],
                     });
 
-                if (*current_bucket_size + length >= TARGET_BUCKET_SIZE
-                    || current_bucket.drops.len() >= MAX_FILES_PER_BUCKET)
+                if (*current_bucket_size + length >= target_bucket_size
+                    || current_bucket.drops.len() >= max_files_per_bucket)
                     && !current_bucket.drops.is_empty()
                 {
                     // Move current bucket into list and make a new one
@@ -334,11 +576,6 @@ impl GameDownloadAgent {
                 current_bucket.drops.push(drop);
                 *current_bucket_size += *length;
             }
-
-            #[cfg(target_os = "linux")]
-            if file_running_offset > 0 && !already_exists {
-                let _ = fallocate(file, FallocateFlags::empty(), 0, file_running_offset as u64);
-            }
         }
 
         for (_, bucket) in current_buckets.into_iter() {
@@ -363,12 +600,21 @@ impl GameDownloadAgent {
 
         *lock!(self.buckets) = buckets;
 
-        Ok(())
+        Ok(true)
     }
 
-    fn run(&self) -> Result<bool, RemoteAccessError> {
+    fn run(&self, app_handle: &AppHandle) -> Result<bool, RemoteAccessError> {
         self.setup_progress();
-        let max_download_threads = borrow_db_checked().settings.max_download_threads;
+        self.progress.set_metered(true);
+        let (max_download_threads, io_buffer_size, retry_attempts, retry_delay) = {
+            let db_lock = borrow_db_checked();
+            (
+                db_lock.settings.max_download_threads,
+                db_lock.settings.io_buffer_size,
+                db_lock.settings.download_retry_attempts.max(1),
+                Duration::from_millis(db_lock.settings.download_retry_delay_ms),
+            )
+        };
 
         debug!(
             "downloading game: {} with {} threads",
@@ -383,8 +629,6 @@ impl GameDownloadAgent {
 
         let buckets = lock!(self.buckets);
 
-        let mut download_contexts = HashMap::<String, DownloadContext>::new();
-
         let versions = buckets
             .iter()
             .map(|e| &e.version)
@@ -398,29 +642,19 @@ impl GameDownloadAgent {
         let completed_contexts = Arc::new(boxcar::Vec::new());
         let completed_indexes_loop_arc = completed_contexts.clone();
 
-        for version in versions {
-            let download_context = DROP_CLIENT_SYNC
-                .post(generate_url(&["/api/v2/client/context"], &[])?)
-                .json(&ManifestBody {
-                    game: self.id.clone(),
-                    version: version.clone(),
-                })
-                .header("Authorization", generate_authorization_header())
-                .send()?;
-
-            if download_context.status() != 200 {
-                return Err(RemoteAccessError::InvalidResponse(download_context.json()?));
-            }
+        push_download_phase(app_handle, &self.id, DownloadPhase::FetchingContexts);
+        let download_contexts = DownloadContexts::fetch_all(self.id.clone(), versions)?;
+        let download_contexts = &download_contexts;
 
-            let download_context = download_context.json::<DownloadContext>()?;
-            info!(
-                "download context: ({}) {}",
-                &version, download_context.context
-            );
-            download_contexts.insert(version, download_context);
-        }
+        push_download_phase(app_handle, &self.id, DownloadPhase::Downloading);
 
-        let download_contexts = &download_contexts;
+        let file_progress = FileProgress::new(
+            app_handle.clone(),
+            self.id.clone(),
+            &buckets,
+            &lock!(self.context_map),
+        );
+        let file_progress = &file_progress;
 
         pool.scope(|scope| {
             let context_map = lock!(self.context_map);
@@ -453,32 +687,79 @@ impl GameDownloadAgent {
 
                 let sender = self.sender.clone();
 
-                let download_context =
-                    download_contexts.get(&bucket.version).unwrap_or_else(|| {
-                        panic!(
-                            "Could not get bucket version {}. Corrupted state.",
-                            bucket.version
-                        )
-                    });
-
                 scope.spawn(move |_| {
-                    // 3 attempts
-                    for i in 0..RETRY_COUNT {
+                    let mut download_context = match download_contexts.get(&bucket.version) {
+                        Ok(download_context) => download_context,
+                        Err(e) => {
+                            send!(
+                                sender,
+                                DownloadManagerSignal::Error(
+                                    self.metadata(),
+                                    ApplicationDownloadError::Communication(e)
+                                )
+                            );
+                            return;
+                        }
+                    };
+                    for i in 0..retry_attempts {
                         let loop_progress_handle = progress_handle.clone();
                         match download_game_bucket(
                             &bucket,
-                            download_context,
+                            &download_context,
                             &self.control_flag,
                             loop_progress_handle,
+                            file_progress,
+                            io_buffer_size,
                         ) {
                             Ok(true) => {
+                                self.statistics.add_downloaded(
+                                    bucket.drops.iter().map(|drop| drop.length).sum(),
+                                );
                                 for drop in bucket.drops {
                                     completed_contexts.push(drop.checksum);
                                 }
                                 return;
                             }
                             Ok(false) => return,
+                            Err(ApplicationDownloadError::ContextExpired)
+                                if i < retry_attempts - 1 =>
+                            {
+                                info!(
+                                    "download context for {} expired, refetching",
+                                    bucket.version
+                                );
+                                match download_contexts.refresh(&bucket.version, &download_context)
+                                {
+                                    Ok(refreshed) => download_context = refreshed,
+                                    Err(e) => {
+                                        send!(
+                                            sender,
+                                            DownloadManagerSignal::Error(
+                                                self.metadata(),
+                                                ApplicationDownloadError::Communication(e)
+                                            )
+                                        );
+                                        return;
+                                    }
+                                }
+                                self.statistics.add_retry();
+                            }
                             Err(e) => {
+                                // The drive or share went away, retrying won't help.
+                                // A single missing file is just a normal error.
+                                let e = match e {
+                                    ApplicationDownloadError::IoError(error)
+                                        if matches!(
+                                            error.kind(),
+                                            io::ErrorKind::NotFound | io::ErrorKind::NotConnected
+                                        ) && !self.dropdata.base_path.exists() =>
+                                    {
+                                        ApplicationDownloadError::InstallDirUnavailable(
+                                            self.dropdata.base_path.clone(),
+                                        )
+                                    }
+                                    e => e,
+                                };
                                 warn!("game download agent error: {e}");
 
                                 let retry = matches!(
@@ -489,9 +770,16 @@ impl GameDownloadAgent {
                                         | ApplicationDownloadError::IoError(_)
                                 );
 
-                                if i == RETRY_COUNT - 1 || !retry {
+                                if i == retry_attempts - 1 || !retry {
                                     warn!("retry logic failed, not re-attempting.");
-                                    send!(sender, DownloadManagerSignal::Error(e));
+                                    send!(sender, DownloadManagerSignal::Error(self.metadata(), e));
+                                    return;
+                                }
+                                self.statistics.add_retry();
+
+                                let delay = retry_delay_for(retry_delay, i, &e);
+                                debug!("retrying bucket in {}ms", delay.as_millis());
+                                if !wait_for_retry(delay, &self.control_flag) {
                                     return;
                                 }
                             }
@@ -542,7 +830,10 @@ impl GameDownloadAgent {
     }
 
     fn setup_validate(&self, app_handle: &AppHandle) {
+        push_download_phase(app_handle, &self.id, DownloadPhase::Validating);
         self.setup_progress();
+        // Only reads from disk, so doesn't count towards the quota
+        self.progress.set_metered(false);
 
         self.control_flag.set(DownloadThreadControlFlag::Go);
 
@@ -567,7 +858,13 @@ impl GameDownloadAgent {
             .into_iter()
             .flat_map(|e| -> Vec<DropValidateContext> { e.into() })
             .collect();
-        let max_download_threads = borrow_db_checked().settings.max_download_threads;
+        let (max_download_threads, io_buffer_size) = {
+            let db_lock = borrow_db_checked();
+            (
+                db_lock.settings.max_download_threads,
+                db_lock.settings.io_buffer_size,
+            )
+        };
 
         info!("{} validation contexts", contexts.len());
         let pool = ThreadPoolBuilder::new()
@@ -586,14 +883,19 @@ impl GameDownloadAgent {
                 let sender = self.sender.clone();
 
                 scope.spawn(move |_| {
-                    match validate_game_chunk(context, &self.control_flag, progress_handle) {
+                    match validate_game_chunk(
+                        context,
+                        &self.control_flag,
+                        progress_handle,
+                        io_buffer_size,
+                    ) {
                         Ok(true) => {}
                         Ok(false) => {
-                            invalid_chunks_scoped.push(context.checksum.clone());
+                            invalid_chunks_scoped.push(context);
                         }
                         Err(e) => {
                             error!("{e}");
-                            send!(sender, DownloadManagerSignal::Error(e));
+                            send!(sender, DownloadManagerSignal::Error(self.metadata(), e));
                         }
                     }
                 });
@@ -604,12 +906,46 @@ impl GameDownloadAgent {
         if !invalid_chunks.is_empty() {
             info!("validation of game id {} failed", self.id);
 
-            for context in invalid_chunks.iter() {
-                self.dropdata.set_context(context.1.clone(), false);
+            let mut chunks = Vec::new();
+            let mut repeated = None;
+            {
+                let mut validation_failures = lock!(self.validation_failures);
+                for (_, context) in invalid_chunks.iter() {
+                    warn!(
+                        "{} failed validation: {} at offset {}",
+                        context.checksum, context.filename, context.offset
+                    );
+                    self.dropdata.set_context(context.checksum.clone(), false);
+
+                    let failures = validation_failures
+                        .entry(context.checksum.clone())
+                        .or_default();
+                    *failures += 1;
+                    if *failures >= MAX_VALIDATION_FAILURES {
+                        repeated = Some(context.filename.clone());
+                    }
+
+                    chunks.push(InvalidChunk {
+                        filename: context.filename.clone(),
+                        offset: context.offset,
+                        checksum: context.checksum.clone(),
+                    });
+                }
             }
 
             self.dropdata.write();
+            app_emit!(
+                app_handle,
+                "download_validation_failed",
+                ValidationFailedEvent {
+                    game_id: self.id.clone(),
+                    chunks,
+                }
+            );
 
+            if let Some(filename) = repeated {
+                return Err(ApplicationDownloadError::RepeatedChecksum(filename));
+            }
             return Ok(false);
         }
 
@@ -628,9 +964,126 @@ impl GameDownloadAgent {
     }
 }
 
+/// The bucket size and file count from settings. The file count is clamped so
+/// every download thread can have a full bucket open without going over the
+/// process's fd limit.
+fn bucket_limits() -> (usize, usize) {
+    let db_lock = borrow_db_checked();
+    let settings = &db_lock.settings;
+
+    let mut max_files_per_bucket = settings.max_files_per_bucket.max(1);
+    if let Some(fd_limit) = soft_fd_limit() {
+        let threads =
+            settings.max_download_threads.max(1) * settings.max_concurrent_downloads.max(1);
+        let allowed = (fd_limit / threads).saturating_sub(1).max(1);
+        if max_files_per_bucket > allowed {
+            warn!(
+                "{max_files_per_bucket} files per bucket across {threads} threads would go over the fd limit of {fd_limit}, using {allowed}"
+            );
+            max_files_per_bucket = allowed;
+        }
+    }
+
+    (settings.bucket_target_size, max_files_per_bucket)
+}
+
+/// How long to wait before retrying after the `attempt`th failure (from 0),
+/// doubling each time
+pub(crate) fn retry_delay_for(
+    base_delay: Duration,
+    attempt: usize,
+    error: &ApplicationDownloadError,
+) -> Duration {
+    let is_connect = matches!(
+        error,
+        ApplicationDownloadError::Communication(RemoteAccessError::FetchError(e)) if e.is_connect()
+    );
+    let multiplier = if is_connect {
+        CONNECT_RETRY_MULTIPLIER
+    } else {
+        1
+    };
+    let backoff = 2u32.saturating_pow(attempt.try_into().unwrap_or(u32::MAX));
+
+    base_delay
+        .saturating_mul(backoff)
+        .saturating_mul(multiplier)
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Sleeps for `delay`, waking up early to return false if the download is
+/// paused in the meantime
+fn wait_for_retry(delay: Duration, control_flag: &DownloadThreadControl) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < delay {
+        if control_flag.get() == DownloadThreadControlFlag::Stop {
+            return false;
+        }
+        sleep(RETRY_WAIT_STEP.min(delay.saturating_sub(started.elapsed())));
+    }
+    control_flag.get() != DownloadThreadControlFlag::Stop
+}
+
+/// Creates (and where supported, preallocates) every file in the manifest.
+/// This can take a while for large games, so `should_stop` is checked between
+/// files. If it returns true, any files created by this call are removed again
+/// and `Ok(false)` is returned.
+pub(crate) fn preallocate_files(
+    base_path: &Path,
+    manifest: &DropManifest,
+    should_stop: impl Fn() -> bool,
+) -> Result<bool, ApplicationDownloadError> {
+    let mut created_files = Vec::new();
+
+    for (raw_path, chunk) in manifest {
+        if should_stop() {
+            for path in created_files {
+                if let Err(e) = remove_file(&path) {
+                    warn!("failed to remove {} after stopping: {e}", path.display());
+                }
+            }
+            return Ok(false);
+        }
+
+        let path = base_path.join(Path::new(raw_path));
+
+        let container = path
+            .parent()
+            .ok_or(ApplicationDownloadError::IoError(Arc::new(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no parent directory",
+            ))))?;
+        create_dir_all(container)?;
+
+        let already_exists = path.exists();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if already_exists {
+            continue;
+        }
+
+        let file_length = chunk.lengths.iter().sum::<usize>();
+        if file_length > 0
+            && let Err(e) = preallocate_file(&file, file_length as u64)
+        {
+            warn!("couldn't preallocate {}: {e}", path.display());
+        }
+        created_files.push(path);
+    }
+
+    Ok(true)
+}
+
 impl Downloadable for GameDownloadAgent {
     fn download(&self, app_handle: &AppHandle) -> Result<bool, ApplicationDownloadError> {
         *lock!(self.status) = DownloadStatus::Downloading;
+        if self.verify_only {
+            return self.verify(app_handle);
+        }
         self.download(app_handle)
     }
 
@@ -655,6 +1108,43 @@ impl Downloadable for GameDownloadAgent {
         }
     }
 
+    fn display_name(&self) -> String {
+        get_cached_object::<Game>(&self.id)
+            .map(|game| game.name().clone())
+            .unwrap_or_else(|_| self.id.clone())
+    }
+
+    fn history_entry(&self) -> Option<DownloadHistoryEntry> {
+        // Nothing new was installed
+        if self.verify_only {
+            return None;
+        }
+        let summary = self.statistics.summarise(
+            self.id.clone(),
+            self.version.clone(),
+            self.progress.get_max(),
+        );
+        let completed_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        Some(DownloadHistoryEntry {
+            game_id: summary.game_id,
+            version: summary.version,
+            bytes_downloaded: summary.bytes_downloaded as u64,
+            duration_secs: summary.elapsed_secs,
+            completed_at,
+        })
+    }
+
+    fn install_dir(&self) -> PathBuf {
+        let base_path = &self.dropdata.base_path;
+        base_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| base_path.clone())
+    }
+
     fn on_queued(&self, app_handle: &tauri::AppHandle) {
         *self.status.lock().unwrap() = DownloadStatus::Queued;
         let mut db_lock = borrow_db_mut_checked();
@@ -668,10 +1158,45 @@ impl Downloadable for GameDownloadAgent {
         push_game_update(app_handle, &self.id, None, (None, Some(status)));
     }
 
+    fn on_paused(&self, app_handle: &tauri::AppHandle) {
+        *lock!(self.status) = DownloadStatus::Paused;
+        // Still waiting its turn as far as the library is concerned
+        let mut db_lock = borrow_db_mut_checked();
+        let status = ApplicationTransientStatus::Queued {
+            version_name: self.version.clone(),
+        };
+        db_lock
+            .applications
+            .transient_statuses
+            .insert(self.metadata(), status.clone());
+        push_game_update(app_handle, &self.id, None, (None, Some(status)));
+    }
+
     fn on_error(&self, app_handle: &tauri::AppHandle, error: &ApplicationDownloadError) {
+        if let ApplicationDownloadError::InstallDirUnavailable(dir) = error {
+            *lock!(self.status) = DownloadStatus::Paused;
+            app_emit!(app_handle, "download_error", error.to_string());
+            warn!("pausing {}: {} went away", self.id, dir.display());
+
+            // So it shows up as resumable, even if Drop is closed before the
+            // drive comes back
+            set_partially_installed(
+                &self.metadata(),
+                dir.to_string_lossy().to_string(),
+                Some(app_handle),
+            );
+            return;
+        }
+
         *lock!(self.status) = DownloadStatus::Error;
         app_emit!(app_handle, "download_error", error.to_string());
 
+        if let ApplicationDownloadError::Corrupted(_) = error {
+            // Left partially installed for the normal resume flow to repair
+            self.cancel(app_handle);
+            return;
+        }
+
         error!("error while managing download: {error:?}");
 
         let mut handle = borrow_db_mut_checked();
@@ -694,12 +1219,28 @@ impl Downloadable for GameDownloadAgent {
             self.dropdata.base_path.to_string_lossy().to_string(),
             app_handle,
         ) {
-            Ok(_) => {}
+            Ok(_) if self.verify_only => info!("verified {}", self.id),
+            Ok(_) => {
+                let summary = self.statistics.summarise(
+                    self.id.clone(),
+                    self.version.clone(),
+                    self.progress.get_max(),
+                );
+                info!(
+                    "{} finished: {} bytes downloaded, {} bytes reused, {} retries",
+                    self.id, summary.bytes_downloaded, summary.bytes_reused, summary.retries
+                );
+                app_emit!(app_handle, "download_summary", &summary);
+                record_download_summary(summary);
+            }
             Err(e) => {
                 error!("could not mark game as complete: {e}");
                 send!(
                     self.sender,
-                    DownloadManagerSignal::Error(ApplicationDownloadError::DownloadError(e))
+                    DownloadManagerSignal::Error(
+                        self.metadata(),
+                        ApplicationDownloadError::DownloadError(e)
+                    )
                 );
             }
         }
@@ -713,4 +1254,16 @@ impl Downloadable for GameDownloadAgent {
     fn status(&self) -> DownloadStatus {
         lock!(self.status).clone()
     }
+
+    fn debug_state(&self) -> DownloadableDebugState {
+        // A running download holds these for as long as it runs
+        let contexts = self.context_map.try_lock().ok();
+        DownloadableDebugState {
+            completed_contexts: contexts
+                .as_ref()
+                .map(|contexts| contexts.values().filter(|x| **x).count()),
+            total_contexts: contexts.as_ref().map(|contexts| contexts.len()),
+            buckets: self.buckets.try_lock().ok().map(|buckets| buckets.len()),
+        }
+    }
 }
@@ -1,13 +1,14 @@
 use std::fs::{Permissions, set_permissions};
 use std::io::Read;
+use std::ops::Range;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::sync::Arc;
+use std::thread;
 use std::time::Instant;
 use std::{
     fs::{File, OpenOptions},
     io::{self, BufWriter, Seek, SeekFrom, Write},
-    path::PathBuf,
 };
 
 use download_manager::error::ApplicationDownloadError;
@@ -16,38 +17,48 @@ use download_manager::util::download_thread_control_flag::{
 };
 use download_manager::util::progress_object::ProgressHandle;
 use log::{debug, info, warn};
-use md5::{Context, Digest};
-use remote::auth::generate_authorization_header;
 use remote::error::{DropServerError, RemoteAccessError};
-use remote::requests::generate_url;
+use remote::requests::{generate_authenticated_headers, generate_url};
 use remote::utils::DROP_CLIENT_SYNC;
 use reqwest::blocking::Response;
+use reqwest::header::{ACCEPT_RANGES, RANGE};
+use reqwest::{StatusCode, Url};
 
+use crate::downloads::checksum::ChunkHasher;
+use crate::downloads::file_progress::{BucketFileProgress, FileProgress};
 use crate::downloads::manifest::{ChunkBody, DownloadBucket, DownloadContext, DownloadDrop};
 
-static MAX_PACKET_LENGTH: usize = 4096 * 4;
 static BUMP_SIZE: usize = 4096 * 16;
 
+/// Chunks this big that get a bucket to themselves are split into ranges
+const RANGE_SPLIT_THRESHOLD: usize = 32 * 1000 * 1000;
+const RANGE_CONNECTIONS: usize = 4;
+
 pub struct DropWriter<W: Write> {
-    hasher: Context,
+    hasher: ChunkHasher,
     destination: BufWriter<W>,
     progress: ProgressHandle,
 }
 impl DropWriter<File> {
-    fn new(path: PathBuf, progress: ProgressHandle) -> Result<Self, io::Error> {
+    fn new(
+        drop: &DownloadDrop,
+        progress: ProgressHandle,
+        buffer_size: usize,
+    ) -> Result<Self, ApplicationDownloadError> {
+        let hasher = ChunkHasher::new(drop.checksum_algorithm.as_deref())?;
         let destination = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(false)
-            .open(&path)?;
+            .open(&drop.path)?;
         Ok(Self {
-            destination: BufWriter::with_capacity(1024 * 1024, destination),
-            hasher: Context::new(),
+            destination: BufWriter::with_capacity(buffer_size, destination),
+            hasher,
             progress,
         })
     }
 
-    fn finish(mut self) -> io::Result<Digest> {
+    fn finish(mut self) -> io::Result<String> {
         self.flush()?;
         Ok(self.hasher.finalize())
     }
@@ -83,6 +94,8 @@ pub struct DropDownloadPipeline<'a, R: Read, W: Write> {
     pub control_flag: &'a DownloadThreadControl,
     #[allow(dead_code)]
     progress: ProgressHandle,
+    file_progress: &'a BucketFileProgress<'a>,
+    buffer_size: usize,
 }
 
 impl<'a> DropDownloadPipeline<'a, Response, File> {
@@ -91,21 +104,25 @@ impl<'a> DropDownloadPipeline<'a, Response, File> {
         drops: Vec<DownloadDrop>,
         control_flag: &'a DownloadThreadControl,
         progress: ProgressHandle,
-    ) -> Result<Self, io::Error> {
+        file_progress: &'a BucketFileProgress<'a>,
+        buffer_size: usize,
+    ) -> Result<Self, ApplicationDownloadError> {
         Ok(Self {
             source,
             destination: drops
                 .iter()
-                .map(|drop| DropWriter::new(drop.path.clone(), progress.clone()))
+                .map(|drop| DropWriter::new(drop, progress.clone(), buffer_size))
                 .try_collect()?,
             drops,
             control_flag,
             progress,
+            file_progress,
+            buffer_size,
         })
     }
 
     fn copy(&mut self) -> Result<bool, io::Error> {
-        let mut copy_buffer = [0u8; MAX_PACKET_LENGTH];
+        let mut copy_buffer = vec![0u8; self.buffer_size];
         for (index, drop) in self.drops.iter().enumerate() {
             let destination = self
                 .destination
@@ -117,7 +134,7 @@ impl<'a> DropDownloadPipeline<'a, Response, File> {
             }
             let mut last_bump = 0;
             loop {
-                let size = MAX_PACKET_LENGTH.min(remaining);
+                let size = self.buffer_size.min(remaining);
                 let size = self
                     .source
                     .read(&mut copy_buffer[0..size])
@@ -128,6 +145,7 @@ impl<'a> DropDownloadPipeline<'a, Response, File> {
                 last_bump += size;
 
                 destination.write_all(&copy_buffer[0..size])?;
+                self.file_progress.add(&drop.filename, size);
 
                 if last_bump > BUMP_SIZE {
                     last_bump -= BUMP_SIZE;
@@ -156,7 +174,7 @@ impl<'a> DropDownloadPipeline<'a, Response, File> {
             .for_each(|mut e| e.flush().unwrap());
     }
 
-    fn finish(self) -> Result<Vec<Digest>, io::Error> {
+    fn finish(self) -> Result<Vec<String>, io::Error> {
         let checksums = self
             .destination
             .into_iter()
@@ -166,11 +184,210 @@ impl<'a> DropDownloadPipeline<'a, Response, File> {
     }
 }
 
+/// The server doesn't have a dedicated status for this, so look for a client
+/// error that mentions the context
+pub(crate) fn is_expired_context(status: StatusCode, body: &str) -> bool {
+    matches!(
+        status,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND | StatusCode::GONE
+    ) && body.to_lowercase().contains("context")
+}
+
+/// Turns an unsuccessful chunk response into the error to retry or fail with
+fn chunk_response_error(response: Response) -> ApplicationDownloadError {
+    let status = response.status();
+    info!("chunk request got status code: {status}");
+    let raw_res = match response.text() {
+        Ok(raw_res) => raw_res,
+        Err(e) => {
+            return ApplicationDownloadError::Communication(RemoteAccessError::FetchError(
+                e.into(),
+            ));
+        }
+    };
+    info!("{raw_res}");
+    if is_expired_context(status, &raw_res) {
+        return ApplicationDownloadError::ContextExpired;
+    }
+    if let Ok(err) = serde_json::from_str::<DropServerError>(&raw_res) {
+        return ApplicationDownloadError::Communication(RemoteAccessError::InvalidResponse(err));
+    }
+    ApplicationDownloadError::Communication(RemoteAccessError::UnparseableResponse(raw_res))
+}
+
+/// Whether the server will serve part of a chunk. Anything other than a clear
+/// yes falls back to a single stream.
+fn supports_ranges(url: &Url, body: &ChunkBody) -> bool {
+    let Ok(headers) = generate_authenticated_headers() else {
+        return false;
+    };
+    match DROP_CLIENT_SYNC
+        .head(url.clone())
+        .json(body)
+        .headers(headers)
+        .send()
+    {
+        Ok(response) => {
+            response.status().is_success()
+                && response
+                    .headers()
+                    .get(ACCEPT_RANGES)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.eq_ignore_ascii_case("bytes"))
+        }
+        Err(e) => {
+            debug!("range support check failed: {e}");
+            false
+        }
+    }
+}
+
+/// Downloads one large chunk over several connections at once, each writing
+/// its own range of the preallocated file
+fn download_drop_ranges(
+    drop: &DownloadDrop,
+    url: &Url,
+    body: &ChunkBody,
+    control_flag: &DownloadThreadControl,
+    progress: ProgressHandle,
+    file_progress: &FileProgress,
+    buffer_size: usize,
+) -> Result<bool, ApplicationDownloadError> {
+    debug!(
+        "downloading {} over {RANGE_CONNECTIONS} connections",
+        drop.filename
+    );
+
+    let part_length = drop.length.div_ceil(RANGE_CONNECTIONS);
+    let bucket_file_progress = BucketFileProgress::new(file_progress);
+
+    let results = thread::scope(|scope| {
+        let handles = (0..drop.length)
+            .step_by(part_length)
+            .map(|part_start| {
+                let part_end = (part_start + part_length).min(drop.length);
+                let progress = progress.clone();
+                let bucket_file_progress = &bucket_file_progress;
+                scope.spawn(move || {
+                    download_drop_range(
+                        drop,
+                        part_start..part_end,
+                        url,
+                        body,
+                        control_flag,
+                        progress,
+                        bucket_file_progress,
+                        buffer_size,
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.join().unwrap_or_else(|_| {
+                    Err(ApplicationDownloadError::IoError(Arc::new(
+                        io::Error::other("range download thread panicked"),
+                    )))
+                })
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut completed = true;
+    for result in results {
+        completed &= result?;
+    }
+    if !completed {
+        return Ok(false);
+    }
+
+    #[cfg(unix)]
+    set_permissions(drop.path.clone(), Permissions::from_mode(drop.permissions))
+        .map_err(|e| ApplicationDownloadError::IoError(Arc::new(e)))?;
+
+    // The parts can't be hashed as one stream, so like a mismatch on a single
+    // stream, this is left for validation to catch
+    bucket_file_progress.complete();
+    Ok(true)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn download_drop_range(
+    drop: &DownloadDrop,
+    range: Range<usize>,
+    url: &Url,
+    body: &ChunkBody,
+    control_flag: &DownloadThreadControl,
+    progress: ProgressHandle,
+    file_progress: &BucketFileProgress,
+    buffer_size: usize,
+) -> Result<bool, ApplicationDownloadError> {
+    let mut response = DROP_CLIENT_SYNC
+        .post(url.clone())
+        .json(body)
+        .headers(generate_authenticated_headers().map_err(ApplicationDownloadError::Communication)?)
+        .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+        .send()
+        .map_err(|e| ApplicationDownloadError::Communication(e.into()))?;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        if response.status().is_success() {
+            return Err(ApplicationDownloadError::DownloadError(
+                RemoteAccessError::UnparseableResponse(format!(
+                    "expected part of {}, got status {}",
+                    drop.filename,
+                    response.status()
+                )),
+            ));
+        }
+        return Err(chunk_response_error(response));
+    }
+
+    let mut destination = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&drop.path)?;
+    destination.seek(SeekFrom::Start((drop.start + range.start) as u64))?;
+    let mut destination = BufWriter::with_capacity(buffer_size, destination);
+
+    let mut copy_buffer = vec![0u8; buffer_size];
+    let mut remaining = range.len();
+    let mut last_bump = 0;
+    while remaining > 0 {
+        let size = response.read(&mut copy_buffer[0..buffer_size.min(remaining)])?;
+        if size == 0 {
+            return Err(ApplicationDownloadError::IoError(Arc::new(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} ended {remaining} bytes early", drop.filename),
+            ))));
+        }
+        destination.write_all(&copy_buffer[0..size])?;
+        progress.add(size);
+        file_progress.add(&drop.filename, size);
+        remaining -= size;
+
+        last_bump += size;
+        if last_bump > BUMP_SIZE {
+            last_bump -= BUMP_SIZE;
+            if control_flag.get() == DownloadThreadControlFlag::Stop {
+                return Ok(false);
+            }
+        }
+    }
+    destination.flush()?;
+
+    Ok(true)
+}
+
 pub fn download_game_bucket(
     bucket: &DownloadBucket,
     ctx: &DownloadContext,
     control_flag: &DownloadThreadControl,
     progress: ProgressHandle,
+    file_progress: &FileProgress,
+    buffer_size: usize,
 ) -> Result<bool, ApplicationDownloadError> {
     // If we're paused
     if control_flag.get() == DownloadThreadControlFlag::Stop {
@@ -180,34 +397,35 @@ pub fn download_game_bucket(
 
     let start = Instant::now();
 
-    let header = generate_authorization_header();
-
     let url = generate_url(&["/api/v2/client/chunk"], &[])
         .map_err(ApplicationDownloadError::Communication)?;
 
     let body = ChunkBody::create(ctx, &bucket.drops);
 
+    if let [drop] = bucket.drops.as_slice()
+        && drop.length >= RANGE_SPLIT_THRESHOLD
+        && supports_ranges(&url, &body)
+    {
+        return download_drop_ranges(
+            drop,
+            &url,
+            &body,
+            control_flag,
+            progress,
+            file_progress,
+            buffer_size,
+        );
+    }
+
     let response = DROP_CLIENT_SYNC
         .post(url)
         .json(&body)
-        .header("Authorization", header)
+        .headers(generate_authenticated_headers().map_err(ApplicationDownloadError::Communication)?)
         .send()
         .map_err(|e| ApplicationDownloadError::Communication(e.into()))?;
 
     if response.status() != 200 {
-        info!("chunk request got status code: {}", response.status());
-        let raw_res = response.text().map_err(|e| {
-            ApplicationDownloadError::Communication(RemoteAccessError::FetchError(e.into()))
-        })?;
-        info!("{raw_res}");
-        if let Ok(err) = serde_json::from_str::<DropServerError>(&raw_res) {
-            return Err(ApplicationDownloadError::Communication(
-                RemoteAccessError::InvalidResponse(err),
-            ));
-        }
-        return Err(ApplicationDownloadError::Communication(
-            RemoteAccessError::UnparseableResponse(raw_res),
-        ));
+        return Err(chunk_response_error(response));
     }
 
     let lengths = response
@@ -257,9 +475,15 @@ pub fn download_game_bucket(
 
     debug!("took {}ms to start downloading", timestep);
 
-    let mut pipeline =
-        DropDownloadPipeline::new(response, bucket.drops.clone(), control_flag, progress)
-            .map_err(|e| ApplicationDownloadError::IoError(Arc::new(e)))?;
+    let bucket_file_progress = BucketFileProgress::new(file_progress);
+    let mut pipeline = DropDownloadPipeline::new(
+        response,
+        bucket.drops.clone(),
+        control_flag,
+        progress,
+        &bucket_file_progress,
+        buffer_size,
+    )?;
 
     let completed = pipeline
         .copy()
@@ -283,13 +507,13 @@ pub fn download_game_bucket(
         .map_err(|e| ApplicationDownloadError::IoError(Arc::new(e)))?;
 
     for (index, drop) in bucket.drops.iter().enumerate() {
-        let res = hex::encode(**checksums.get(index).unwrap());
-        if res != drop.checksum {
+        if checksums[index] != drop.checksum {
             warn!("context didn't match... doing nothing because we will validate later.");
             // return Ok(false);
             // return Err(ApplicationDownloadError::Checksum);
         }
     }
 
+    bucket_file_progress.complete();
     Ok(true)
 }
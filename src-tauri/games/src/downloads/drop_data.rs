@@ -5,11 +5,13 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use log::error;
-use native_model::{Decode, Encode};
+use log::{error, warn};
+
+use super::manifest::DropManifest;
+use native_model::Decode;
 use utils::lock;
 
-pub type DropData = v1::DropData;
+pub type DropData = v2::DropData;
 
 pub static DROP_DATA_PATH: &str = ".dropdata";
 
@@ -27,6 +29,32 @@ pub mod v1 {
         pub contexts: Mutex<HashMap<String, bool>>,
         pub base_path: PathBuf,
     }
+}
+
+pub mod v2 {
+    use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+    use native_model::native_model;
+    use serde::{Deserialize, Serialize};
+
+    use crate::downloads::manifest::DropManifest;
+
+    use super::v1;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[native_model(id = 9, version = 2, with = native_model::rmp_serde_1_3::RmpSerde, from = v1::DropData)]
+    pub struct DropData {
+        pub game_id: String,
+        pub game_version: String,
+        pub contexts: Mutex<HashMap<String, bool>>,
+        pub base_path: PathBuf,
+        // The manifest for game_version, so resuming or verifying doesn't
+        // have to fetch it again
+        pub manifest: Mutex<Option<DropManifest>>,
+        // The manifest of the version on disk, when updating it in place
+        #[serde(skip)]
+        pub previous_manifest: Mutex<Option<DropManifest>>,
+    }
 
     impl DropData {
         pub fn new(game_id: String, game_version: String, base_path: PathBuf) -> Self {
@@ -35,6 +63,21 @@ pub mod v1 {
                 game_id,
                 game_version,
                 contexts: Mutex::new(HashMap::new()),
+                manifest: Mutex::new(None),
+                previous_manifest: Mutex::new(None),
+            }
+        }
+    }
+
+    impl From<v1::DropData> for DropData {
+        fn from(value: v1::DropData) -> Self {
+            Self {
+                game_id: value.game_id,
+                game_version: value.game_version,
+                contexts: value.contexts,
+                base_path: value.base_path,
+                manifest: Mutex::new(None),
+                previous_manifest: Mutex::new(None),
             }
         }
     }
@@ -42,9 +85,41 @@ pub mod v1 {
 
 impl DropData {
     pub fn generate(game_id: String, game_version: String, base_path: PathBuf) -> Self {
+        Self::load(game_id, game_version, base_path).0
+    }
+    /// Reads existing `DropData` if it's there, otherwise starts fresh.
+    /// Corrupted data, or data that belongs to another game, is discarded so
+    /// every context gets re-checked. The returned bool is true if that happened.
+    pub fn load(game_id: String, game_version: String, base_path: PathBuf) -> (Self, bool) {
         match DropData::read(&base_path) {
-            Ok(v) => v,
-            Err(_) => DropData::new(game_id, game_version, base_path),
+            Ok(mut data) if data.game_id == game_id => {
+                if data.game_version != game_version {
+                    let previous_manifest = lock!(data.manifest).take();
+                    *lock!(data.previous_manifest) = previous_manifest;
+                }
+                data.game_version = game_version;
+                data.base_path = base_path;
+                (data, false)
+            }
+            Ok(data) => {
+                warn!(
+                    "{} contains drop data for {}, expected {}. resetting progress",
+                    base_path.display(),
+                    data.game_id,
+                    game_id
+                );
+                (DropData::new(game_id, game_version, base_path), true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                (DropData::new(game_id, game_version, base_path), false)
+            }
+            Err(e) => {
+                warn!(
+                    "discarding unreadable drop data in {}: {e}",
+                    base_path.display()
+                );
+                (DropData::new(game_id, game_version, base_path), true)
+            }
         }
     }
     pub fn read(base_path: &Path) -> Result<Self, io::Error> {
@@ -53,15 +128,21 @@ impl DropData {
         let mut s = Vec::new();
         file.read_to_end(&mut s)?;
 
-        native_model::rmp_serde_1_3::RmpSerde::decode(s).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Failed to decode drop data: {e}"),
-            )
-        })
+        if let Ok((data, _version)) = native_model::decode::<DropData>(s.clone()) {
+            return Ok(data);
+        }
+        // Written before drop data was versioned, so there's no header
+        native_model::rmp_serde_1_3::RmpSerde::decode(s)
+            .map(|data: v1::DropData| data.into())
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed to decode drop data: {e}"),
+                )
+            })
     }
     pub fn write(&self) {
-        let manifest_raw = match native_model::rmp_serde_1_3::RmpSerde::encode(&self) {
+        let manifest_raw = match native_model::encode(self) {
             Ok(data) => data,
             Err(_) => return,
         };
@@ -91,4 +172,10 @@ impl DropData {
     pub fn get_contexts(&self) -> HashMap<String, bool> {
         lock!(self.contexts).clone()
     }
+    pub fn set_manifest(&self, manifest: DropManifest) {
+        *lock!(self.manifest) = Some(manifest);
+    }
+    pub fn get_manifest(&self) -> Option<DropManifest> {
+        lock!(self.manifest).clone()
+    }
 }
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, io, path::PathBuf};
 
 use serde_with::SerializeDisplay;
 
@@ -6,6 +6,10 @@ use serde_with::SerializeDisplay;
 pub enum LibraryError {
     MetaNotFound(String),
     VersionNotFound(String),
+    UninstallFailed(PathBuf, io::Error),
+    InvalidExecutable(String),
+    CannotHide(String),
+    InvalidEnvKey(String),
 }
 impl Display for LibraryError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -23,6 +27,23 @@ impl Display for LibraryError {
                         "Could not locate any installed version  for game id {game_id} in the database"
                     )
                 }
+                LibraryError::UninstallFailed(path, error) => {
+                    format!(
+                        "Failed to remove {} while uninstalling: {error}",
+                        path.display()
+                    )
+                }
+                LibraryError::InvalidExecutable(path) => {
+                    format!("{path} is not a path inside the game's install directory")
+                }
+                LibraryError::CannotHide(game_id) => {
+                    format!("Game {game_id} is installed or downloading, so it can't be hidden")
+                }
+                LibraryError::InvalidEnvKey(key) => {
+                    format!(
+                        "{key:?} is not a valid environment variable name, use only letters, digits and _, not starting with a digit"
+                    )
+                }
             }
         )
     }
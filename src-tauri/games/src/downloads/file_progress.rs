@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use tauri::AppHandle;
+use throttle_my_fn::throttle;
+use utils::{app_emit, lock};
+
+use super::manifest::DownloadBucket;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FileProgressEvent {
+    pub game_id: String,
+    pub filename: String,
+    pub done: usize,
+    pub total: usize,
+}
+
+struct FileTotals {
+    done: AtomicUsize,
+    total: usize,
+}
+
+/// Bytes done per file across every bucket of a download. Large files are
+/// split into several drops, possibly in different buckets, so progress is
+/// added up by filename rather than reported per drop.
+pub struct FileProgress {
+    app_handle: AppHandle,
+    game_id: String,
+    files: HashMap<String, FileTotals>,
+}
+
+impl FileProgress {
+    /// Drops whose checksum is marked complete in `contexts` count as done
+    pub fn new(
+        app_handle: AppHandle,
+        game_id: String,
+        buckets: &[DownloadBucket],
+        contexts: &HashMap<String, bool>,
+    ) -> Self {
+        let mut totals = HashMap::<String, (usize, usize)>::new();
+        for drop in buckets.iter().flat_map(|bucket| &bucket.drops) {
+            let (done, total) = totals.entry(drop.filename.clone()).or_default();
+            *total += drop.length;
+            if *contexts.get(&drop.checksum).unwrap_or(&false) {
+                *done += drop.length;
+            }
+        }
+        let files = totals
+            .into_iter()
+            .map(|(filename, (done, total))| {
+                (
+                    filename,
+                    FileTotals {
+                        done: AtomicUsize::new(done),
+                        total,
+                    },
+                )
+            })
+            .collect();
+        Self {
+            app_handle,
+            game_id,
+            files,
+        }
+    }
+
+    fn add(&self, filename: &str, amount: usize) {
+        let Some(file) = self.files.get(filename) else {
+            return;
+        };
+        let done = file.done.fetch_add(amount, Ordering::AcqRel) + amount;
+        let event = FileProgressEvent {
+            game_id: self.game_id.clone(),
+            filename: filename.to_owned(),
+            done,
+            total: file.total,
+        };
+        // Always report a finished file, so it doesn't look stuck just short
+        if done >= file.total {
+            emit_file_progress(&self.app_handle, event);
+        } else {
+            push_file_progress(&self.app_handle, event);
+        }
+    }
+
+    fn remove(&self, filename: &str, amount: usize) {
+        if let Some(file) = self.files.get(filename) {
+            file.done.fetch_sub(amount, Ordering::AcqRel);
+        }
+    }
+}
+
+/// What one attempt at a bucket has added to each file. Unless the bucket
+/// completes, it's taken back off when this is dropped, since the bucket will
+/// be downloaded again from the start.
+pub struct BucketFileProgress<'a> {
+    file_progress: &'a FileProgress,
+    written: Mutex<HashMap<String, usize>>,
+    completed: bool,
+}
+
+impl<'a> BucketFileProgress<'a> {
+    pub fn new(file_progress: &'a FileProgress) -> Self {
+        Self {
+            file_progress,
+            written: Mutex::new(HashMap::new()),
+            completed: false,
+        }
+    }
+
+    pub fn add(&self, filename: &str, amount: usize) {
+        *lock!(self.written).entry(filename.to_owned()).or_default() += amount;
+        self.file_progress.add(filename, amount);
+    }
+
+    pub fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for BucketFileProgress<'_> {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        for (filename, amount) in lock!(self.written).drain() {
+            self.file_progress.remove(&filename, amount);
+        }
+    }
+}
+
+#[throttle(1, Duration::from_millis(250))]
+fn push_file_progress(app_handle: &AppHandle, event: FileProgressEvent) {
+    emit_file_progress(app_handle, event);
+}
+
+fn emit_file_progress(app_handle: &AppHandle, event: FileProgressEvent) {
+    app_emit!(app_handle, "download_file_progress", event);
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use database::{
+    DownloadType, DownloadableMetadata, GameDownloadStatus, GameVersion, borrow_db_checked,
+};
+use download_manager::error::ApplicationDownloadError;
+use log::{error, info, warn};
+use tauri::AppHandle;
+
+use crate::library::{on_game_complete, register_installed_game, set_partially_installed};
+
+use super::{checksum::ChunkHasher, drop_data::DropData, manifest::DropManifest};
+
+/// Registers a game that was copied onto this machine by hand, checking its
+/// files against a manifest instead of downloading them.
+///
+/// Without `game_version`, the version information is fetched from the
+/// server. If that fails, the game is left partially installed with every
+/// chunk marked complete, so resuming it once online only has to validate.
+pub fn import_offline_game(
+    game_id: String,
+    version: String,
+    dir: PathBuf,
+    manifest_path: &Path,
+    game_version: Option<GameVersion>,
+    app_handle: &AppHandle,
+) -> Result<GameDownloadStatus, ApplicationDownloadError> {
+    let manifest: DropManifest = serde_json::from_slice(&fs::read(manifest_path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut contexts = Vec::new();
+    for (raw_path, chunk) in &manifest {
+        if chunk.lengths.len() != chunk.checksums.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "manifest has {} chunk lengths but {} checksums for {raw_path}",
+                    chunk.lengths.len(),
+                    chunk.checksums.len()
+                ),
+            )
+            .into());
+        }
+        let mut file = File::open(dir.join(raw_path))?;
+        for (index, length) in chunk.lengths.iter().enumerate() {
+            let hasher = ChunkHasher::new(chunk.checksum_algorithm.as_deref())?;
+            let checksum = checksum_chunk(&mut file, *length, hasher)?;
+            if checksum != chunk.checksums[index] {
+                error!("chunk {index} of {raw_path} does not match the manifest");
+                return Err(ApplicationDownloadError::Checksum);
+            }
+            contexts.push((checksum, true));
+        }
+    }
+    info!("imported {} chunks for {game_id}", contexts.len());
+
+    let dropdata = DropData::generate(game_id.clone(), version.clone(), dir.clone());
+    dropdata.set_contexts(&contexts);
+    dropdata.write();
+
+    let meta = DownloadableMetadata::new(game_id.clone(), Some(version), DownloadType::Game);
+    let install_dir = dir.to_string_lossy().to_string();
+    match game_version {
+        Some(game_version) => register_installed_game(&meta, install_dir, game_version, app_handle),
+        None => {
+            if let Err(e) = on_game_complete(&meta, install_dir.clone(), app_handle) {
+                warn!(
+                    "could not fetch version information for {game_id}, finish the install when online: {e}"
+                );
+                set_partially_installed(&meta, install_dir, Some(app_handle));
+            }
+        }
+    }
+
+    Ok(borrow_db_checked()
+        .applications
+        .game_statuses
+        .get(&game_id)
+        .cloned()
+        .unwrap_or(GameDownloadStatus::Remote {}))
+}
+
+fn checksum_chunk(file: &mut File, length: usize, mut hasher: ChunkHasher) -> io::Result<String> {
+    let mut remaining = length;
+    let mut buf = vec![0; 1024 * 1024];
+    while remaining > 0 {
+        let to_read = remaining.min(buf.len());
+        file.read_exact(&mut buf[..to_read])?;
+        hasher.update(&buf[..to_read]);
+        remaining -= to_read;
+    }
+    Ok(hasher.finalize())
+}
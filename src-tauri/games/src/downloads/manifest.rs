@@ -11,6 +11,7 @@ pub struct DownloadDrop {
     pub start: usize,
     pub length: usize,
     pub checksum: String,
+    pub checksum_algorithm: Option<String>,
     pub permissions: u32,
 }
 
@@ -21,7 +22,7 @@ pub struct DownloadBucket {
     pub drops: Vec<DownloadDrop>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct DownloadContext {
     pub context: String,
 }
@@ -62,6 +63,7 @@ impl ChunkBody {
 }
 
 pub type DropManifest = HashMap<String, DropChunk>;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DropChunk {
@@ -70,14 +72,24 @@ pub struct DropChunk {
     pub checksums: Vec<String>,
     pub lengths: Vec<usize>,
     pub version_name: String,
+    // Optional part of the game, like a texture pack. Untagged files are
+    // always installed.
+    #[serde(default)]
+    pub component: Option<String>,
+    // What `checksums` were made with, md5 if unset
+    #[serde(default)]
+    pub checksum_algorithm: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DropValidateContext {
     pub index: usize,
+    pub filename: String,
     pub offset: usize,
     pub path: PathBuf,
     pub checksum: String,
+    #[serde(default)]
+    pub checksum_algorithm: Option<String>,
     pub length: usize,
 }
 
@@ -88,9 +100,11 @@ impl From<DownloadBucket> for Vec<DropValidateContext> {
             .into_iter()
             .map(|e| DropValidateContext {
                 index: e.index,
+                filename: e.filename,
                 offset: e.start,
                 path: e.path,
                 checksum: e.checksum,
+                checksum_algorithm: e.checksum_algorithm,
                 length: e.length,
             })
             .collect()
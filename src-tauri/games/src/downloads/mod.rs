@@ -1,7 +1,14 @@
+pub mod checksum;
+mod context;
+pub(crate) mod delta;
 pub mod download_agent;
-mod download_logic;
+pub(crate) mod download_logic;
 pub mod drop_data;
 pub mod error;
-mod manifest;
+pub mod file_progress;
+pub mod import;
+pub(crate) mod manifest;
+pub mod summary;
+pub mod update;
 pub mod utils;
 pub mod validate;
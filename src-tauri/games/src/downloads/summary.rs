@@ -0,0 +1,85 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use utils::lock;
+
+static DOWNLOAD_SUMMARIES: LazyLock<Mutex<HashMap<String, DownloadSummary>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DownloadSummary {
+    pub game_id: String,
+    pub version: String,
+    pub total_bytes: usize,
+    pub bytes_downloaded: usize,
+    pub bytes_reused: usize,
+    pub elapsed_secs: u64,
+    pub average_speed: usize, // kb/s
+    pub retries: usize,
+}
+
+/// Tracked by each `GameDownloadAgent` across all of its download and
+/// validation passes, and turned into a `DownloadSummary` on completion
+#[derive(Default)]
+pub struct DownloadStatistics {
+    started: Mutex<Option<Instant>>,
+    download_time: Mutex<Duration>,
+    bytes_downloaded: AtomicUsize,
+    retries: AtomicUsize,
+}
+
+impl DownloadStatistics {
+    pub fn mark_started(&self) {
+        let mut started = lock!(self.started);
+        if started.is_none() {
+            *started = Some(Instant::now());
+        }
+    }
+    pub fn add_download_time(&self, duration: Duration) {
+        *lock!(self.download_time) += duration;
+    }
+    pub fn add_downloaded(&self, bytes: usize) {
+        self.bytes_downloaded.fetch_add(bytes, Ordering::AcqRel);
+    }
+    pub fn add_retry(&self) {
+        self.retries.fetch_add(1, Ordering::AcqRel);
+    }
+    pub fn summarise(
+        &self,
+        game_id: String,
+        version: String,
+        total_bytes: usize,
+    ) -> DownloadSummary {
+        let bytes_downloaded = self.bytes_downloaded.load(Ordering::Acquire);
+        let elapsed = lock!(self.started)
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        let download_millis = lock!(self.download_time).as_millis().max(1) as usize;
+
+        DownloadSummary {
+            game_id,
+            version,
+            total_bytes,
+            bytes_downloaded,
+            bytes_reused: total_bytes.saturating_sub(bytes_downloaded),
+            elapsed_secs: elapsed.as_secs(),
+            average_speed: bytes_downloaded / download_millis,
+            retries: self.retries.load(Ordering::Acquire),
+        }
+    }
+}
+
+pub fn record_download_summary(summary: DownloadSummary) {
+    lock!(DOWNLOAD_SUMMARIES).insert(summary.game_id.clone(), summary);
+}
+
+pub fn get_last_download_summary(game_id: &str) -> Option<DownloadSummary> {
+    lock!(DOWNLOAD_SUMMARIES).get(game_id).cloned()
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{LazyLock, Mutex},
+};
+
+use database::{GameDownloadStatus, borrow_db_checked};
+use download_manager::error::ApplicationDownloadError;
+use remote::{
+    error::RemoteAccessError,
+    requests::{generate_authenticated_headers, generate_url},
+    utils::DROP_CLIENT_ASYNC,
+};
+use serde::Serialize;
+use utils::lock;
+
+use super::{drop_data::DropData, manifest::DropManifest};
+
+/// Manifests fetched to size an update, keyed by (game id, version), so
+/// queuing that update doesn't have to fetch them again. Only the last game
+/// looked at is kept, as manifests for big games can be large.
+static MANIFEST_CACHE: LazyLock<Mutex<HashMap<(String, String), DropManifest>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UpdateSize {
+    pub full_size: usize,
+    pub delta_size: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GameComponent {
+    pub name: String,
+    pub size: u64,
+    pub excluded: bool,
+}
+
+pub(crate) async fn fetch_manifest(
+    game_id: &str,
+    version: &str,
+) -> Result<DropManifest, RemoteAccessError> {
+    let key = (game_id.to_owned(), version.to_owned());
+    if let Some(manifest) = lock!(MANIFEST_CACHE).get(&key) {
+        return Ok(manifest.clone());
+    }
+
+    let client = DROP_CLIENT_ASYNC.clone();
+    let url = generate_url(
+        &["/api/v1/client/game/manifest"],
+        &[("id", game_id), ("version", version)],
+    )?;
+
+    let response = client
+        .get(url)
+        .headers(generate_authenticated_headers()?)
+        .send()
+        .await?;
+
+    if response.status() != 200 {
+        return Err(RemoteAccessError::ManifestDownloadFailed(
+            response.status(),
+            response.text().await.unwrap_or_default(),
+        ));
+    }
+
+    let manifest: DropManifest = response.json().await?;
+    let mut cache = lock!(MANIFEST_CACHE);
+    cache.retain(|(id, _), _| id == game_id);
+    cache.insert(key, manifest.clone());
+
+    Ok(manifest)
+}
+
+pub(crate) fn forget_manifest(game_id: &str, version: &str) {
+    lock!(MANIFEST_CACHE).remove(&(game_id.to_owned(), version.to_owned()));
+}
+
+/// Drops every cached manifest, for when the library is refreshed and the
+/// versions they're for may have changed
+pub fn clear_manifest_cache() {
+    lock!(MANIFEST_CACHE).clear();
+}
+
+/// Compares the manifest for `version` against the chunks already on disk for
+/// the installed copy of `game_id`, to work out how much an update would
+/// actually have to download
+pub async fn get_update_size(
+    game_id: String,
+    version: String,
+) -> Result<UpdateSize, ApplicationDownloadError> {
+    let install_dir = match borrow_db_checked().applications.game_statuses.get(&game_id) {
+        Some(
+            GameDownloadStatus::Installed { install_dir, .. }
+            | GameDownloadStatus::SetupRequired { install_dir, .. }
+            | GameDownloadStatus::PartiallyInstalled { install_dir, .. },
+        ) => Some(install_dir.clone()),
+        _ => None,
+    };
+
+    let manifest = fetch_manifest(&game_id, &version)
+        .await
+        .map_err(ApplicationDownloadError::Communication)?;
+
+    let existing_contexts = install_dir
+        .and_then(|dir| DropData::read(Path::new(&dir)).ok())
+        .filter(|data| data.game_id == game_id)
+        .map(|data| data.get_contexts())
+        .unwrap_or_default();
+
+    let (full_size, delta_size) = manifest
+        .values()
+        .flat_map(|chunk| chunk.checksums.iter().zip(chunk.lengths.iter()))
+        .fold((0, 0), |(full, delta), (checksum, length)| {
+            let on_disk = *existing_contexts.get(checksum).unwrap_or(&false);
+            (full + length, if on_disk { delta } else { delta + length })
+        });
+
+    Ok(UpdateSize {
+        full_size,
+        delta_size,
+    })
+}
+
+/// The optional components tagged in the manifest for `version`, with their
+/// download sizes and whether the current install of the game left them out
+pub async fn get_game_components(
+    game_id: String,
+    version: String,
+) -> Result<Vec<GameComponent>, ApplicationDownloadError> {
+    let manifest = fetch_manifest(&game_id, &version)
+        .await
+        .map_err(ApplicationDownloadError::Communication)?;
+    let excluded = borrow_db_checked()
+        .applications
+        .game_excluded_components
+        .get(&game_id)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut sizes = BTreeMap::<String, u64>::new();
+    for chunk in manifest.into_values() {
+        if let Some(component) = chunk.component {
+            *sizes.entry(component).or_default() += chunk.lengths.iter().sum::<usize>() as u64;
+        }
+    }
+
+    Ok(sizes
+        .into_iter()
+        .map(|(name, size)| GameComponent {
+            excluded: excluded.contains(&name),
+            name,
+            size,
+        })
+        .collect())
+}
@@ -1,8 +1,102 @@
-use std::{io, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
 
+use database::{GameDownloadStatus, borrow_db_checked, borrow_db_mut_checked};
 use download_manager::error::ApplicationDownloadError;
+use log::{debug, warn};
+use serde::Serialize;
 use sysinfo::{Disk, DiskRefreshKind, Disks};
 
+use super::{drop_data::DropData, manifest::DropManifest, update::fetch_manifest};
+use crate::install_size::game_install_size;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadFeasibility {
+    pub required: u64,
+    pub available: u64,
+    pub sufficient: bool,
+}
+
+/// The soft limit on open files for this process, read the first time it's
+/// needed. `None` where there's no limit worth worrying about.
+pub fn soft_fd_limit() -> Option<usize> {
+    static SOFT_FD_LIMIT: LazyLock<Option<usize>> = LazyLock::new(|| {
+        #[cfg(unix)]
+        {
+            rustix::process::getrlimit(rustix::process::Resource::Nofile)
+                .current
+                .map(|limit| limit as usize)
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    });
+    *SOFT_FD_LIMIT
+}
+
+/// Reserves `len` bytes on disk for a freshly created `file`, so it isn't
+/// fragmented as chunks arrive out of order and running out of space fails
+/// before the download rather than partway through
+pub fn preallocate_file(file: &File, len: u64) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use rustix::fs::{FallocateFlags, fallocate};
+
+        fallocate(file, FallocateFlags::empty(), 0, len)?;
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let mut store = libc::fstore_t {
+            fst_flags: libc::F_ALLOCATECONTIG | libc::F_ALLOCATEALL,
+            fst_posmode: libc::F_PEOFPOSMODE,
+            fst_offset: 0,
+            fst_length: len as libc::off_t,
+            fst_bytesalloc: 0,
+        };
+        // SAFETY: the descriptor is open for as long as `file` is borrowed,
+        // and `store` outlives the call
+        let mut result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PREALLOCATE, &store) };
+        if result == -1 {
+            // Not enough contiguous space, so take whatever there is
+            store.fst_flags = libc::F_ALLOCATEALL;
+            result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PREALLOCATE, &store) };
+        }
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // F_PREALLOCATE reserves the blocks without changing the file's size
+        file.set_len(len)?;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Storage::FileSystem::SetFileValidData;
+
+        // Same as SetEndOfFile, which allocates the space
+        file.set_len(len)?;
+        // Skips zero filling up to each write, but needs the manage volume
+        // privilege, which most users don't run with
+        // SAFETY: the handle is open for as long as `file` is borrowed
+        if unsafe { SetFileValidData(file.as_raw_handle(), len as i64) } == 0 {
+            debug!("couldn't skip zero filling: {}", io::Error::last_os_error());
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    {
+        let _ = (file, len);
+    }
+    Ok(())
+}
+
 pub fn get_disk_available(mount_point: PathBuf) -> Result<u64, ApplicationDownloadError> {
     let disks = Disks::new_with_refreshed_list_specifics(DiskRefreshKind::nothing().with_storage());
 
@@ -23,3 +117,322 @@ pub fn get_disk_available(mount_point: PathBuf) -> Result<u64, ApplicationDownlo
         io::Error::other("could not find disk of path"),
     )))
 }
+
+/// Total bytes needed to install `version` of a game from scratch
+pub async fn get_required_space(
+    game_id: &str,
+    version: &str,
+) -> Result<u64, ApplicationDownloadError> {
+    let manifest = fetch_manifest(game_id, version)
+        .await
+        .map_err(ApplicationDownloadError::Communication)?;
+    Ok(manifest
+        .values()
+        .flat_map(|chunk| chunk.lengths.iter())
+        .sum::<usize>() as u64)
+}
+
+/// Bytes of `manifest` that still have to be downloaded, going by which
+/// chunks `contexts` says are already on disk
+pub fn remaining_size(manifest: &DropManifest, contexts: &HashMap<String, bool>) -> u64 {
+    manifest
+        .values()
+        .flat_map(|chunk| chunk.checksums.iter().zip(chunk.lengths.iter()))
+        .filter(|(checksum, _)| !*contexts.get(*checksum).unwrap_or(&false))
+        .map(|(_, length)| *length as u64)
+        .sum()
+}
+
+/// Works out whether `version` of a game fits in the install dir at
+/// `install_dir`, the same way starting the download does. Chunks already in
+/// the game's folder there, e.g. from a paused download, don't count.
+/// `excluded_components` replaces the ones saved from the last download.
+pub async fn check_download_feasible(
+    game_id: String,
+    version: String,
+    install_dir: usize,
+    excluded_components: Option<Vec<String>>,
+) -> Result<DownloadFeasibility, ApplicationDownloadError> {
+    let (base_dir, folder_name, excluded_components) = {
+        let db_lock = borrow_db_checked();
+        let base_dir = db_lock
+            .applications
+            .install_dirs
+            .get(install_dir)
+            .cloned()
+            .ok_or_else(|| {
+                ApplicationDownloadError::IoError(Arc::new(io::Error::other(format!(
+                    "there is no install dir {install_dir}"
+                ))))
+            })?;
+        let folder_name = db_lock
+            .applications
+            .install_folder_names
+            .get(&game_id)
+            .cloned()
+            .unwrap_or_else(|| game_id.clone());
+        let excluded_components = excluded_components.unwrap_or_else(|| {
+            db_lock
+                .applications
+                .game_excluded_components
+                .get(&game_id)
+                .cloned()
+                .unwrap_or_default()
+        });
+        (base_dir, folder_name, excluded_components)
+    };
+    let game_dir = base_dir.join(folder_name);
+
+    let (drop_data, _) = DropData::load(game_id.clone(), version.clone(), game_dir.clone());
+    let mut manifest = match drop_data.get_manifest() {
+        Some(manifest) => manifest,
+        None => fetch_manifest(&game_id, &version)
+            .await
+            .map_err(ApplicationDownloadError::Communication)?,
+    };
+    manifest.retain(|_, chunk| {
+        chunk
+            .component
+            .as_ref()
+            .is_none_or(|component| !excluded_components.contains(component))
+    });
+
+    let required = remaining_size(&manifest, &drop_data.get_contexts());
+    let available = get_disk_available(game_dir)?;
+
+    Ok(DownloadFeasibility {
+        required,
+        available,
+        sufficient: required <= available,
+    })
+}
+
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += dir_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Refuses a download that would push the Drop installs in `install_dir`
+/// past the quota the user set for it. Dirs without a quota always pass.
+/// Any existing install of `game_id` is left out, as it gets replaced.
+pub fn check_install_quota(
+    game_id: &str,
+    install_dir: &Path,
+    required_space: u64,
+) -> Result<(), ApplicationDownloadError> {
+    let (quota, installs) = {
+        let db_lock = borrow_db_checked();
+        let Some(quota) = db_lock
+            .applications
+            .install_dir_quotas
+            .get(install_dir)
+            .copied()
+        else {
+            return Ok(());
+        };
+        let installs = db_lock
+            .applications
+            .game_statuses
+            .iter()
+            .filter(|(id, _)| *id != game_id)
+            .filter_map(|(id, status)| match status {
+                GameDownloadStatus::Installed { install_dir, .. }
+                | GameDownloadStatus::SetupRequired { install_dir, .. }
+                | GameDownloadStatus::PartiallyInstalled { install_dir, .. } => {
+                    Some((id.clone(), PathBuf::from(install_dir)))
+                }
+                GameDownloadStatus::Remote {} => None,
+            })
+            .filter(|(_, path)| path.starts_with(install_dir))
+            .map(|(id, _)| id)
+            .collect::<Vec<String>>();
+        (quota, installs)
+    };
+
+    // Finished installs are only walked the first time they're sized
+    let used = installs
+        .iter()
+        .filter_map(|id| game_install_size(id))
+        .map(|size| size.bytes)
+        .sum::<u64>();
+
+    let required = used + required_space;
+    if required > quota {
+        return Err(ApplicationDownloadError::QuotaExceeded(
+            install_dir.to_path_buf(),
+            quota,
+            required,
+        ));
+    }
+    Ok(())
+}
+
+/// Picks the first install dir, in the user's priority order, with enough
+/// free space and quota for the whole game
+pub async fn pick_install_dir(
+    game_id: &str,
+    version: &str,
+) -> Result<usize, ApplicationDownloadError> {
+    let required_space = get_required_space(game_id, version).await?;
+
+    let install_dirs = borrow_db_checked().applications.install_dirs.clone();
+
+    let mut most_available = 0;
+    for (index, dir) in install_dirs.into_iter().enumerate() {
+        if let Err(e) = check_install_quota(game_id, &dir, required_space) {
+            warn!("skipping install dir {}: {e}", dir.display());
+            continue;
+        }
+        let available_space = match get_disk_available(dir.clone()) {
+            Ok(available_space) => available_space,
+            Err(e) => {
+                warn!("skipping install dir {}: {e}", dir.display());
+                continue;
+            }
+        };
+        if available_space >= required_space {
+            return Ok(index);
+        }
+        most_available = most_available.max(available_space);
+    }
+
+    Err(ApplicationDownloadError::DiskFull(
+        required_space,
+        most_available,
+    ))
+}
+
+/// Turns a game name into something every platform accepts as a folder name
+pub fn sanitise_folder_name(name: &str) -> String {
+    let sanitised = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    // Windows drops trailing dots and spaces, which would break the lookup
+    sanitised
+        .trim()
+        .trim_end_matches('.')
+        .trim_end()
+        .to_string()
+}
+
+/// Decides which folder under `base_dir` a game gets installed into and
+/// remembers it so resuming and launching find the same place.
+///
+/// Without `requested_name` the game id is used, as before. A requested name
+/// that is taken by something other than this game gets " (2)", " (3)", ...
+/// appended until it is free.
+pub fn assign_install_folder_name(
+    game_id: &str,
+    base_dir: &Path,
+    requested_name: Option<&str>,
+) -> String {
+    let requested_name = requested_name
+        .map(sanitise_folder_name)
+        .filter(|name| !name.is_empty() && name != "." && name != "..");
+
+    let Some(requested_name) = requested_name else {
+        let mut db_lock = borrow_db_mut_checked();
+        // Updates of an existing install keep whatever folder it already has
+        let installed = db_lock
+            .applications
+            .game_statuses
+            .get(game_id)
+            .is_some_and(|status| !matches!(status, GameDownloadStatus::Remote {}));
+        if !installed {
+            db_lock.applications.install_folder_names.remove(game_id);
+        }
+        return db_lock
+            .applications
+            .install_folder_names
+            .get(game_id)
+            .cloned()
+            .unwrap_or_else(|| game_id.to_string());
+    };
+
+    let mut db_lock = borrow_db_mut_checked();
+    let claimed = db_lock
+        .applications
+        .game_statuses
+        .iter()
+        .filter(|(id, _)| *id != game_id)
+        .filter_map(|(_, status)| match status {
+            GameDownloadStatus::Installed { install_dir, .. }
+            | GameDownloadStatus::SetupRequired { install_dir, .. }
+            | GameDownloadStatus::PartiallyInstalled { install_dir, .. } => {
+                Some(PathBuf::from(install_dir))
+            }
+            GameDownloadStatus::Remote {} => None,
+        })
+        .collect::<Vec<PathBuf>>();
+    let own_dir = db_lock
+        .applications
+        .install_folder_names
+        .get(game_id)
+        .map(|name| base_dir.join(name));
+
+    let mut folder_name = requested_name.clone();
+    let mut suffix = 2;
+    loop {
+        let path = base_dir.join(&folder_name);
+        let ours = own_dir.as_ref().is_some_and(|own| *own == path);
+        if !claimed.contains(&path) && (ours || !path.exists()) {
+            break;
+        }
+        folder_name = format!("{requested_name} ({suffix})");
+        suffix += 1;
+    }
+
+    db_lock
+        .applications
+        .install_folder_names
+        .insert(game_id.to_string(), folder_name.clone());
+    folder_name
+}
+
+/// Remembers which optional components `game_id` is downloaded without, so
+/// resumes and validation leave the same files out.
+///
+/// Without a selection, a fresh install gets everything and an existing
+/// install keeps what it had.
+pub fn set_excluded_components(game_id: &str, excluded: Option<Vec<String>>) {
+    let mut db_lock = borrow_db_mut_checked();
+    match excluded {
+        Some(excluded) if !excluded.is_empty() => {
+            db_lock
+                .applications
+                .game_excluded_components
+                .insert(game_id.to_string(), excluded);
+        }
+        Some(_) => {
+            db_lock
+                .applications
+                .game_excluded_components
+                .remove(game_id);
+        }
+        None => {
+            let installed = db_lock
+                .applications
+                .game_statuses
+                .get(game_id)
+                .is_some_and(|status| !matches!(status, GameDownloadStatus::Remote {}));
+            if !installed {
+                db_lock
+                    .applications
+                    .game_excluded_components
+                    .remove(game_id);
+            }
+        }
+    }
+}
@@ -10,15 +10,15 @@ use download_manager::{
         progress_object::ProgressHandle,
     },
 };
-use log::debug;
-use md5::Context;
+use log::{debug, warn};
 
-use crate::downloads::manifest::DropValidateContext;
+use crate::downloads::{checksum::ChunkHasher, manifest::DropValidateContext};
 
 pub fn validate_game_chunk(
     ctx: &DropValidateContext,
     control_flag: &DownloadThreadControl,
     progress: ProgressHandle,
+    buffer_size: usize,
 ) -> Result<bool, ApplicationDownloadError> {
     debug!(
         "Starting chunk validation {}, {}, {} #{}",
@@ -33,6 +33,8 @@ pub fn validate_game_chunk(
         return Ok(false);
     }
 
+    let mut hasher = ChunkHasher::new(ctx.checksum_algorithm.as_deref())?;
+
     let Ok(mut source) = File::open(&ctx.path) else {
         return Ok(false);
     };
@@ -43,15 +45,24 @@ pub fn validate_game_chunk(
             .expect("Failed to seek to file offset");
     }
 
-    let mut hasher = md5::Context::new();
-
-    let completed = validate_copy(&mut source, &mut hasher, ctx.length, control_flag, progress)?;
+    let completed = validate_copy(
+        &mut source,
+        &mut hasher,
+        ctx.length,
+        control_flag,
+        progress,
+        buffer_size,
+    )?;
     if !completed {
         return Ok(false);
     }
 
-    let res = hex::encode(hasher.finalize().0);
+    let res = hasher.finalize();
     if res != ctx.checksum {
+        warn!(
+            "checksum mismatch in {} at offset {}: expected {}, got {res}",
+            ctx.filename, ctx.offset, ctx.checksum
+        );
         return Ok(false);
     }
 
@@ -65,14 +76,14 @@ pub fn validate_game_chunk(
 
 fn validate_copy(
     source: &mut File,
-    dest: &mut Context,
+    dest: &mut ChunkHasher,
     size: usize,
     control_flag: &DownloadThreadControl,
     progress: ProgressHandle,
+    buffer_size: usize,
 ) -> Result<bool, io::Error> {
-    let copy_buf_size = 512;
-    let mut copy_buf = vec![0; copy_buf_size];
-    let mut buf_writer = BufWriter::with_capacity(1024 * 1024, dest);
+    let mut copy_buf = vec![0; buffer_size];
+    let mut buf_writer = BufWriter::with_capacity(buffer_size, dest);
     let mut total_bytes = 0;
 
     loop {
@@ -0,0 +1,93 @@
+use std::{
+    fs::read_dir,
+    path::{Component, Path},
+};
+
+use database::platform::Platform;
+
+/// How many folders deep into an install `list_executables` looks
+pub const EXECUTABLE_SCAN_DEPTH: usize = 4;
+
+/// Files under `install_dir` that could launch a game for `platform`,
+/// relative to `install_dir` and sorted
+pub fn list_executables(install_dir: &Path, platform: Platform, max_depth: usize) -> Vec<String> {
+    let mut executables = Vec::new();
+    scan_dir(
+        install_dir,
+        install_dir,
+        platform,
+        max_depth,
+        &mut executables,
+    );
+    executables.sort();
+    executables
+}
+
+fn scan_dir(
+    root: &Path,
+    dir: &Path,
+    platform: Platform,
+    depth: usize,
+    executables: &mut Vec<String>,
+) {
+    let Ok(entries) = read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if depth > 0 {
+                scan_dir(root, &path, platform, depth - 1, executables);
+            }
+        } else if file_type.is_file()
+            && is_executable(&path, platform)
+            && let Ok(relative) = path.strip_prefix(root)
+        {
+            executables.push(relative.to_string_lossy().to_string());
+        }
+    }
+}
+
+fn is_executable(path: &Path, platform: Platform) -> bool {
+    match platform {
+        Platform::Windows => has_extension(path, "exe", platform.is_case_sensitive()),
+        Platform::Linux | Platform::macOS => has_executable_bit(path),
+    }
+}
+
+fn has_extension(path: &Path, extension: &str, case_sensitive: bool) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy();
+        if case_sensitive {
+            ext == extension
+        } else {
+            ext.eq_ignore_ascii_case(extension)
+        }
+    })
+}
+
+#[cfg(unix)]
+fn has_executable_bit(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+// There's no executable bit to go by
+#[cfg(not(unix))]
+fn has_executable_bit(_path: &Path) -> bool {
+    false
+}
+
+/// Whether `path` stays inside the install dir it's relative to
+pub fn is_contained_relative_path(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{LazyLock, Mutex},
+};
+
+use database::{GameDownloadStatus, borrow_db_checked};
+use log::warn;
+use serde::Serialize;
+use utils::lock;
+
+use crate::downloads::utils::dir_size;
+
+/// Sizes of finished installs, keyed by (game id, version, install dir), so
+/// big games aren't walked every time they're looked at
+static INSTALL_SIZES: LazyLock<Mutex<HashMap<(String, String, String), u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallSize {
+    pub bytes: u64,
+    /// False while the game is only partially installed, so `bytes` is what's
+    /// on disk so far
+    pub complete: bool,
+}
+
+/// How much disk the game takes up, or `None` if it isn't installed. Don't
+/// call this with the database locked, it borrows it.
+pub fn game_install_size(game_id: &str) -> Option<InstallSize> {
+    let (version_name, install_dir, complete) = match borrow_db_checked()
+        .applications
+        .game_statuses
+        .get(game_id)?
+    {
+        GameDownloadStatus::Installed {
+            version_name,
+            install_dir,
+        }
+        | GameDownloadStatus::SetupRequired {
+            version_name,
+            install_dir,
+        } => (version_name.clone(), install_dir.clone(), true),
+        GameDownloadStatus::PartiallyInstalled {
+            version_name,
+            install_dir,
+        } => (version_name.clone(), install_dir.clone(), false),
+        GameDownloadStatus::Remote {} => return None,
+    };
+    install_size_at(game_id, version_name, install_dir, complete)
+}
+
+/// Sizes `install_dir`, reusing the last size if the install is complete
+pub(crate) fn install_size_at(
+    game_id: &str,
+    version_name: String,
+    install_dir: String,
+    complete: bool,
+) -> Option<InstallSize> {
+    let key = (game_id.to_owned(), version_name, install_dir);
+    if complete && let Some(bytes) = lock!(INSTALL_SIZES).get(&key) {
+        return Some(InstallSize {
+            bytes: *bytes,
+            complete,
+        });
+    }
+
+    let bytes = match dir_size(Path::new(&key.2)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("failed to size install of {game_id} at {}: {e}", key.2);
+            return None;
+        }
+    };
+    if complete {
+        lock!(INSTALL_SIZES).insert(key, bytes);
+    }
+    Some(InstallSize { bytes, complete })
+}
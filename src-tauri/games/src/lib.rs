@@ -2,6 +2,13 @@
 
 pub mod collections;
 pub mod downloads;
+pub mod executables;
+pub mod install_size;
 pub mod library;
 pub mod scan;
+pub mod shortcuts;
 pub mod state;
+pub mod versions;
+
+#[cfg(test)]
+mod test;
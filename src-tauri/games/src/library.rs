@@ -1,36 +1,47 @@
 use bitcode::{Decode, Encode};
 use database::{
-    ApplicationTransientStatus, Database, DownloadableMetadata, GameDownloadStatus, GameVersion,
-    borrow_db_checked, borrow_db_mut_checked,
+    ApplicationTransientStatus, Database, DownloadableMetadata, GameDownloadStatus, GamePlaytime,
+    GameVersion, LaunchConfig, LauncherKind, borrow_db_checked, borrow_db_mut_checked,
+    launch_config::DEFAULT_LAUNCH_TEMPLATE,
 };
 use log::{debug, error, warn};
 use remote::{
-    auth::generate_authorization_header, error::RemoteAccessError, requests::generate_url,
+    error::RemoteAccessError,
+    requests::{generate_authenticated_headers, generate_url},
     utils::DROP_CLIENT_SYNC,
 };
 use serde::{Deserialize, Serialize};
-use std::fs::remove_dir_all;
-use std::thread::spawn;
+use std::fs::{read_dir, remove_dir, remove_file};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
 use tauri::AppHandle;
 use utils::app_emit;
 
+use crate::downloads::error::LibraryError;
+use crate::install_size::InstallSize;
 use crate::state::{GameStatusManager, GameStatusWithTransient};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FetchGameStruct {
-    game: Game,
-    status: GameStatusWithTransient,
-    version: Option<GameVersion>,
+    pub game: Game,
+    pub status: GameStatusWithTransient,
+    pub version: Option<GameVersion>,
+    pub note: Option<String>,
+    pub playtime: Option<GamePlaytime>,
+    pub favorite: bool,
+    pub install_size: Option<InstallSize>,
+    pub tags: Vec<String>,
 }
 
-impl FetchGameStruct {
-    pub fn new(game: Game, status: GameStatusWithTransient, version: Option<GameVersion>) -> Self {
-        Self {
-            game,
-            status,
-            version,
-        }
-    }
+/// A game that's been played, for the recently played list
+#[derive(Serialize, Debug)]
+pub struct RecentlyPlayedGame {
+    pub game: Game,
+    pub status: GameStatusWithTransient,
+    pub playtime: GamePlaytime,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default, Encode, Decode)]
@@ -52,6 +63,12 @@ impl Game {
     pub fn id(&self) -> &String {
         &self.id
     }
+    pub fn name(&self) -> &String {
+        &self.m_name
+    }
+    pub fn icon_object_id(&self) -> &String {
+        &self.m_icon_object_id
+    }
 }
 #[derive(serde::Serialize, Clone)]
 pub struct GameUpdateEvent {
@@ -154,8 +171,19 @@ pub fn uninstall_game_logic(meta: DownloadableMetadata, app_handle: &AppHandle)
 
         let app_handle = app_handle.clone();
         spawn(move || {
-            if let Err(e) = remove_dir_all(install_dir) {
-                error!("{e}");
+            if let Err((path, e)) = remove_install_dir(Path::new(&install_dir)) {
+                error!(
+                    "failed to uninstall {}, couldn't remove {}: {e}",
+                    meta.id,
+                    path.display()
+                );
+                // Whatever is left over can be resumed or uninstalled again
+                set_partially_installed(&meta, install_dir, Some(&app_handle));
+                app_emit!(
+                    &app_handle,
+                    "uninstall_error",
+                    LibraryError::UninstallFailed(path, e).to_string()
+                );
             } else {
                 let mut db_handle = borrow_db_mut_checked();
                 db_handle.applications.transient_statuses.remove(&meta);
@@ -177,7 +205,7 @@ pub fn uninstall_game_logic(meta: DownloadableMetadata, app_handle: &AppHandle)
                 );
 
                 debug!("uninstalled game id {}", &meta.id);
-                app_emit!(&app_handle, "update_library", ());
+                push_library_update(&app_handle);
             }
         });
     } else {
@@ -185,6 +213,39 @@ pub fn uninstall_game_logic(meta: DownloadableMetadata, app_handle: &AppHandle)
     }
 }
 
+/// How long library changes have to settle before `update_library` is sent
+const LIBRARY_UPDATE_DEBOUNCE: Duration = Duration::from_millis(500);
+static LIBRARY_UPDATE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Sends `update_library` once a burst of library changes has settled, so
+/// batch operations only cause one refresh in the frontend
+pub fn push_library_update(app_handle: &AppHandle) {
+    let generation = LIBRARY_UPDATE_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    let app_handle = app_handle.clone();
+    spawn(move || {
+        sleep(LIBRARY_UPDATE_DEBOUNCE);
+        if LIBRARY_UPDATE_GENERATION.load(Ordering::Acquire) == generation {
+            app_emit!(&app_handle, "update_library", ());
+        }
+    });
+}
+
+/// Same as `remove_dir_all`, but reports which path couldn't be removed
+fn remove_install_dir(path: &Path) -> Result<(), (PathBuf, io::Error)> {
+    let entries = read_dir(path).map_err(|e| (path.to_path_buf(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| (path.to_path_buf(), e))?;
+        let entry_path = entry.path();
+        let file_type = entry.file_type().map_err(|e| (entry_path.clone(), e))?;
+        if file_type.is_dir() {
+            remove_install_dir(&entry_path)?;
+        } else {
+            remove_file(&entry_path).map_err(|e| (entry_path, e))?;
+        }
+    }
+    remove_dir(path).map_err(|e| (path.to_path_buf(), e))
+}
+
 pub fn get_current_meta(game_id: &String) -> Option<DownloadableMetadata> {
     borrow_db_checked()
         .applications
@@ -213,12 +274,31 @@ pub fn on_game_complete(
     )?;
     let response = client
         .get(response)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .send()?;
 
     let game_version: GameVersion = response.json()?;
 
+    register_installed_game(meta, install_dir, game_version, app_handle);
+
+    Ok(())
+}
+
+/// Marks a game as installed (or needing setup) in `install_dir` with the
+/// given version information
+pub fn register_installed_game(
+    meta: &DownloadableMetadata,
+    install_dir: String,
+    mut game_version: GameVersion,
+    app_handle: &AppHandle,
+) {
     let mut handle = borrow_db_mut_checked();
+    // Carry the user's launch options over to the new version
+    if let Some(launch_config) = handle.applications.game_launch_configs.get(&meta.id)
+        && game_version.launch_command_template == DEFAULT_LAUNCH_TEMPLATE
+    {
+        game_version.launch_command_template = launch_config.template();
+    }
     handle
         .applications
         .game_versions
@@ -259,8 +339,6 @@ pub fn on_game_complete(
             version: Some(game_version),
         }
     );
-
-    Ok(())
 }
 
 pub fn push_game_update(
@@ -287,14 +365,43 @@ pub fn push_game_update(
     );
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontendLaunchConfig {
+    pub base_command: String,
+    pub base_args: Vec<String>,
+    pub config: LaunchConfig,
+    pub raw_template: String,
+    // The template was edited by hand, so it doesn't match `config`
+    pub custom_template: bool,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FrontendGameOptions {
-    launch_string: String,
+    // Left as it is if not sent, e.g. when `update_launch_config` just
+    // rebuilt it
+    #[serde(default)]
+    launch_string: Option<String>,
+    // Unset means auto-detect
+    #[serde(default)]
+    preferred_launcher: Option<LauncherKind>,
+    // Blank means the version's own launch command
+    #[serde(default)]
+    launch_executable: Option<String>,
 }
 
 impl FrontendGameOptions {
-    pub fn launch_string(&self) -> &String {
-        &self.launch_string
+    pub fn launch_string(&self) -> Option<&String> {
+        self.launch_string.as_ref()
+    }
+    pub fn preferred_launcher(&self) -> Option<LauncherKind> {
+        self.preferred_launcher
+    }
+    pub fn launch_executable(&self) -> Option<String> {
+        self.launch_executable
+            .as_ref()
+            .map(|executable| executable.trim().to_owned())
+            .filter(|executable| !executable.is_empty())
     }
 }
@@ -1,14 +1,51 @@
-use std::fs;
+use std::{fs, path::Path};
 
-use database::{DownloadType, DownloadableMetadata, borrow_db_mut_checked};
-use log::warn;
+use database::{
+    Database, DownloadType, DownloadableMetadata, GameDownloadStatus, borrow_db_checked,
+    borrow_db_mut_checked,
+};
+use log::{info, warn};
+use serde::Serialize;
+use tauri::AppHandle;
 
 use crate::{
     downloads::drop_data::{DROP_DATA_PATH, DropData},
-    library::set_partially_installed_db,
+    library::{push_game_update, push_library_update, set_partially_installed_db},
+    state::GameStatusManager,
 };
 
-pub fn scan_install_dirs() {
+/// What a scan of the install dirs found, by game id
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanSummary {
+    /// Marked missing before, and back with the version Drop last installed
+    pub restored: Vec<String>,
+    /// Not known to be installed, so imported as partially installed
+    pub imported: Vec<String>,
+    /// Left alone because they're being downloaded, validated, etc.
+    pub skipped: Vec<String>,
+    /// Installed, but their install dir has gone, so marked as remote
+    pub missing: Vec<String>,
+}
+impl ScanSummary {
+    /// Every game whose status changed
+    pub fn changed(&self) -> impl Iterator<Item = &String> {
+        self.restored
+            .iter()
+            .chain(&self.imported)
+            .chain(&self.missing)
+    }
+}
+
+/// Picks up games in the install dirs that Drop doesn't know are installed.
+///
+/// A game that was marked missing, but whose files are back with the version
+/// Drop last installed, is restored to its previous state. Everything else is
+/// imported as partially installed, so resuming it validates the files.
+/// Games with a download or other operation in progress are skipped, as
+/// their files are still being written.
+pub fn scan_install_dirs() -> ScanSummary {
+    let mut summary = ScanSummary::default();
     let mut db_lock = borrow_db_mut_checked();
     for install_dir in db_lock.applications.install_dirs.clone() {
         let Ok(files) = fs::read_dir(install_dir) else {
@@ -19,7 +56,6 @@ pub fn scan_install_dirs() {
             if !drop_data_file.exists() {
                 continue;
             }
-            let game_id = game.file_name().display().to_string();
             let Ok(drop_data) = DropData::read(&game.path()) else {
                 warn!(
                     ".dropdata exists for {}, but couldn't read it. is it corrupted?",
@@ -27,21 +63,136 @@ pub fn scan_install_dirs() {
                 );
                 continue;
             };
-            if db_lock.applications.game_statuses.contains_key(&game_id) {
-                continue;
+            // The folder isn't always named after the game
+            let game_id = drop_data.game_id.clone();
+            match GameStatusManager::fetch_state(&game_id, &db_lock) {
+                (_, Some(_)) => {
+                    summary.skipped.push(game_id);
+                    continue;
+                }
+                (None | Some(GameDownloadStatus::Remote {}), None) => {}
+                (Some(_), None) => continue,
             }
 
-            let metadata = DownloadableMetadata::new(
-                drop_data.game_id,
-                Some(drop_data.game_version),
-                DownloadType::Game,
-            );
-            set_partially_installed_db(
-                &mut db_lock,
-                &metadata,
-                drop_data.base_path.to_str().unwrap().to_string(),
-                None,
-            );
+            let install_dir = drop_data.base_path.to_str().unwrap().to_string();
+            if restore_installed_game(&mut db_lock, &drop_data, install_dir.clone()) {
+                summary.restored.push(game_id);
+            } else {
+                let metadata = DownloadableMetadata::new(
+                    drop_data.game_id,
+                    Some(drop_data.game_version),
+                    DownloadType::Game,
+                );
+                set_partially_installed_db(&mut db_lock, &metadata, install_dir, None);
+                summary.imported.push(game_id);
+            }
+        }
+    }
+    summary
+}
+
+fn restore_installed_game(
+    db_lock: &mut Database,
+    drop_data: &DropData,
+    install_dir: String,
+) -> bool {
+    let installed_version = db_lock
+        .applications
+        .installed_game_version
+        .get(&drop_data.game_id)
+        .and_then(|meta| meta.version.clone());
+    if installed_version.as_ref() != Some(&drop_data.game_version) {
+        return false;
+    }
+    let Some(game_version) = db_lock
+        .applications
+        .game_versions
+        .get(&drop_data.game_id)
+        .and_then(|versions| versions.get(&drop_data.game_version))
+    else {
+        return false;
+    };
+
+    let version_name = drop_data.game_version.clone();
+    let status = if game_version.setup_command.is_empty() {
+        GameDownloadStatus::Installed {
+            version_name,
+            install_dir,
+        }
+    } else {
+        GameDownloadStatus::SetupRequired {
+            version_name,
+            install_dir,
         }
+    };
+    db_lock
+        .applications
+        .game_statuses
+        .insert(drop_data.game_id.clone(), status);
+    true
+}
+
+/// Marks installed games whose install dir has disappeared as remote, and
+/// returns their ids
+pub fn mark_missing_games() -> Vec<String> {
+    let mut db_handle = borrow_db_mut_checked();
+    let missing_games = db_handle
+        .applications
+        .game_statuses
+        .iter()
+        .filter(|(_, status)| match status {
+            GameDownloadStatus::Installed { install_dir, .. }
+            | GameDownloadStatus::SetupRequired { install_dir, .. } => {
+                !Path::new(install_dir).exists()
+            }
+            GameDownloadStatus::Remote {} | GameDownloadStatus::PartiallyInstalled { .. } => false,
+        })
+        .map(|(game_id, _)| game_id.clone())
+        .collect::<Vec<String>>();
+
+    info!("detected games missing: {missing_games:?}");
+
+    for game_id in &missing_games {
+        db_handle
+            .applications
+            .game_statuses
+            .insert(game_id.clone(), GameDownloadStatus::Remote {});
+    }
+    missing_games
+}
+
+/// Re-runs the startup reconciliation, for when an install dir comes back
+/// (or goes away), or a game is copied into one, while Drop is running
+pub fn rescan_install_dirs(app_handle: &AppHandle) -> ScanSummary {
+    let missing = mark_missing_games();
+    let mut summary = scan_install_dirs();
+    summary.missing = missing;
+    info!("rescanned install dirs: {summary:?}");
+
+    let db_lock = borrow_db_checked();
+    let mut changed = false;
+    for game_id in summary.changed() {
+        changed = true;
+        let status = GameStatusManager::fetch_state(game_id, &db_lock);
+        let version = db_lock
+            .applications
+            .installed_game_version
+            .get(game_id)
+            .and_then(|meta| meta.version.as_ref())
+            .and_then(|version| {
+                db_lock
+                    .applications
+                    .game_versions
+                    .get(game_id)?
+                    .get(version)
+                    .cloned()
+            });
+        push_game_update(app_handle, game_id, version, status);
+    }
+    drop(db_lock);
+
+    if changed {
+        push_library_update(app_handle);
     }
+    summary
 }
@@ -0,0 +1,215 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use database::db::DATA_ROOT_DIR;
+use log::{info, warn};
+use remote::fetch_object::fetch_object_by_id;
+use serde_with::SerializeDisplay;
+
+#[cfg(any(windows, target_os = "macos"))]
+use crate::downloads::utils::sanitise_folder_name;
+use crate::library::Game;
+
+#[derive(Debug, SerializeDisplay)]
+pub enum ShortcutError {
+    NotInstalled(String),
+    NoShortcutDir,
+    Io(io::Error),
+}
+
+impl Display for ShortcutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShortcutError::NotInstalled(game_id) => {
+                write!(f, "{game_id} isn't installed, so there's nothing to launch")
+            }
+            ShortcutError::NoShortcutDir => {
+                write!(f, "couldn't find a folder to put the shortcut in")
+            }
+            ShortcutError::Io(error) => write!(f, "couldn't write the shortcut: {error}"),
+        }
+    }
+}
+
+impl From<io::Error> for ShortcutError {
+    fn from(value: io::Error) -> Self {
+        ShortcutError::Io(value)
+    }
+}
+
+/// Opens Drop and launches the game, see the `launch` deep link
+pub fn launch_url(game_id: &str) -> String {
+    format!("drop://launch/{game_id}")
+}
+
+/// Icons are kept out of the object cache, so clearing it doesn't break
+/// shortcuts
+fn icons_dir() -> PathBuf {
+    DATA_ROOT_DIR.join("icons")
+}
+
+/// Saves the game's icon for a shortcut to point at. Only PNG icons are
+/// used, anything else leaves the shortcut with the default icon.
+pub async fn save_icon(game: &Game) -> Option<PathBuf> {
+    let response = match fetch_object_by_id(game.icon_object_id()).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("couldn't fetch the icon for {}: {e}", game.id());
+            return None;
+        }
+    };
+    let is_png = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("image/png"));
+    if !is_png {
+        warn!("icon for {} isn't a PNG, using the default", game.id());
+        return None;
+    }
+
+    let path = icons_dir().join(format!("{}.png", game.id()));
+    let written = fs::create_dir_all(icons_dir()).and_then(|_| fs::write(&path, response.body()));
+    if let Err(e) = written {
+        warn!("couldn't save the icon for {}: {e}", game.id());
+        return None;
+    }
+    Some(path)
+}
+
+/// Creates a shortcut that launches the game through Drop, replacing any
+/// earlier one for the same game. Returns where it was created.
+pub fn create_shortcut(game: &Game, icon: Option<&Path>) -> Result<PathBuf, ShortcutError> {
+    let path = write_shortcut(game, icon)?;
+    info!("created shortcut for {} at {}", game.id(), path.display());
+    Ok(path)
+}
+
+/// An entry in the applications menu
+#[cfg(target_os = "linux")]
+fn write_shortcut(game: &Game, icon: Option<&Path>) -> Result<PathBuf, ShortcutError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = dirs::data_dir()
+        .ok_or(ShortcutError::NoShortcutDir)?
+        .join("applications");
+    fs::create_dir_all(&dir)?;
+
+    // Named by id, so renaming the game doesn't leave the old one behind
+    let path = dir.join(format!("drop-{}.desktop", game.id()));
+    let icon = icon
+        .map(|icon| icon.to_string_lossy().to_string())
+        .unwrap_or_else(|| "drop".to_owned());
+    let name = game.name().replace(['\n', '\r'], " ");
+    let entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name={name}\n\
+         Comment=Play {name} with Drop\n\
+         Exec=xdg-open {}\n\
+         Icon={icon}\n\
+         Terminal=false\n\
+         Categories=Game;\n",
+        launch_url(game.id())
+    );
+    fs::write(&path, entry)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
+
+/// A `.lnk` on the desktop, opening the deep link through Explorer
+#[cfg(windows)]
+fn write_shortcut(game: &Game, icon: Option<&Path>) -> Result<PathBuf, ShortcutError> {
+    let dir = dirs::desktop_dir().ok_or(ShortcutError::NoShortcutDir)?;
+    let path = dir.join(format!("{}.lnk", sanitise_folder_name(game.name())));
+
+    let windows_dir = std::env::var("WINDIR").unwrap_or_else(|_| "C:\\Windows".to_owned());
+    let mut link = mslnk::ShellLink::new(Path::new(&windows_dir).join("explorer.exe"))
+        .map_err(|e| io::Error::other(format!("{e:?}")))?;
+    link.set_name(Some(game.name().clone()));
+    link.set_arguments(Some(launch_url(game.id())));
+    if let Some(icon) = icon.and_then(|icon| convert_to_ico(game, icon)) {
+        link.set_icon_location(Some(icon.to_string_lossy().to_string()));
+    }
+    // Overwrites an existing shortcut
+    link.create_lnk(&path)?;
+    Ok(path)
+}
+
+/// Shortcut icons have to be .ico files on Windows
+#[cfg(windows)]
+fn convert_to_ico(game: &Game, png: &Path) -> Option<PathBuf> {
+    let path = icons_dir().join(format!("{}.ico", game.id()));
+    let converted = (|| -> io::Result<()> {
+        let image = ico::IconImage::read_png(fs::File::open(png)?)?;
+        let mut icon_dir = ico::IconDir::new(ico::ResourceType::Icon);
+        icon_dir.add_entry(ico::IconDirEntry::encode(&image)?);
+        icon_dir.write(fs::File::create(&path)?)
+    })();
+    match converted {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("couldn't convert the icon for {}: {e}", game.id());
+            None
+        }
+    }
+}
+
+/// A tiny app in ~/Applications that opens the deep link. macOS wants an
+/// .icns icon, which we can't make from the PNG, so it keeps the default.
+#[cfg(target_os = "macos")]
+fn write_shortcut(game: &Game, _icon: Option<&Path>) -> Result<PathBuf, ShortcutError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = dirs::home_dir()
+        .ok_or(ShortcutError::NoShortcutDir)?
+        .join("Applications");
+    let path = dir.join(format!("{}.app", sanitise_folder_name(game.name())));
+    let macos_dir = path.join("Contents").join("MacOS");
+    fs::create_dir_all(&macos_dir)?;
+
+    let info_plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleExecutable</key>
+    <string>launch</string>
+    <key>CFBundleIdentifier</key>
+    <string>drop.shortcut.{}</string>
+    <key>CFBundleName</key>
+    <string>{}</string>
+    <key>CFBundlePackageType</key>
+    <string>APPL</string>
+</dict>
+</plist>
+"#,
+        game.id(),
+        escape_xml(game.name())
+    );
+    fs::write(path.join("Contents").join("Info.plist"), info_plist)?;
+
+    let script = macos_dir.join("launch");
+    fs::write(
+        &script,
+        format!("#!/bin/sh\nopen '{}'\n", launch_url(game.id())),
+    )?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
+
+#[cfg(target_os = "macos")]
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn write_shortcut(_game: &Game, _icon: Option<&Path>) -> Result<PathBuf, ShortcutError> {
+    Err(ShortcutError::NoShortcutDir)
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+    time::{Duration, Instant},
+};
+
+use database::{DefaultVersionPolicy, GameVersion, platform::Platform};
+use download_manager::error::ApplicationDownloadError;
+use download_manager::util::{
+    download_thread_control_flag::{DownloadThreadControl, DownloadThreadControlFlag},
+    progress_object::{ProgressHandle, ProgressObject},
+};
+use native_model::{Encode, rmp_serde_1_3::RmpSerde};
+use reqwest::StatusCode;
+use utils::test_support::test_dir;
+
+use crate::downloads::{
+    delta::reuse_previous_chunks,
+    download_agent::{preallocate_files, retry_delay_for},
+    download_logic::is_expired_context,
+    drop_data::{DROP_DATA_PATH, DropData, v1},
+    manifest::{DropChunk, DropManifest, DropValidateContext},
+    validate::validate_game_chunk,
+};
+use crate::executables::{is_contained_relative_path, list_executables};
+use crate::install_size::install_size_at;
+use crate::versions::pick_default_version;
+
+#[test]
+fn corrupted_drop_data_resets_contexts() {
+    let dir = test_dir("corrupted-drop-data");
+    fs::write(dir.join(DROP_DATA_PATH), b"\xde\xad\xbe\xef not drop data").unwrap();
+
+    let (data, reset) = DropData::load("game".to_owned(), "1.0".to_owned(), dir.clone());
+    assert!(reset);
+    assert_eq!(data.game_id, "game");
+    assert_eq!(data.base_path, dir);
+    assert!(data.get_contexts().is_empty());
+
+    // Writing over the garbage makes it readable again
+    data.set_context("checksum".to_owned(), true);
+    data.write();
+    let data = DropData::generate("game".to_owned(), "1.0".to_owned(), dir.clone());
+    assert_eq!(data.get_contexts().get("checksum"), Some(&true));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unversioned_drop_data_is_still_read() {
+    let dir = test_dir("unversioned-drop-data");
+    // As written before drop data had a native_model header
+    let legacy = v1::DropData {
+        game_id: "game".to_owned(),
+        game_version: "1.0".to_owned(),
+        contexts: Mutex::new(HashMap::from([("checksum".to_owned(), true)])),
+        base_path: dir.clone(),
+    };
+    fs::write(dir.join(DROP_DATA_PATH), RmpSerde::encode(&legacy).unwrap()).unwrap();
+
+    let (data, reset) = DropData::load("game".to_owned(), "1.0".to_owned(), dir.clone());
+    assert!(!reset);
+    assert_eq!(data.get_contexts().get("checksum"), Some(&true));
+    assert!(data.manifest.lock().unwrap().is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_drop_data_is_not_a_reset() {
+    let dir = test_dir("missing-drop-data");
+
+    let (data, reset) = DropData::load("game".to_owned(), "1.0".to_owned(), dir.clone());
+    assert!(!reset);
+    assert!(data.get_contexts().is_empty());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stopping_preallocation_removes_created_files() {
+    let dir = test_dir("stopped-preallocation");
+    fs::write(dir.join("existing.bin"), b"keep me").unwrap();
+
+    let chunk = |length| DropChunk {
+        permissions: 0o644,
+        ids: vec!["id".to_owned()],
+        checksums: vec!["checksum".to_owned()],
+        lengths: vec![length],
+        version_name: "1.0".to_owned(),
+        component: None,
+        checksum_algorithm: None,
+    };
+    let manifest: DropManifest = [
+        ("existing.bin", 7),
+        ("data/a.bin", 1024),
+        ("data/b.bin", 2048),
+        ("data/c.bin", 4096),
+        ("data/d.bin", 8192),
+    ]
+    .into_iter()
+    .map(|(path, length)| (path.to_owned(), chunk(length)))
+    .collect();
+
+    // Simulate a cancel arriving after a few files have been created
+    let checks = AtomicUsize::new(0);
+    let completed = preallocate_files(&dir, &manifest, || {
+        checks.fetch_add(1, Ordering::Relaxed) >= 3
+    })
+    .unwrap();
+
+    assert!(!completed);
+    for name in ["a", "b", "c", "d"] {
+        assert!(!dir.join("data").join(format!("{name}.bin")).exists());
+    }
+    assert_eq!(fs::read(dir.join("existing.bin")).unwrap(), b"keep me");
+
+    assert!(preallocate_files(&dir, &manifest, || false).unwrap());
+    for name in ["a", "b", "c", "d"] {
+        assert!(dir.join("data").join(format!("{name}.bin")).exists());
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Rough throughput comparison for the `io_buffer_size` setting. Run with
+/// `cargo test -p games --release -- --ignored --nocapture io_buffer_size`
+#[test]
+#[ignore]
+fn io_buffer_size_throughput() {
+    const FILE_SIZE: usize = 512 * 1024 * 1024;
+
+    let dir = test_dir("io-buffer-size");
+    let path = dir.join("large.bin");
+    let data = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    fs::write(&path, &data).unwrap();
+    let checksum = hex::encode(md5::compute(&data).0);
+    drop(data);
+
+    let ctx = DropValidateContext {
+        index: 0,
+        filename: "large.bin".to_owned(),
+        offset: 0,
+        path,
+        checksum,
+        checksum_algorithm: None,
+        length: FILE_SIZE,
+    };
+    let control_flag = DownloadThreadControl::new(DownloadThreadControlFlag::Go);
+    // Progress updates get sent here, so it has to outlive the runs
+    let (sender, _receiver) = channel();
+
+    for buffer_size in [4 * 1024, 64 * 1024, 1024 * 1024, 8 * 1024 * 1024] {
+        let progress = Arc::new(ProgressObject::new(FILE_SIZE, 1, 1, sender.clone()));
+        let handle = ProgressHandle::new(progress.get(0), progress.clone());
+
+        let start = Instant::now();
+        assert!(validate_game_chunk(&ctx, &control_flag, handle, buffer_size).unwrap());
+        let elapsed = start.elapsed();
+
+        println!(
+            "{buffer_size:>8} byte buffer: {:.0} MB/s",
+            FILE_SIZE as f64 / 1_000_000.0 / elapsed.as_secs_f64()
+        );
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn validates_chunks_with_the_manifest_algorithm() {
+    let dir = test_dir("checksum-algorithm");
+    let path = dir.join("file.bin");
+    fs::write(&path, b"drop").unwrap();
+
+    let ctx = |checksum: &str, algorithm: Option<&str>| DropValidateContext {
+        index: 0,
+        filename: "file.bin".to_owned(),
+        offset: 0,
+        path: path.clone(),
+        checksum: checksum.to_owned(),
+        checksum_algorithm: algorithm.map(str::to_owned),
+        length: 4,
+    };
+    let control_flag = DownloadThreadControl::new(DownloadThreadControlFlag::Go);
+    let (sender, _receiver) = channel();
+    let progress = Arc::new(ProgressObject::new(4, 1, 1, sender));
+    let validate = |ctx: &DropValidateContext| {
+        let handle = ProgressHandle::new(progress.get(0), progress.clone());
+        validate_game_chunk(ctx, &control_flag, handle, 1024)
+    };
+
+    let md5 = hex::encode(md5::compute(b"drop").0);
+    assert!(validate(&ctx(&md5, None)).unwrap());
+    assert!(validate(&ctx(&md5, Some("MD5"))).unwrap());
+    let sha256 = "d90ee9ccf6bea1d2942a7b21319338198dec2a746f8a0d0771621f00da2e0864";
+    assert!(validate(&ctx(sha256, Some("sha256"))).unwrap());
+    assert!(!validate(&ctx(&md5, Some("sha256"))).unwrap());
+    assert!(matches!(
+        validate(&ctx(&md5, Some("blake3"))),
+        Err(ApplicationDownloadError::UnsupportedChecksum(algorithm)) if algorithm == "blake3"
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn game_version(version_name: &str, version_index: usize) -> GameVersion {
+    GameVersion {
+        game_id: "game".to_owned(),
+        version_name: version_name.to_owned(),
+        platform: Platform::HOST,
+        launch_command: "game.exe".to_owned(),
+        launch_args: Vec::new(),
+        launch_command_template: "{}".to_owned(),
+        setup_command: String::new(),
+        setup_args: Vec::new(),
+        setup_command_template: "{}".to_owned(),
+        only_setup: false,
+        version_index,
+        delta: false,
+        umu_id_override: None,
+        preferred_launcher: None,
+        executable_override: None,
+    }
+}
+
+#[test]
+fn default_version_policy() {
+    let versions = vec![
+        game_version("1.0", 0),
+        game_version("1.1", 1),
+        game_version("1.2-beta", 2),
+    ];
+    let pick = |policy| {
+        pick_default_version(&versions, policy)
+            .map(|v| v.version_name.as_str())
+            .unwrap()
+    };
+    assert_eq!(pick(DefaultVersionPolicy::Latest), "1.2-beta");
+    assert_eq!(pick(DefaultVersionPolicy::LatestStable), "1.1");
+
+    let prereleases = vec![game_version("2.0 RC1", 0)];
+    let picked = pick_default_version(&prereleases, DefaultVersionPolicy::LatestStable).unwrap();
+    assert_eq!(picked.version_name, "2.0 RC1");
+    assert!(pick_default_version(&[], DefaultVersionPolicy::Latest).is_none());
+}
+
+#[test]
+fn retry_delay_doubles_up_to_a_cap() {
+    let base = Duration::from_secs(1);
+    let error = ApplicationDownloadError::Checksum;
+
+    assert_eq!(retry_delay_for(base, 0, &error), Duration::from_secs(1));
+    assert_eq!(retry_delay_for(base, 1, &error), Duration::from_secs(2));
+    assert_eq!(retry_delay_for(base, 2, &error), Duration::from_secs(4));
+    assert_eq!(retry_delay_for(base, 40, &error), Duration::from_secs(60));
+}
+
+#[test]
+fn expired_context_needs_a_client_error_about_the_context() {
+    let body = r#"{"statusCode":403,"statusMessage":"Invalid download context"}"#;
+    for status in [
+        StatusCode::UNAUTHORIZED,
+        StatusCode::FORBIDDEN,
+        StatusCode::NOT_FOUND,
+        StatusCode::GONE,
+    ] {
+        assert!(is_expired_context(status, body), "{status}");
+    }
+
+    // Other errors mentioning it aren't about it expiring
+    assert!(!is_expired_context(StatusCode::BAD_REQUEST, body));
+    assert!(!is_expired_context(StatusCode::INTERNAL_SERVER_ERROR, body));
+    // Nor are the same statuses for anything else
+    assert!(!is_expired_context(
+        StatusCode::NOT_FOUND,
+        r#"{"statusCode":404,"statusMessage":"Chunk not found"}"#
+    ));
+    assert!(!is_expired_context(StatusCode::FORBIDDEN, ""));
+}
+
+#[test]
+fn update_moves_reused_chunks_into_place() {
+    let dir = test_dir("delta-update");
+    fs::write(dir.join("data.bin"), b"aaaabbbb").unwrap();
+
+    let chunk = |checksums: &[&str]| DropChunk {
+        permissions: 0o644,
+        ids: vec!["id".to_owned()],
+        checksums: checksums.iter().map(|c| c.to_string()).collect(),
+        lengths: vec![4; checksums.len()],
+        version_name: "1.0".to_owned(),
+        component: None,
+        checksum_algorithm: None,
+    };
+    let previous: DropManifest = [("data.bin".to_owned(), chunk(&["a", "b"]))].into();
+    // Both chunks swap places, and one is also copied into a new file
+    let manifest: DropManifest = [
+        ("data.bin".to_owned(), chunk(&["b", "a"])),
+        ("moved.bin".to_owned(), chunk(&["a"])),
+    ]
+    .into();
+    let contexts = [("a".to_owned(), true), ("b".to_owned(), true)].into();
+
+    let missing = reuse_previous_chunks(&dir, &previous, &manifest, &contexts);
+
+    assert!(missing.is_empty());
+    assert_eq!(fs::read(dir.join("data.bin")).unwrap(), b"bbbbaaaa");
+    assert_eq!(fs::read(dir.join("moved.bin")).unwrap(), b"aaaa");
+    assert!(!dir.join(".drop-delta").exists());
+}
+
+#[test]
+fn lists_windows_executables_up_to_the_depth_limit() {
+    let dir = test_dir("list-executables");
+    fs::create_dir_all(dir.join("bin/deeper")).unwrap();
+    fs::write(dir.join("Launcher.EXE"), b"").unwrap();
+    fs::write(dir.join("readme.txt"), b"").unwrap();
+    fs::write(dir.join("bin/game.exe"), b"").unwrap();
+    fs::write(dir.join("bin/deeper/tool.exe"), b"").unwrap();
+
+    let executables = list_executables(&dir, Platform::Windows, 1);
+    let expected = [
+        "Launcher.EXE".to_owned(),
+        PathBuf::from("bin")
+            .join("game.exe")
+            .to_string_lossy()
+            .to_string(),
+    ];
+    assert_eq!(executables, expected);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn executable_overrides_stay_in_the_install() {
+    assert!(is_contained_relative_path("bin/game.exe"));
+    assert!(!is_contained_relative_path("../other/game.exe"));
+    assert!(!is_contained_relative_path("/usr/bin/game"));
+    assert!(!is_contained_relative_path(""));
+}
+
+#[test]
+fn complete_install_sizes_are_cached() {
+    let dir = test_dir("install-size");
+    fs::write(dir.join("game.bin"), [0; 100]).unwrap();
+    let install_dir = dir.to_string_lossy().to_string();
+    let size = |complete| {
+        install_size_at("game", "1.0".to_owned(), install_dir.clone(), complete).unwrap()
+    };
+
+    let first = size(true);
+    assert_eq!(first.bytes, 100);
+    assert!(first.complete);
+
+    // Not walked again once it's known
+    fs::write(dir.join("save.dat"), [0; 50]).unwrap();
+    assert_eq!(size(true).bytes, 100);
+
+    // Partial installs are still growing, so they're always walked
+    let partial = size(false);
+    assert_eq!(partial.bytes, 150);
+    assert!(!partial.complete);
+
+    assert!(
+        install_size_at("game", "1.0".to_owned(), "/does/not/exist".to_owned(), true).is_none()
+    );
+}
@@ -0,0 +1,30 @@
+use database::{DefaultVersionPolicy, GameVersion};
+
+const PRERELEASE_MARKERS: [&str; 7] = ["alpha", "beta", "rc", "pre", "dev", "nightly", "preview"];
+
+/// Whether a version name reads like a pre-release, e.g. "1.2-beta" or
+/// "2.0 RC1". Version names are free text, so this is a best guess.
+pub fn is_prerelease(version_name: &str) -> bool {
+    version_name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .any(|word| PRERELEASE_MARKERS.contains(&word))
+}
+
+/// Picks the version to install when the user didn't choose one. `versions`
+/// should already be filtered down to ones this platform can run.
+pub fn pick_default_version(
+    versions: &[GameVersion],
+    policy: DefaultVersionPolicy,
+) -> Option<&GameVersion> {
+    let latest = versions.iter().max_by_key(|v| v.version_index);
+    match policy {
+        DefaultVersionPolicy::Latest => latest,
+        // Fall back to the latest version if every version is a pre-release
+        DefaultVersionPolicy::LatestStable => versions
+            .iter()
+            .filter(|v| !is_prerelease(&v.version_name))
+            .max_by_key(|v| v.version_index)
+            .or(latest),
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use database::launch_config::escape_cmd;
 use dynfmt::{Argument, FormatArgs};
 
 pub struct DropFormatArgs {
@@ -36,3 +37,26 @@ impl FormatArgs for DropFormatArgs {
         Ok(self.map.get(key).map(|arg| arg as Argument<'_>))
     }
 }
+
+/// Runs the whole formatted launch command through `wrapper`, e.g.
+/// `gamemoderun`. The command can be several shell commands chained together,
+/// so it's handed to a shell of its own rather than just put after the
+/// wrapper. This happens after formatting, so the quotes and braces added
+/// here never go through the template.
+pub fn wrap_launch_string(wrapper: &str, launch_string: &str) -> String {
+    #[cfg(target_os = "windows")]
+    return wrap_for_cmd(wrapper, launch_string);
+    #[cfg(not(target_os = "windows"))]
+    return wrap_for_sh(wrapper, launch_string);
+}
+
+pub fn wrap_for_sh(wrapper: &str, launch_string: &str) -> String {
+    format!("{wrapper} sh -c '{}'", launch_string.replace('\'', "'\\''"))
+}
+
+/// Quotes in the launch command would end the quotes around it early, so
+/// everything is escaped instead, quotes included. The outer cmd takes one
+/// level of escaping off, and `/S` has the inner one keep the quotes inside.
+pub fn wrap_for_cmd(wrapper: &str, launch_string: &str) -> String {
+    format!("{wrapper} cmd /S /C ^\"{}^\"", escape_cmd(launch_string))
+}
@@ -14,8 +14,13 @@ pub static PROCESS_MANAGER: ProcessManagerWrapper = ProcessManagerWrapper::new()
 
 pub mod error;
 pub mod format;
+pub mod logs;
 pub mod process_handlers;
 pub mod process_manager;
+pub mod rich_presence;
+
+#[cfg(test)]
+mod test;
 
 pub struct ProcessManagerWrapper(OnceLock<Mutex<ProcessManager<'static>>>);
 impl ProcessManagerWrapper {
@@ -0,0 +1,77 @@
+use std::{
+    fs::{read, read_dir, remove_file},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+/// A launch's `<version>-<timestamp>.log` and `<version>-<timestamp>-error.log`
+pub struct LogSession {
+    pub timestamp: i64,
+    pub files: Vec<PathBuf>,
+}
+
+impl LogSession {
+    /// The game's stdout log, as opposed to its error log
+    pub fn output_log(&self) -> Option<&PathBuf> {
+        self.files.iter().find(|file| {
+            file.file_name()
+                .is_some_and(|name| !name.to_string_lossy().ends_with("-error.log"))
+        })
+    }
+}
+
+/// Parses the timestamp out of a log name, as written by `launch_process`
+fn log_timestamp(name: &str) -> Option<i64> {
+    let stem = name.strip_suffix(".log")?;
+    let stem = stem.strip_suffix("-error").unwrap_or(stem);
+    stem.rsplit_once('-')?.1.parse().ok()
+}
+
+/// Every launch that has logs in `dir`, newest first
+pub fn log_sessions(dir: &Path) -> Vec<LogSession> {
+    let Ok(entries) = read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut sessions: Vec<LogSession> = Vec::new();
+    for entry in entries.flatten() {
+        let Some(timestamp) = log_timestamp(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        match sessions.iter_mut().find(|s| s.timestamp == timestamp) {
+            Some(session) => session.files.push(entry.path()),
+            None => sessions.push(LogSession {
+                timestamp,
+                files: vec![entry.path()],
+            }),
+        }
+    }
+    sessions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    sessions
+}
+
+/// Deletes all but the newest `keep` launches' logs in `dir`
+pub fn rotate_logs(dir: &Path, keep: usize) {
+    for session in log_sessions(dir).into_iter().skip(keep) {
+        for file in session.files {
+            debug!("removing old log {}", file.display());
+            if let Err(e) = remove_file(&file) {
+                warn!("failed to remove old log {}: {e}", file.display());
+            }
+        }
+    }
+}
+
+/// The last `count` lines of the log at `path`
+pub fn tail_log(path: &Path, count: usize) -> Vec<String> {
+    let Ok(bytes) = read(path) else {
+        return Vec::new();
+    };
+    let contents = String::from_utf8_lossy(&bytes);
+    let lines = contents.lines().collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
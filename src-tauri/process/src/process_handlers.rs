@@ -1,11 +1,21 @@
-use client::compat::{COMPAT_INFO, UMU_LAUNCHER_EXECUTABLE};
-use database::{Database, DownloadableMetadata, GameVersion, platform::Platform};
-use log::debug;
+use std::fs::create_dir_all;
+
+use client::compat::{COMPAT_INFO, GAMESCOPE_EXECUTABLE, UMU_LAUNCHER_EXECUTABLE};
+use database::{
+    Database, DownloadableMetadata, GameVersion, LauncherKind,
+    platform::Platform,
+    prefix::{game_prefix_dir, games_sharing_prefix},
+};
+use log::{debug, warn};
 
 use crate::{error::ProcessError, process_manager::ProcessHandler};
 
 pub struct NativeGameLauncher;
 impl ProcessHandler for NativeGameLauncher {
+    fn kind(&self) -> LauncherKind {
+        LauncherKind::Native
+    }
+
     fn create_launch_process(
         &self,
         _meta: &DownloadableMetadata,
@@ -13,6 +23,7 @@ impl ProcessHandler for NativeGameLauncher {
         args: Vec<String>,
         _game_version: &GameVersion,
         _current_dir: &str,
+        _db: &Database,
     ) -> Result<String, ProcessError> {
         Ok(format!("\"{}\" {}", launch_command, args.join(" ")))
     }
@@ -24,6 +35,10 @@ impl ProcessHandler for NativeGameLauncher {
 
 pub struct UMULauncher;
 impl ProcessHandler for UMULauncher {
+    fn kind(&self) -> LauncherKind {
+        LauncherKind::Umu
+    }
+
     fn create_launch_process(
         &self,
         _meta: &DownloadableMetadata,
@@ -31,6 +46,7 @@ impl ProcessHandler for UMULauncher {
         args: Vec<String>,
         game_version: &GameVersion,
         _current_dir: &str,
+        db: &Database,
     ) -> Result<String, ProcessError> {
         debug!("Game override: \"{:?}\"", &game_version.umu_id_override);
         let game_id = match &game_version.umu_id_override {
@@ -43,8 +59,21 @@ impl ProcessHandler for UMULauncher {
             }
             None => game_version.game_id.clone(),
         };
+
+        let prefix = game_prefix_dir(db, &game_version.game_id);
+        create_dir_all(&prefix).map_err(ProcessError::IOError)?;
+        let sharing = games_sharing_prefix(db, &game_version.game_id);
+        if !sharing.is_empty() {
+            warn!(
+                "{} shares its prefix {} with {}",
+                game_version.game_id,
+                prefix.display(),
+                sharing.join(", ")
+            );
+        }
+
         Ok(format!(
-            "GAMEID={game_id} {umu:?} \"{launch}\" {args}",
+            "WINEPREFIX={prefix:?} GAMEID={game_id} {umu:?} \"{launch}\" {args}",
             umu = UMU_LAUNCHER_EXECUTABLE
                 .as_ref()
                 .expect("Failed to get UMU_LAUNCHER_EXECUTABLE as ref"),
@@ -63,6 +92,10 @@ impl ProcessHandler for UMULauncher {
 
 pub struct AsahiMuvmLauncher;
 impl ProcessHandler for AsahiMuvmLauncher {
+    fn kind(&self) -> LauncherKind {
+        LauncherKind::AsahiMuvm
+    }
+
     fn create_launch_process(
         &self,
         meta: &DownloadableMetadata,
@@ -70,6 +103,7 @@ impl ProcessHandler for AsahiMuvmLauncher {
         args: Vec<String>,
         game_version: &GameVersion,
         current_dir: &str,
+        db: &Database,
     ) -> Result<String, ProcessError> {
         let umu_launcher = UMULauncher {};
         let umu_string = umu_launcher.create_launch_process(
@@ -78,6 +112,7 @@ impl ProcessHandler for AsahiMuvmLauncher {
             args,
             game_version,
             current_dir,
+            db,
         )?;
         let mut args_cmd = umu_string
             .split("umu-run")
@@ -118,3 +153,63 @@ impl ProcessHandler for AsahiMuvmLauncher {
         compat_info.umu_installed
     }
 }
+
+pub struct GamescopeLauncher;
+impl ProcessHandler for GamescopeLauncher {
+    fn kind(&self) -> LauncherKind {
+        LauncherKind::Gamescope
+    }
+
+    fn create_launch_process(
+        &self,
+        meta: &DownloadableMetadata,
+        launch_command: String,
+        args: Vec<String>,
+        game_version: &GameVersion,
+        current_dir: &str,
+        db: &Database,
+    ) -> Result<String, ProcessError> {
+        let launch_string = match game_version.platform {
+            Platform::Windows => UMULauncher {}.create_launch_process(
+                meta,
+                launch_command,
+                args,
+                game_version,
+                current_dir,
+                db,
+            )?,
+            _ => NativeGameLauncher {}.create_launch_process(
+                meta,
+                launch_command,
+                args,
+                game_version,
+                current_dir,
+                db,
+            )?,
+        };
+
+        // The launch string can start with variables like GAMEID, which
+        // gamescope would take as the command, so it runs through env
+        Ok(format!(
+            "{gamescope:?} -W {width} -H {height} -- env {launch_string}",
+            gamescope = GAMESCOPE_EXECUTABLE
+                .as_ref()
+                .ok_or(ProcessError::InvalidPlatform)?,
+            width = db.settings.gamescope_width,
+            height = db.settings.gamescope_height,
+        ))
+    }
+
+    fn valid_for_platform(&self, db: &Database, target: &Platform) -> bool {
+        if !db.settings.gamescope_enabled {
+            return false;
+        }
+
+        let Some(compat_info) = &*COMPAT_INFO else {
+            return false;
+        };
+
+        compat_info.gamescope_installed
+            && (*target != Platform::Windows || compat_info.umu_installed)
+    }
+}
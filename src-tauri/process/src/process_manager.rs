@@ -6,32 +6,76 @@ use std::{
     process::{Command, ExitStatus},
     str::FromStr,
     sync::Arc,
-    thread::spawn,
+    thread::{JoinHandle, spawn},
     time::{Duration, SystemTime},
 };
 
+use cloud_saves::{
+    error::BackupError,
+    progress::SaveSyncProgress,
+    snapshots::{effective_config, sync_after_exit, sync_before_launch},
+};
 use database::{
     ApplicationTransientStatus, Database, DownloadType, DownloadableMetadata, GameDownloadStatus,
-    GameVersion, borrow_db_checked, borrow_db_mut_checked, db::DATA_ROOT_DIR, platform::Platform,
+    GameVersion, LauncherKind, borrow_db_checked, borrow_db_mut_checked, db::DATA_ROOT_DIR,
+    launch_config::is_valid_env_key, platform::Platform,
 };
 use dynfmt::Format;
 use dynfmt::SimpleCurlyFormat;
-use games::{library::push_game_update, state::GameStatusManager};
+use games::{
+    library::{Game, push_game_update},
+    state::GameStatusManager,
+};
 use log::{debug, info, warn};
+use remote::cache::get_cached_object_db;
+use serde::Serialize;
 use shared_child::SharedChild;
 use tauri::AppHandle;
+use utils::app_emit;
 
 use crate::{
     PROCESS_MANAGER,
     error::ProcessError,
-    format::DropFormatArgs,
-    process_handlers::{AsahiMuvmLauncher, NativeGameLauncher, UMULauncher},
+    format::{DropFormatArgs, wrap_launch_string},
+    logs::{rotate_logs, tail_log},
+    process_handlers::{AsahiMuvmLauncher, GamescopeLauncher, NativeGameLauncher, UMULauncher},
+    rich_presence,
 };
 
+/// Sessions this short are taken as a failed launch rather than play
+const FAILED_LAUNCH_THRESHOLD_SECS: u64 = 2;
+/// Lines of the error log sent with `launch_error`
+const LAUNCH_FAILED_LOG_LINES: usize = 20;
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchErrorEvent {
+    pub game_id: String,
+    pub message: String,
+    // Only for games that started and then exited too soon
+    pub exit_code: Option<i32>,
+    pub stderr_tail: Vec<String>,
+}
+
+impl LaunchErrorEvent {
+    /// For games that couldn't be started at all
+    pub fn new(game_id: String, message: String) -> Self {
+        Self {
+            game_id,
+            message,
+            exit_code: None,
+            stderr_tail: Vec::new(),
+        }
+    }
+}
+
 pub struct RunningProcess {
     handle: Arc<SharedChild>,
     start: SystemTime,
     manually_killed: bool,
+    error_log: PathBuf,
+    // Runs `on_process_finish` once the game exits
+    wait_thread: Option<JoinHandle<Result<(), ProcessError>>>,
 }
 
 pub struct ProcessManager<'a> {
@@ -63,6 +107,15 @@ impl ProcessManager<'_> {
             log_output_dir,
             game_launchers: vec![
                 // Current platform to target platform
+                // Gamescope goes first, as it's only valid when it's enabled
+                (
+                    (Platform::Linux, Platform::Windows),
+                    &GamescopeLauncher {} as &(dyn ProcessHandler + Sync + Send + 'static),
+                ),
+                (
+                    (Platform::Linux, Platform::Linux),
+                    &GamescopeLauncher {} as &(dyn ProcessHandler + Sync + Send + 'static),
+                ),
                 (
                     (Platform::Windows, Platform::Windows),
                     &NativeGameLauncher {} as &(dyn ProcessHandler + Sync + Send + 'static),
@@ -103,6 +156,23 @@ impl ProcessManager<'_> {
         }
     }
 
+    /// Kills every running game without waiting for them to exit. The wait
+    /// threads lock the process manager to clean up after each game, so they
+    /// are returned to be joined once the lock is released.
+    pub fn kill_all(&mut self) -> Vec<JoinHandle<Result<(), ProcessError>>> {
+        let mut wait_threads = Vec::new();
+        for (game_id, process) in self.processes.iter_mut() {
+            process.manually_killed = true;
+            if let Err(e) = process.handle.kill() {
+                warn!("failed to kill {game_id}: {e}");
+            }
+            if let Some(wait_thread) = process.wait_thread.take() {
+                wait_threads.push(wait_thread);
+            }
+        }
+        wait_threads
+    }
+
     pub fn get_log_dir(&self, game_id: String) -> PathBuf {
         self.log_output_dir.join(game_id)
     }
@@ -128,6 +198,7 @@ impl ProcessManager<'_> {
                 return Ok(());
             }
         };
+        rich_presence::clear();
 
         let mut db_handle = borrow_db_mut_checked();
         let meta = db_handle
@@ -139,6 +210,7 @@ impl ProcessManager<'_> {
         db_handle.applications.transient_statuses.remove(&meta);
 
         let current_state = db_handle.applications.game_statuses.get(&game_id).cloned();
+        let was_installed = matches!(current_state, Some(GameDownloadStatus::Installed { .. }));
         if let Some(GameDownloadStatus::SetupRequired {
             version_name,
             install_dir,
@@ -156,15 +228,41 @@ impl ProcessManager<'_> {
         }
 
         let elapsed = process.start.elapsed().unwrap_or(Duration::ZERO);
+        if elapsed.as_secs() <= FAILED_LAUNCH_THRESHOLD_SECS {
+            info!(
+                "not counting {:.1}s session of {game_id} towards its playtime",
+                elapsed.as_secs_f64()
+            );
+        } else {
+            let playtime = db_handle
+                .applications
+                .game_playtime
+                .entry(game_id.clone())
+                .or_default();
+            playtime.playtime_seconds += elapsed.as_secs();
+            playtime.last_played = Some(chrono::Utc::now().timestamp());
+        }
+
         // If we started and ended really quickly, something might've gone wrong
         // Or if the status isn't 0
         // Or if it's an error
         if !process.manually_killed
-            && (elapsed.as_secs() <= 2 || result.map_or(true, |r| !r.success()))
+            && (elapsed.as_secs() <= FAILED_LAUNCH_THRESHOLD_SECS
+                || !result.as_ref().is_ok_and(|r| r.success()))
         {
             warn!("drop detected that the game {game_id} may have failed to launch properly");
-            return Err(ProcessError::FailedLaunch(game_id));
-            // let _ = self.app_handle.emit("launch_external_error", &game_id);
+            let error = ProcessError::FailedLaunch(game_id.clone());
+            app_emit!(
+                &self.app_handle,
+                "launch_error",
+                LaunchErrorEvent {
+                    game_id,
+                    message: error.to_string(),
+                    exit_code: result.as_ref().ok().and_then(|status| status.code()),
+                    stderr_tail: tail_log(&process.error_log, LAUNCH_FAILED_LOG_LINES),
+                }
+            );
+            return Err(error);
         }
 
         let version_data = match db_handle.applications.game_versions.get(&game_id) {
@@ -173,6 +271,34 @@ impl ProcessManager<'_> {
             None => todo!(),
         };
 
+        if was_installed
+            && let Some(config) = effective_config(&db_handle, &game_id)
+            && config.sync_on_exit
+            && !config.paths.is_empty()
+        {
+            let game_version = version_data.clone();
+            let app_handle = self.app_handle.clone();
+            spawn(move || {
+                let progress =
+                    SaveSyncProgress::new(app_handle.clone(), game_version.game_id.clone());
+                match sync_after_exit(&game_version, &config, &progress) {
+                    Ok(_) => {}
+                    Err(BackupError::Conflict(conflict)) => {
+                        warn!(
+                            "not backing up saves for {}: {conflict:?}",
+                            game_version.game_id
+                        );
+                        app_emit!(
+                            &app_handle,
+                            &format!("save_conflict/{}", game_version.game_id),
+                            conflict
+                        );
+                    }
+                    Err(e) => warn!("couldn't back up saves for {}: {e}", game_version.game_id),
+                }
+            });
+        }
+
         let status = GameStatusManager::fetch_state(&game_id, &db_handle);
 
         push_game_update(
@@ -184,27 +310,71 @@ impl ProcessManager<'_> {
         Ok(())
     }
 
+    /// Picks `preferred` if it can run the game, or else the first launcher
+    /// that can
     fn fetch_process_handler(
         &self,
         db_lock: &Database,
         target_platform: &Platform,
+        preferred: Option<LauncherKind>,
     ) -> Result<&(dyn ProcessHandler + Send + Sync), ProcessError> {
-        Ok(self
-            .game_launchers
+        let launchers = self.valid_launchers(db_lock, target_platform);
+        if let Some(preferred) = preferred {
+            if let Some(launcher) = launchers.iter().find(|e| e.kind() == preferred) {
+                return Ok(*launcher);
+            }
+            warn!("preferred launcher {preferred:?} can't run the game, auto-detecting instead");
+        }
+        launchers
+            .first()
+            .copied()
+            .ok_or(ProcessError::InvalidPlatform)
+    }
+
+    fn valid_launchers(
+        &self,
+        db_lock: &Database,
+        target_platform: &Platform,
+    ) -> Vec<&(dyn ProcessHandler + Send + Sync)> {
+        self.game_launchers
             .iter()
-            .find(|e| {
+            .filter(|e| {
                 let (e_current, e_target) = e.0;
                 e_current == self.current_platform
                     && e_target == *target_platform
                     && e.1.valid_for_platform(db_lock, target_platform)
             })
-            .ok_or(ProcessError::InvalidPlatform)?
-            .1)
+            .map(|e| e.1 as &(dyn ProcessHandler + Send + Sync))
+            .collect()
+    }
+
+    /// The launchers that can run games for `platform`, in the order they'd be
+    /// auto-detected
+    pub fn available_launchers(
+        &self,
+        db_lock: &Database,
+        platform: &Platform,
+    ) -> Vec<LauncherKind> {
+        let mut kinds = Vec::new();
+        for launcher in self.valid_launchers(db_lock, platform) {
+            if !kinds.contains(&launcher.kind()) {
+                kinds.push(launcher.kind());
+            }
+        }
+        kinds
+    }
+
+    pub fn is_running(&self, game_id: &str) -> bool {
+        self.processes.contains_key(game_id)
+    }
+
+    pub fn any_running(&self) -> bool {
+        !self.processes.is_empty()
     }
 
     pub fn valid_platform(&self, platform: &Platform) -> bool {
         let db_lock = borrow_db_checked();
-        let process_handler = self.fetch_process_handler(&db_lock, platform);
+        let process_handler = self.fetch_process_handler(&db_lock, platform, None);
         process_handler.is_ok()
     }
 
@@ -267,6 +437,12 @@ impl ProcessManager<'_> {
         // TODO: refactor this path with open_process_logs
         let game_log_folder = &self.get_log_dir(game_id);
         create_dir_all(game_log_folder).map_err(ProcessError::IOError)?;
+        // The game isn't running, so none of these are in use. Leaves room
+        // for the logs of this launch.
+        rotate_logs(
+            game_log_folder,
+            db_lock.settings.max_game_logs.saturating_sub(1),
+        );
 
         let current_time = chrono::offset::Local::now();
         let log_file = OpenOptions::new()
@@ -277,27 +453,38 @@ impl ProcessManager<'_> {
             .open(game_log_folder.join(format!("{}-{}.log", &version, current_time.timestamp())))
             .map_err(ProcessError::IOError)?;
 
+        let error_log = game_log_folder.join(format!(
+            "{}-{}-error.log",
+            &version,
+            current_time.timestamp()
+        ));
         let error_file = OpenOptions::new()
             .write(true)
             .truncate(true)
             .read(true)
             .create(true)
-            .open(game_log_folder.join(format!(
-                "{}-{}-error.log",
-                &version,
-                current_time.timestamp()
-            )))
+            .open(&error_log)
             .map_err(ProcessError::IOError)?;
 
         let target_platform = game_version.platform;
 
-        let process_handler = self.fetch_process_handler(&db_lock, &target_platform)?;
+        let process_handler = self.fetch_process_handler(
+            &db_lock,
+            &target_platform,
+            game_version.preferred_launcher,
+        )?;
+
+        // The user can pick another executable in the install to launch
+        let launch_command = game_version
+            .executable_override
+            .as_ref()
+            .unwrap_or(&game_version.launch_command);
 
         let (launch, args) = match game_status {
             GameDownloadStatus::Installed {
                 version_name: _,
                 install_dir: _,
-            } => (&game_version.launch_command, &game_version.launch_args),
+            } => (launch_command, &game_version.launch_args),
             GameDownloadStatus::SetupRequired {
                 version_name: _,
                 install_dir: _,
@@ -319,12 +506,13 @@ impl ProcessManager<'_> {
             args.clone(),
             game_version,
             install_dir,
+            &db_lock,
         )?;
 
         let format_args = DropFormatArgs::new(
             launch_string,
             install_dir,
-            &game_version.launch_command,
+            launch_command,
             launch.to_string(),
         );
 
@@ -333,6 +521,16 @@ impl ProcessManager<'_> {
             .map_err(|e| ProcessError::FormatError(e.to_string()))?
             .to_string();
 
+        let defaults = &db_lock.settings.default_launch_config;
+        let wrapper = match db_lock.applications.game_launch_configs.get(&meta.id) {
+            Some(config) => config.merged_wrapper(defaults),
+            None => defaults.wrapper(),
+        };
+        let launch_string = match wrapper {
+            Some(wrapper) => wrap_launch_string(wrapper, &launch_string),
+            None => launch_string,
+        };
+
         #[cfg(target_os = "windows")]
         use std::os::windows::process::CommandExt;
         #[cfg(target_os = "windows")]
@@ -349,9 +547,20 @@ impl ProcessManager<'_> {
 
         debug!("final launch string:\n\n{launch_string}\n");
 
+        // The game's own variables are set in its launch template, so they
+        // win over these
+        let env_vars = defaults.env.iter().filter(|(key, _)| {
+            let valid = is_valid_env_key(key);
+            if !valid {
+                warn!("skipping invalid environment variable name {key:?}");
+            }
+            valid
+        });
+
         command
             .stderr(error_file)
             .stdout(log_file)
+            .envs(env_vars)
             .env_remove("RUST_LOG")
             .current_dir(install_dir);
 
@@ -372,29 +581,87 @@ impl ProcessManager<'_> {
             (None, Some(ApplicationTransientStatus::Running {})),
         );
 
+        let start = SystemTime::now();
+        if db_lock.settings.discord_rich_presence {
+            match get_cached_object_db::<Game>(&meta.id, &db_lock) {
+                Ok(game) => rich_presence::set_playing(game.name(), start),
+                Err(e) => warn!(
+                    "no cached game for {}, not setting rich presence: {e}",
+                    meta.id
+                ),
+            }
+        }
+
         let wait_thread_handle = launch_process_handle.clone();
         let wait_thread_game_id = meta.clone();
 
+        // Can't finish before it's inserted, as it needs the lock we're holding
+        let wait_thread = spawn(move || {
+            let result: Result<ExitStatus, std::io::Error> = launch_process_handle.wait();
+
+            PROCESS_MANAGER
+                .lock()
+                .on_process_finish(wait_thread_game_id.id, result)
+        });
         self.processes.insert(
             meta.id,
             RunningProcess {
                 handle: wait_thread_handle,
-                start: SystemTime::now(),
+                start,
                 manually_killed: false,
+                error_log,
+                wait_thread: Some(wait_thread),
             },
         );
-        spawn(move || {
-            let result: Result<ExitStatus, std::io::Error> = launch_process_handle.wait();
-
-            PROCESS_MANAGER
-                .lock()
-                .on_process_finish(wait_thread_game_id.id, result)
-        });
         Ok(())
     }
 }
 
+/// Restores the latest save backup if the game is installed and syncs its
+/// saves on launch. Launching goes ahead with the local saves if that fails.
+///
+/// This can take a while, so call it before locking the process manager to
+/// launch the game rather than while holding it.
+pub fn restore_saves_before_launch(app_handle: &AppHandle, game_id: &str) {
+    let (game_version, config) = {
+        let db_lock = borrow_db_checked();
+        let Some(GameDownloadStatus::Installed { version_name, .. }) =
+            db_lock.applications.game_statuses.get(game_id)
+        else {
+            return;
+        };
+        let Some(config) = effective_config(&db_lock, game_id)
+            .filter(|config| config.sync_on_launch && !config.paths.is_empty())
+        else {
+            return;
+        };
+        let Some(game_version) = db_lock
+            .applications
+            .game_versions
+            .get(game_id)
+            .and_then(|versions| versions.get(version_name))
+        else {
+            return;
+        };
+        (game_version.clone(), config)
+    };
+
+    let progress = SaveSyncProgress::new(app_handle.clone(), game_id.to_string());
+    match sync_before_launch(&game_version, &config, &progress) {
+        Ok(_) => {}
+        Err(BackupError::Conflict(conflict)) => {
+            warn!("not restoring saves for {game_id}: {conflict:?}");
+            app_emit!(app_handle, &format!("save_conflict/{game_id}"), conflict);
+        }
+        Err(e) => {
+            warn!("couldn't restore saves for {game_id}, launching with the local ones: {e}")
+        }
+    }
+}
+
 pub trait ProcessHandler: Send + 'static {
+    fn kind(&self) -> LauncherKind;
+
     fn create_launch_process(
         &self,
         meta: &DownloadableMetadata,
@@ -402,6 +669,7 @@ pub trait ProcessHandler: Send + 'static {
         args: Vec<String>,
         game_version: &GameVersion,
         current_dir: &str,
+        db: &Database,
     ) -> Result<String, ProcessError>;
 
     fn valid_for_platform(&self, db: &Database, target: &Platform) -> bool;
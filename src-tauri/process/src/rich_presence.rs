@@ -0,0 +1,60 @@
+use std::{
+    sync::{LazyLock, nonpoison::Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use discord_rich_presence::{
+    DiscordIpc, DiscordIpcClient,
+    activity::{Activity, Timestamps},
+};
+use log::debug;
+
+// Set at build time, rich presence is unavailable without it
+const DISCORD_CLIENT_ID: Option<&str> = option_env!("DROP_DISCORD_CLIENT_ID");
+
+static DISCORD_CLIENT: LazyLock<Mutex<Option<DiscordIpcClient>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// Shows `name` as the game being played on the user's Discord profile.
+/// Does nothing if Discord isn't running.
+pub fn set_playing(name: &str, start: SystemTime) {
+    let Some(client_id) = DISCORD_CLIENT_ID else {
+        debug!("built without a Discord client id, not setting rich presence");
+        return;
+    };
+
+    let mut client_lock = DISCORD_CLIENT.lock();
+    let client = match client_lock.as_mut() {
+        Some(client) => client,
+        None => {
+            let mut client = DiscordIpcClient::new(client_id);
+            if let Err(e) = client.connect() {
+                debug!("couldn't connect to Discord for rich presence: {e}");
+                return;
+            }
+            client_lock.insert(client)
+        }
+    };
+
+    let start = start
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default();
+    let activity = Activity::new()
+        .name(name)
+        .details(name)
+        .timestamps(Timestamps::new().start(start));
+    if let Err(e) = client.set_activity(activity) {
+        // Most likely Discord was closed, so connect again next time
+        debug!("couldn't set Discord rich presence: {e}");
+        *client_lock = None;
+    }
+}
+
+/// Removes the game from the user's Discord profile
+pub fn clear() {
+    if let Some(mut client) = DISCORD_CLIENT.lock().take() {
+        let _ = client.clear_activity();
+        let _ = client.close();
+    }
+}
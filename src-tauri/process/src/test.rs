@@ -0,0 +1,25 @@
+use crate::format::{wrap_for_cmd, wrap_for_sh};
+
+#[test]
+fn sh_wrapper_runs_the_whole_command() {
+    assert_eq!(
+        wrap_for_sh("gamemoderun", "cd 'My Game' && DXVK_HUD=fps ./game"),
+        r#"gamemoderun sh -c 'cd '\''My Game'\'' && DXVK_HUD=fps ./game'"#
+    );
+}
+
+#[test]
+fn cmd_wrapper_escapes_nested_quotes() {
+    assert_eq!(
+        wrap_for_cmd(
+            "wrapper.exe",
+            r#"cd /d "C:\My Game" && set A=b^&c&& "game.exe" "an arg""#
+        ),
+        r#"wrapper.exe cmd /S /C ^"cd /d ^"C:\My Game^" ^&^& set A=b^^^&c^&^& ^"game.exe^" ^"an arg^"^""#
+    );
+    // Nothing is left unescaped that the outer cmd would act on
+    assert_eq!(
+        wrap_for_cmd("wrapper.exe", "game.exe | more"),
+        r#"wrapper.exe cmd /S /C ^"game.exe ^| more^""#
+    );
+}
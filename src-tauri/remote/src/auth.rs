@@ -2,7 +2,10 @@ use std::{collections::HashMap, env};
 
 use chrono::Utc;
 use client::{app_status::AppStatus, user::User};
-use database::{DatabaseAuth, interface::borrow_db_checked};
+use database::{
+    DatabaseAuth,
+    interface::{borrow_db_checked, borrow_db_mut_checked},
+};
 use droplet_rs::ssl::sign_nonce;
 use gethostname::gethostname;
 use log::{error, warn};
@@ -11,7 +14,7 @@ use url::Url;
 
 use crate::{
     error::{DropServerError, RemoteAccessError},
-    requests::make_authenticated_get,
+    requests::{generate_authenticated_headers, generate_extra_headers, make_authenticated_get},
     utils::DROP_CLIENT_SYNC,
 };
 
@@ -60,18 +63,44 @@ impl From<HandshakeResponse> for DatabaseAuth {
     }
 }
 
-pub fn generate_authorization_header() -> String {
+#[derive(Serialize, Clone, Debug)]
+pub enum ReauthReason {
+    /// The stored key can't be used to sign requests
+    UnusableCredentials,
+    NonceExpired,
+    CertificateRejected,
+}
+
+/// Payload of the `auth/needs_reauth` event
+#[derive(Serialize, Clone, Debug)]
+pub struct ReauthRequired {
+    pub reason: ReauthReason,
+    pub message: String,
+}
+
+impl ReauthRequired {
+    fn new(reason: ReauthReason, error: RemoteAccessError) -> Self {
+        Self {
+            reason,
+            message: error.to_string(),
+        }
+    }
+}
+
+pub fn generate_authorization_header() -> Result<String, RemoteAccessError> {
     let certs = {
         let db = borrow_db_checked();
-        db.auth.clone().expect("Authorisation not initialised")
+        db.auth
+            .clone()
+            .ok_or(RemoteAccessError::SigningFailed("not signed in".to_owned()))?
     };
 
     let nonce = Utc::now().timestamp_millis().to_string();
 
-    let signature =
-        sign_nonce(certs.private, nonce.clone()).expect("Failed to generate authorisation header");
+    let signature = sign_nonce(certs.private, nonce.clone())
+        .map_err(|e| RemoteAccessError::SigningFailed(format!("{e:?}")))?;
 
-    format!("Nonce {} {} {}", certs.client_id, nonce, signature)
+    Ok(format!("Nonce {} {} {}", certs.client_id, nonce, signature))
 }
 
 pub async fn fetch_user() -> Result<User, RemoteAccessError> {
@@ -113,7 +142,11 @@ pub fn auth_initiate_logic(mode: String) -> Result<String, RemoteAccessError> {
     };
 
     let client = DROP_CLIENT_SYNC.clone();
-    let response = client.post(endpoint.to_string()).json(&body).send()?;
+    let response = client
+        .post(endpoint.to_string())
+        .headers(generate_extra_headers())
+        .json(&body)
+        .send()?;
 
     if response.status() != 200 {
         let data: DropServerError = response.json()?;
@@ -127,26 +160,70 @@ pub fn auth_initiate_logic(mode: String) -> Result<String, RemoteAccessError> {
     Ok(response)
 }
 
-pub async fn setup() -> (AppStatus, Option<User>) {
+/// Requests a new web token for the embedded store and saves it. Web tokens
+/// expire independently of the client certificate.
+pub fn refresh_web_token() -> Result<String, RemoteAccessError> {
+    let response = DROP_CLIENT_SYNC
+        .post(generate_url(&["/api/v1/client/user/webtoken"], &[])?)
+        .headers(generate_authenticated_headers()?)
+        .send()?;
+    if !response.status().is_success() {
+        return Err(RemoteAccessError::InvalidResponse(response.json()?));
+    }
+    let web_token = response.text()?;
+
+    let mut handle = borrow_db_mut_checked();
+    let auth = handle
+        .auth
+        .as_mut()
+        .ok_or(RemoteAccessError::SigningFailed("not signed in".to_owned()))?;
+    auth.web_token = Some(web_token.clone());
+
+    Ok(web_token)
+}
+
+pub async fn setup() -> (AppStatus, Option<User>, Option<ReauthRequired>) {
     let auth = {
         let data = borrow_db_checked();
         data.auth.clone()
     };
 
     if auth.is_some() {
+        // A corrupt or partially written key can't sign anything, so there's
+        // no point trying the server
+        if let Err(e) = generate_authorization_header() {
+            error!("stored credentials are unusable: {e}");
+            return (
+                AppStatus::SignedInNeedsReauth,
+                None,
+                Some(ReauthRequired::new(ReauthReason::UnusableCredentials, e)),
+            );
+        }
+
         let user_result = match fetch_user().await {
             Ok(data) => data,
             Err(RemoteAccessError::FetchError(_)) => {
                 let user = get_cached_object::<User>("user").ok();
-                return (AppStatus::Offline, user);
+                return (AppStatus::Offline, user, None);
+            }
+            Err(e) => {
+                let reason = match e {
+                    RemoteAccessError::OutOfSync => ReauthReason::NonceExpired,
+                    RemoteAccessError::SigningFailed(_) => ReauthReason::UnusableCredentials,
+                    _ => ReauthReason::CertificateRejected,
+                };
+                return (
+                    AppStatus::SignedInNeedsReauth,
+                    None,
+                    Some(ReauthRequired::new(reason, e)),
+                );
             }
-            Err(_) => return (AppStatus::SignedInNeedsReauth, None),
         };
         if let Err(e) = cache_object("user", &user_result) {
             warn!("Could not cache user object with error {e}");
         }
-        return (AppStatus::SignedIn, Some(user_result));
+        return (AppStatus::SignedIn, Some(user_result), None);
     }
 
-    (AppStatus::SignedOut, None)
+    (AppStatus::SignedOut, None, None)
 }
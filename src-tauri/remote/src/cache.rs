@@ -1,13 +1,25 @@
 use std::{
-    fs::File,
-    io::{self, Write},
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
     path::{Path, PathBuf},
+    sync::{
+        LazyLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        nonpoison::Mutex,
+    },
+    thread::spawn,
     time::SystemTime,
 };
 
 use bitcode::{Decode, DecodeOwned, Encode};
 use database::{Database, borrow_db_checked};
-use http::{Response, header::CONTENT_TYPE, response::Builder as ResponseBuilder};
+use http::{
+    HeaderMap, Response,
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    response::Builder as ResponseBuilder,
+};
+use log::warn;
 
 use crate::error::{CacheError, RemoteAccessError};
 
@@ -26,6 +38,17 @@ macro_rules! offline {
     }
 }
 
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// When entries were last read this run. Eviction goes by whichever is newer
+/// of this and the modification time, which is all there is after a restart.
+static LAST_READ: LazyLock<Mutex<HashMap<PathBuf, SystemTime>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Eviction walks the whole cache, so it runs at most this often
+const EVICTION_INTERVAL_SECS: u64 = 60;
+static LAST_EVICTION: AtomicU64 = AtomicU64::new(0);
+
 fn get_sys_time_in_secs() -> u64 {
     match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
         Ok(n) => n.as_secs(),
@@ -33,27 +56,66 @@ fn get_sys_time_in_secs() -> u64 {
     }
 }
 
-fn get_cache_path(base: &Path, key: &str) -> PathBuf {
+pub(crate) fn get_cache_path(base: &Path, key: &str) -> PathBuf {
     let key_hash = hex::encode(md5::compute(key.as_bytes()).0);
     base.join(key_hash)
 }
 
-fn write_sync(base: &Path, key: &str, data: Vec<u8>) -> io::Result<()> {
+pub(crate) fn write_sync(base: &Path, key: &str, data: Vec<u8>) -> io::Result<()> {
     let cache_path = get_cache_path(base, key);
-    let mut file = File::create(cache_path)?;
-    file.write_all(&data)?;
-    Ok(())
+    // Write next to the entry and rename over it, so a crash mid-write leaves
+    // either the old entry or the new one, never half of one
+    let temp_path = cache_path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &cache_path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
 }
 
 fn read_sync(base: &Path, key: &str) -> io::Result<Vec<u8>> {
     let cache_path = get_cache_path(base, key);
-    let file = std::fs::read(cache_path)?;
+    let file = fs::read(&cache_path)?;
+    LAST_READ.lock().insert(cache_path, SystemTime::now());
     Ok(file)
 }
 
+/// Reads and decodes a cache entry. Empty or undecodable entries are deleted
+/// and reported as missing, the same as if they had never been written.
+pub(crate) fn read_cached_object<D: DecodeOwned>(
+    base: &Path,
+    key: &str,
+) -> Result<D, RemoteAccessError> {
+    let bytes = read_sync(base, key).map_err(RemoteAccessError::Cache)?;
+    let data = if bytes.is_empty() {
+        None
+    } else {
+        bitcode::decode::<D>(&bytes).ok()
+    };
+    data.ok_or_else(|| {
+        warn!("discarding corrupt cache entry for {key}");
+        if let Err(e) = delete_sync(base, key) {
+            warn!("could not remove corrupt cache entry for {key}: {e}");
+        }
+        RemoteAccessError::Cache(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no usable cache entry for {key}"),
+        ))
+    })
+}
+
 fn delete_sync(base: &Path, key: &str) -> io::Result<()> {
     let cache_path = get_cache_path(base, key);
-    std::fs::remove_file(cache_path)?;
+    fs::remove_file(cache_path)?;
     Ok(())
 }
 
@@ -66,7 +128,125 @@ pub fn cache_object_db<D: Encode>(
     database: &Database,
 ) -> Result<(), RemoteAccessError> {
     let bytes = bitcode::encode(data);
-    write_sync(&database.cache_dir, key, bytes).map_err(RemoteAccessError::Cache)
+    write_sync(&database.cache_dir, key, bytes).map_err(RemoteAccessError::Cache)?;
+    schedule_eviction(database);
+    Ok(())
+}
+
+/// Evicts old entries on another thread, so the caller isn't held up (often
+/// with the database borrowed) walking the cache. Skipped if it already ran
+/// recently.
+fn schedule_eviction(database: &Database) {
+    let max_bytes = database.settings.max_cache_bytes;
+    if max_bytes == 0 {
+        return;
+    }
+    let now = get_sys_time_in_secs();
+    let last = LAST_EVICTION.load(Ordering::Acquire);
+    if now.saturating_sub(last) < EVICTION_INTERVAL_SECS
+        || LAST_EVICTION
+            .compare_exchange(last, now, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+    {
+        return;
+    }
+
+    let base = database.cache_dir.clone();
+    let pinned = pinned_keys(database);
+    spawn(move || {
+        if let Err(e) = evict_cache(&base, max_bytes, &pinned) {
+            warn!("could not evict old cache entries: {e}");
+        }
+    });
+}
+
+/// Entries offline mode can't do without, which are never evicted
+const STRUCTURAL_KEYS: &[&str] = &["library", "collections", "user"];
+
+/// Removes the structural entries, which belong to whoever was signed in, so
+/// the next account doesn't see them. Ones that aren't cached are skipped.
+pub fn clear_structural_cache(base: &Path) -> io::Result<()> {
+    for key in STRUCTURAL_KEYS {
+        match delete_sync(base, key) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The structural entries, plus the installed games so they can still be
+/// shown and launched offline
+pub fn pinned_keys(db: &Database) -> Vec<String> {
+    STRUCTURAL_KEYS
+        .iter()
+        .map(|key| key.to_string())
+        .chain(db.applications.installed_game_version.keys().cloned())
+        .collect()
+}
+
+/// Cache entries on disk with their size and when they were last used.
+/// Temporary files from writes in progress are left out.
+fn cache_entries(base: &Path) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+    let last_read = LAST_READ.lock().clone();
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(base)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_some() {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified()?;
+        let used = last_read
+            .get(&path)
+            .map_or(modified, |read| modified.max(*read));
+        entries.push((path, metadata.len(), used));
+    }
+    Ok(entries)
+}
+
+/// Total size of the cache, in bytes
+pub fn cache_size(base: &Path) -> io::Result<u64> {
+    Ok(cache_entries(base)?.iter().map(|(_, size, _)| size).sum())
+}
+
+/// Deletes the least recently used entries, other than `pinned`, until the
+/// cache fits in `max_bytes`. Returns how many bytes were freed.
+pub fn evict_cache(base: &Path, max_bytes: u64, pinned: &[String]) -> io::Result<u64> {
+    let pinned: Vec<PathBuf> = pinned.iter().map(|key| get_cache_path(base, key)).collect();
+    let mut entries = cache_entries(base)?;
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    if total <= max_bytes {
+        return Ok(0);
+    }
+
+    entries.retain(|(path, _, _)| !pinned.contains(path));
+    entries.sort_by_key(|(_, _, used)| *used);
+
+    let mut freed = 0;
+    for (path, size, _) in entries {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                LAST_READ.lock().remove(&path);
+                total -= size;
+                freed += size;
+            }
+            Err(e) => warn!("could not evict cache entry {}: {e}", path.display()),
+        }
+    }
+    Ok(freed)
+}
+
+/// Deletes every entry other than `pinned`. Returns how many bytes were freed.
+pub fn clear_cache(base: &Path, pinned: &[String]) -> io::Result<u64> {
+    evict_cache(base, 0, pinned)
 }
 pub fn get_cached_object<D: Encode + DecodeOwned>(key: &str) -> Result<D, RemoteAccessError> {
     get_cached_object_db::<D>(key, &borrow_db_checked())
@@ -75,10 +255,7 @@ pub fn get_cached_object_db<D: DecodeOwned>(
     key: &str,
     db: &Database,
 ) -> Result<D, RemoteAccessError> {
-    let bytes = read_sync(&db.cache_dir, key).map_err(RemoteAccessError::Cache)?;
-    let data =
-        bitcode::decode::<D>(&bytes).map_err(|e| RemoteAccessError::Cache(io::Error::other(e)))?;
-    Ok(data)
+    read_cached_object(&db.cache_dir, key)
 }
 pub fn clear_cached_object(key: &str) -> Result<(), RemoteAccessError> {
     clear_cached_object_db(key, &borrow_db_checked())
@@ -96,16 +273,13 @@ pub struct ObjectCache {
 }
 
 impl ObjectCache {
-    pub fn has_expired(&self) -> bool {
-        let current = get_sys_time_in_secs();
-        self.expiry < current
-    }
-}
-
-impl TryFrom<Response<Vec<u8>>> for ObjectCache {
-    type Error = CacheError;
-
-    fn try_from(value: Response<Vec<u8>>) -> Result<Self, Self::Error> {
+    /// Caches `value` for as long as its `Cache-Control: max-age` allows, or
+    /// for `default_ttl_secs` if it doesn't say
+    pub fn from_response(
+        value: &Response<Vec<u8>>,
+        default_ttl_secs: u64,
+    ) -> Result<Self, CacheError> {
+        let ttl = max_age(value.headers()).unwrap_or(default_ttl_secs);
         Ok(ObjectCache {
             content_type: value
                 .headers()
@@ -115,10 +289,36 @@ impl TryFrom<Response<Vec<u8>>> for ObjectCache {
                 .map_err(CacheError::ParseError)?
                 .to_owned(),
             body: value.body().clone(),
-            expiry: get_sys_time_in_secs() + 60 * 60 * 24,
+            expiry: get_sys_time_in_secs() + ttl,
         })
     }
+    pub fn has_expired(&self) -> bool {
+        // No expiry was recorded, so refetch it and store one with the
+        // default TTL
+        if self.expiry == 0 {
+            return true;
+        }
+        let current = get_sys_time_in_secs();
+        self.expiry < current
+    }
 }
+
+/// The `max-age` directive of a `Cache-Control` header, in seconds
+pub fn max_age(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CACHE_CONTROL)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .find_map(|directive| {
+            let (name, value) = directive.trim().split_once('=')?;
+            if !name.eq_ignore_ascii_case("max-age") {
+                return None;
+            }
+            value.trim().trim_matches('"').parse().ok()
+        })
+}
+
 impl TryFrom<ObjectCache> for Response<Vec<u8>> {
     type Error = CacheError;
     fn try_from(value: ObjectCache) -> Result<Self, Self::Error> {
@@ -0,0 +1,129 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use database::db::DATA_ROOT_DIR;
+use log::{info, warn};
+use reqwest::Certificate;
+use serde::Serialize;
+
+use crate::error::RemoteAccessError;
+
+/// Files that went into the clients' certificate bundle, once it's been built
+static LOADED_CERTIFICATES: OnceLock<HashSet<String>> = OnceLock::new();
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateInfo {
+    pub name: String,
+    /// How many certificates the file holds
+    pub certificates: usize,
+    /// Whether the running clients trust this file yet
+    pub loaded: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateList {
+    pub certificates: Vec<CertificateInfo>,
+    /// The certificate bundle is only read when the clients are built, so
+    /// changes since then need a restart to apply
+    pub restart_required: bool,
+}
+
+pub fn certificates_dir() -> PathBuf {
+    DATA_ROOT_DIR.join("certificates")
+}
+
+pub(crate) fn mark_loaded(names: HashSet<String>) {
+    let _ = LOADED_CERTIFICATES.set(names);
+}
+
+fn parse_certificates(pem: &[u8]) -> Result<Vec<Certificate>, RemoteAccessError> {
+    let certificates = Certificate::from_pem_bundle(pem)
+        .map_err(|e| RemoteAccessError::InvalidCertificate(e.to_string()))?;
+    if certificates.is_empty() {
+        return Err(RemoteAccessError::InvalidCertificate(
+            "no certificates found in PEM".to_string(),
+        ));
+    }
+    Ok(certificates)
+}
+
+/// The certificate files in `dir`, usually [`certificates_dir`]
+pub fn list_certificates(dir: &Path) -> Result<CertificateList, RemoteAccessError> {
+    let mut certificates = Vec::new();
+    if dir.exists() {
+        for entry in fs::read_dir(dir).map_err(RemoteAccessError::CertificateStorage)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("skipping unreadable certificate entry: {e}");
+                    continue;
+                }
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            let count = match fs::read(entry.path()) {
+                Ok(pem) => parse_certificates(&pem).map(|c| c.len()).unwrap_or(0),
+                Err(e) => {
+                    warn!("failed to read certificate {name}: {e}");
+                    0
+                }
+            };
+            certificates.push(CertificateInfo {
+                loaded: LOADED_CERTIFICATES
+                    .get()
+                    .is_some_and(|loaded| loaded.contains(&name)),
+                name,
+                certificates: count,
+            });
+        }
+    }
+    certificates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // New files that would be loaded, or loaded files that are gone
+    let restart_required = LOADED_CERTIFICATES.get().is_some_and(|loaded| {
+        certificates.iter().any(|c| c.certificates > 0 && !c.loaded)
+            || loaded
+                .iter()
+                .any(|name| !certificates.iter().any(|c| &c.name == name))
+    });
+    Ok(CertificateList {
+        certificates,
+        restart_required,
+    })
+}
+
+/// Validates and stores a PEM bundle, named after its hash so adding the
+/// same one twice is harmless
+pub fn add_certificate(dir: &Path, pem: &[u8]) -> Result<CertificateInfo, RemoteAccessError> {
+    let certificates = parse_certificates(pem)?;
+    let name = format!("{:x}.pem", md5::compute(pem));
+    fs::create_dir_all(dir).map_err(RemoteAccessError::CertificateStorage)?;
+    fs::write(dir.join(&name), pem).map_err(RemoteAccessError::CertificateStorage)?;
+    info!("added {} certificate(s) as {name}", certificates.len());
+    Ok(CertificateInfo {
+        loaded: LOADED_CERTIFICATES
+            .get()
+            .is_some_and(|loaded| loaded.contains(&name)),
+        name,
+        certificates: certificates.len(),
+    })
+}
+
+pub fn remove_certificate(dir: &Path, name: &str) -> Result<(), RemoteAccessError> {
+    // Only names we listed, so this can't delete anything outside the folder
+    if !list_certificates(dir)?
+        .certificates
+        .iter()
+        .any(|certificate| certificate.name == name)
+    {
+        return Err(RemoteAccessError::CertificateNotFound(name.to_string()));
+    }
+    fs::remove_file(dir.join(name)).map_err(RemoteAccessError::CertificateStorage)?;
+    info!("removed certificate {name}");
+    Ok(())
+}
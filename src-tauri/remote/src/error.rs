@@ -33,6 +33,12 @@ pub enum RemoteAccessError {
     OutOfSync,
     Cache(std::io::Error),
     CorruptedState,
+    SigningFailed(String),
+    Cancelled,
+    InvalidCertificate(String),
+    CertificateNotFound(String),
+    CertificateStorage(std::io::Error),
+    IncompatibleServer(String, String),
 }
 
 impl Display for RemoteAccessError {
@@ -93,6 +99,23 @@ impl Display for RemoteAccessError {
                 f,
                 "Drop encountered a corrupted internal state. Please report this to the developers, with details of reproduction."
             ),
+            RemoteAccessError::SigningFailed(error) => {
+                write!(f, "failed to sign request, please sign in again: {error}")
+            }
+            RemoteAccessError::Cancelled => write!(f, "request was cancelled"),
+            RemoteAccessError::InvalidCertificate(error) => {
+                write!(f, "invalid certificate: {error}")
+            }
+            RemoteAccessError::CertificateNotFound(name) => {
+                write!(f, "could not find certificate: {name}")
+            }
+            RemoteAccessError::CertificateStorage(error) => {
+                write!(f, "failed to access certificates: {error}")
+            }
+            RemoteAccessError::IncompatibleServer(client, server) => write!(
+                f,
+                "this Drop server is on version {server}, but this client only supports {client}. Please update the client or the server."
+            ),
         }
     }
 }
@@ -1,18 +1,126 @@
-use database::{DB, interface::DatabaseImpls};
-use http::{Response, header::CONTENT_TYPE, response::Builder as ResponseBuilder};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+        nonpoison::Mutex,
+    },
+};
+
+use database::{DB, borrow_db_checked, interface::DatabaseImpls};
+use http::{
+    Response, StatusCode,
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    response::Builder as ResponseBuilder,
+};
 use log::{debug, warn};
 use tauri::UriSchemeResponder;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 
-use crate::{error::CacheError, utils::DROP_CLIENT_ASYNC};
+use crate::{
+    error::{CacheError, RemoteAccessError},
+    utils::DROP_CLIENT_ASYNC,
+};
 
 use super::{
-    auth::generate_authorization_header,
     cache::{ObjectCache, cache_object, get_cached_object},
+    requests::generate_authenticated_headers,
 };
 
+/// An `object://` request that hasn't finished
+struct InFlightFetch {
+    request: u64,
+    // Set by the page that made the request, with `?owner=`
+    owner: Option<String>,
+    token: CancellationToken,
+}
+
+/// Object fetches that haven't finished, by object id. Each request has its
+/// own token, so cancelling one page's requests leaves the rest running.
+static IN_FLIGHT_FETCHES: LazyLock<Mutex<HashMap<String, Vec<InFlightFetch>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static NEXT_FETCH_REQUEST: AtomicU64 = AtomicU64::new(0);
+
+/// Cancels the `object://` requests `owner` made that are still loading
+/// `object_id`, e.g. when the page that made them is left. Returns whether
+/// there were any.
+pub fn cancel_object_fetch(object_id: &str, owner: &str) -> bool {
+    let mut in_flight = IN_FLIGHT_FETCHES.lock();
+    let Some(fetches) = in_flight.get_mut(object_id) else {
+        return false;
+    };
+    let before = fetches.len();
+    fetches.retain(|fetch| {
+        let owned = fetch.owner.as_deref() == Some(owner);
+        if owned {
+            fetch.token.cancel();
+        }
+        !owned
+    });
+    let cancelled = fetches.len() != before;
+    if fetches.is_empty() {
+        in_flight.remove(object_id);
+    }
+    cancelled
+}
+
+/// Runs `fetch` until it finishes or `cancel_object_fetch` is called for the
+/// object by `owner`. Dropping the fetch also drops its connection. The
+/// request is tracked from when this is called, not when it's first polled.
+pub(crate) fn run_cancellable_fetch(
+    object_id: String,
+    owner: Option<String>,
+    fetch: impl Future<Output = Result<Response<Vec<u8>>, CacheError>>,
+) -> impl Future<Output = Result<Response<Vec<u8>>, CacheError>> {
+    let request = NEXT_FETCH_REQUEST.fetch_add(1, Ordering::Relaxed);
+    let token = CancellationToken::new();
+    IN_FLIGHT_FETCHES
+        .lock()
+        .entry(object_id.clone())
+        .or_default()
+        .push(InFlightFetch {
+            request,
+            owner,
+            token: token.clone(),
+        });
+
+    async move {
+        let result = token.run_until_cancelled(fetch).await;
+
+        let mut in_flight = IN_FLIGHT_FETCHES.lock();
+        // Already gone if it was cancelled
+        if let Some(fetches) = in_flight.get_mut(&object_id) {
+            fetches.retain(|fetch| fetch.request != request);
+            if fetches.is_empty() {
+                in_flight.remove(&object_id);
+            }
+        }
+        drop(in_flight);
+
+        result.unwrap_or(Err(CacheError::Remote(RemoteAccessError::Cancelled)))
+    }
+}
+
 pub async fn fetch_object_wrapper(request: http::Request<Vec<u8>>, responder: UriSchemeResponder) {
-    match fetch_object(request).await {
+    // Drop leading /
+    let object_id = request.uri().path()[1..].to_owned();
+    let owner = request.uri().query().and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "owner")
+            .map(|(_, owner)| owner.into_owned())
+    });
+    match run_cancellable_fetch(object_id.clone(), owner, fetch_object(request)).await {
         Ok(r) => responder.respond(r),
+        Err(CacheError::Remote(RemoteAccessError::Cancelled)) => {
+            debug!("cancelled fetch of object {object_id}");
+            responder.respond(
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Vec::new())
+                    .expect("Failed to build cancelled response"),
+            );
+        }
         Err(e) => {
             warn!("Cache error: {e}");
             responder.respond(
@@ -25,12 +133,77 @@ pub async fn fetch_object_wrapper(request: http::Request<Vec<u8>>, responder: Ur
     };
 }
 
+/// Objects fetched at once by `prefetch_objects`
+const MAX_CONCURRENT_PREFETCHES: usize = 8;
+
+/// Fetches a batch of objects into the cache, so `object://` requests for
+/// them are served from disk. Cached objects that haven't expired are
+/// skipped. Returns whether each one is now available.
+pub async fn prefetch_objects(object_ids: Vec<String>) -> HashMap<String, bool> {
+    prefetch_with(object_ids, |object_id| async move {
+        match fetch_object_by_id(&object_id).await {
+            Ok(response) if response.status().is_success() => true,
+            Ok(response) => {
+                debug!("couldn't prefetch {object_id}: {}", response.status());
+                false
+            }
+            Err(e) => {
+                debug!("couldn't prefetch {object_id}: {e}");
+                false
+            }
+        }
+    })
+    .await
+}
+
+/// Runs `fetch` once for each distinct id, a few at a time
+pub(crate) async fn prefetch_with<F, Fut>(
+    mut object_ids: Vec<String>,
+    fetch: F,
+) -> HashMap<String, bool>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    object_ids.sort();
+    object_ids.dedup();
+
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_PREFETCHES));
+    let tasks = object_ids
+        .into_iter()
+        .map(|object_id| {
+            let permits = permits.clone();
+            let fetch = fetch(object_id.clone());
+            tauri::async_runtime::spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                (object_id, fetch.await)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = HashMap::new();
+    for task in tasks {
+        match task.await {
+            Ok((object_id, fetched)) => {
+                results.insert(object_id, fetched);
+            }
+            Err(e) => warn!("object prefetch task failed: {e}"),
+        }
+    }
+    results
+}
+
 pub async fn fetch_object(
     request: http::Request<Vec<u8>>,
 ) -> Result<Response<Vec<u8>>, CacheError> {
     // Drop leading /
     let object_id = &request.uri().path()[1..];
+    fetch_object_by_id(object_id).await
+}
 
+/// An object from the cache, or from the server if it isn't cached or has
+/// expired
+pub async fn fetch_object_by_id(object_id: &str) -> Result<Response<Vec<u8>>, CacheError> {
     let cache_result = get_cached_object::<ObjectCache>(object_id);
     if let Ok(cache_result) = &cache_result
         && !cache_result.has_expired()
@@ -38,19 +211,26 @@ pub async fn fetch_object(
         return cache_result.try_into();
     }
 
-    let header = generate_authorization_header();
     let client = DROP_CLIENT_ASYNC.clone();
     let url = format!("{}api/v1/client/object/{object_id}", DB.fetch_base_url());
-    let response = client.get(url).header("Authorization", header).send().await;
+    let response = client
+        .get(url)
+        .headers(generate_authenticated_headers().map_err(CacheError::Remote)?)
+        .send()
+        .await;
 
     match response {
         Ok(r) => {
-            let resp_builder = ResponseBuilder::new().header(
+            let status = r.status();
+            let mut resp_builder = ResponseBuilder::new().status(status).header(
                 CONTENT_TYPE,
                 r.headers()
                     .get("Content-Type")
                     .expect("Failed get Content-Type header"),
             );
+            if let Some(cache_control) = r.headers().get(CACHE_CONTROL) {
+                resp_builder = resp_builder.header(CACHE_CONTROL, cache_control);
+            }
             let data = match r.bytes().await {
                 Ok(data) => Vec::from(data),
                 Err(e) => {
@@ -61,9 +241,14 @@ pub async fn fetch_object(
             let resp = resp_builder
                 .body(data)
                 .expect("Failed to build object cache response body");
-            if cache_result.map_or(true, |x| x.has_expired()) {
-                cache_object::<ObjectCache>(object_id, &resp.clone().try_into()?)
-                    .expect("Failed to create cached object");
+            // Errors are passed on, but not cached in place of the object
+            if status.is_success() && cache_result.map_or(true, |x| x.has_expired()) {
+                let default_ttl = borrow_db_checked().settings.object_cache_ttl_hours * 60 * 60;
+                cache_object::<ObjectCache>(
+                    object_id,
+                    &ObjectCache::from_response(&resp, default_ttl)?,
+                )
+                .expect("Failed to create cached object");
             }
 
             Ok(resp)
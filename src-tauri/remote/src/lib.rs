@@ -1,6 +1,10 @@
+#![feature(nonpoison_mutex)]
+#![feature(sync_nonpoison)]
+
 pub mod auth;
 #[macro_use]
 pub mod cache;
+pub mod certificates;
 pub mod error;
 pub mod fetch_object;
 pub mod requests;
@@ -8,3 +12,6 @@ pub mod server_proto;
 pub mod utils;
 
 pub use auth::setup;
+
+#[cfg(test)]
+mod test;
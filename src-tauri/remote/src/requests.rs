@@ -1,4 +1,11 @@
-use database::{DB, interface::DatabaseImpls};
+use std::time::Duration;
+
+use database::{DB, borrow_db_checked, interface::DatabaseImpls};
+use log::warn;
+use reqwest::{
+    StatusCode,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+};
 use url::Url;
 
 use crate::{
@@ -22,10 +29,102 @@ pub fn generate_url<T: AsRef<str>>(
     Ok(base_url)
 }
 
-pub async fn make_authenticated_get(url: Url) -> Result<reqwest::Response, reqwest::Error> {
-    DROP_CLIENT_ASYNC
-        .get(url)
-        .header("Authorization", generate_authorization_header())
-        .send()
-        .await
+/// Extra headers the user has configured for their server, e.g. for an auth
+/// proxy in front of it. Values are marked sensitive so they never get logged.
+pub fn generate_extra_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &borrow_db_checked().settings.extra_headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(mut value)) => {
+                value.set_sensitive(true);
+                headers.insert(name, value);
+            }
+            _ => warn!("ignoring invalid custom header {name}"),
+        }
+    }
+    headers
+}
+
+/// The `Authorization` header for this client, along with any extra headers
+pub fn generate_authenticated_headers() -> Result<HeaderMap, RemoteAccessError> {
+    let mut headers = generate_extra_headers();
+    let mut authorization = HeaderValue::from_str(&generate_authorization_header()?)
+        .map_err(|e| RemoteAccessError::SigningFailed(e.to_string()))?;
+    authorization.set_sensitive(true);
+    headers.insert(AUTHORIZATION, authorization);
+    Ok(headers)
+}
+
+/// How many times a failed GET is retried
+const MAX_GET_RETRIES: u32 = 2;
+const GET_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Longest `Retry-After` we'll wait out before giving up on the request
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// Whether a request that never got a response is worth trying again
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout()
+}
+
+/// Server-side errors are retried, everything else (e.g. 401 or 404) isn't
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// How long the server asked us to wait, from a `Retry-After` header in
+/// either seconds or HTTP-date form
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// GETs `url` with this client's authentication, retrying a couple of times
+/// with backoff if the server is unreachable or returns a 5xx
+pub async fn make_authenticated_get(url: Url) -> Result<reqwest::Response, RemoteAccessError> {
+    let mut attempt = 0;
+    loop {
+        // Regenerated each time, since the signature is timestamped
+        let result = DROP_CLIENT_ASYNC
+            .get(url.clone())
+            .headers(generate_authenticated_headers()?)
+            .send()
+            .await;
+        let backoff = GET_RETRY_BASE_DELAY.saturating_mul(2u32.pow(attempt));
+        let delay = match &result {
+            Ok(response) if is_retryable_status(response.status()) => {
+                match retry_after(response.headers()) {
+                    Some(delay) if delay > MAX_RETRY_AFTER => return Ok(result?),
+                    Some(delay) => delay,
+                    None => backoff,
+                }
+            }
+            Err(e) if is_retryable_error(e) => backoff,
+            _ => return Ok(result?),
+        };
+        if attempt >= MAX_GET_RETRIES {
+            return Ok(result?);
+        }
+        attempt += 1;
+        match &result {
+            Ok(response) => warn!(
+                "GET {url} returned {}, retrying in {delay:?} ({attempt}/{MAX_GET_RETRIES})",
+                response.status()
+            ),
+            Err(e) => {
+                warn!("GET {url} failed: {e}, retrying in {delay:?} ({attempt}/{MAX_GET_RETRIES})")
+            }
+        }
+        tokio::time::sleep(delay).await;
+    }
 }
@@ -2,13 +2,22 @@ use std::str::FromStr;
 
 use database::borrow_db_checked;
 use http::{Request, Response, StatusCode, Uri, uri::PathAndQuery};
-use log::{error, warn};
-use tauri::UriSchemeResponder;
-use utils::webbrowser_open::webbrowser_open;
+use log::{error, info, warn};
+use serde::Serialize;
+use tauri::{AppHandle, UriSchemeResponder};
+use utils::{app_emit, webbrowser_open::webbrowser_open};
 
-use crate::utils::DROP_CLIENT_SYNC;
+use crate::{auth::refresh_web_token, requests::generate_extra_headers, utils::DROP_CLIENT_SYNC};
+
+/// Tells the frontend to show a game's own page
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OpenGameEvent {
+    game_id: String,
+}
 
 pub async fn handle_server_proto_offline_wrapper(
+    _app: AppHandle,
     request: Request<Vec<u8>>,
     responder: UriSchemeResponder,
 ) {
@@ -27,8 +36,12 @@ pub async fn handle_server_proto_offline(
         .expect("Failed to build error response for proto offline"))
 }
 
-pub async fn handle_server_proto_wrapper(request: Request<Vec<u8>>, responder: UriSchemeResponder) {
-    match handle_server_proto(request).await {
+pub async fn handle_server_proto_wrapper(
+    app: AppHandle,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    match handle_server_proto(&app, request).await {
         Ok(r) => responder.respond(r),
         Err(e) => {
             warn!("Cache error: {e}");
@@ -42,23 +55,53 @@ pub async fn handle_server_proto_wrapper(request: Request<Vec<u8>>, responder: U
     }
 }
 
-async fn handle_server_proto(request: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, StatusCode> {
-    let db_handle = borrow_db_checked();
-    let auth = match db_handle.auth.as_ref() {
-        Some(auth) => auth,
-        None => {
-            error!("Could not find auth in database");
-            return Err(StatusCode::UNAUTHORIZED);
-        }
-    };
-    let web_token = match &auth.web_token {
-        Some(token) => token,
-        None => return Err(StatusCode::UNAUTHORIZED),
+/// The game a `/store/<game_id>` page is for, if it's one the client knows
+/// about. Other pages under `/store` have a segment there too, so unknown
+/// ids are left to the web store.
+fn store_game_id(path: &str) -> Option<String> {
+    let mut segments = path.strip_prefix("/store/")?.split('/');
+    let game_id = segments.next().filter(|id| !id.is_empty())?;
+    if segments.any(|segment| !segment.is_empty()) {
+        return None;
+    }
+    borrow_db_checked()
+        .applications
+        .game_statuses
+        .contains_key(game_id)
+        .then(|| game_id.to_owned())
+}
+
+async fn handle_server_proto(
+    app: &AppHandle,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, StatusCode> {
+    if borrow_db_checked().settings.native_store_pages
+        && let Some(game_id) = store_game_id(request.uri().path())
+    {
+        info!("showing the native page for {game_id} instead of the store");
+        app_emit!(app, "open_game", OpenGameEvent { game_id });
+        return Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Vec::new())
+            .expect("Failed to build native store page response"));
+    }
+
+    let (web_token, base_url) = {
+        let db_handle = borrow_db_checked();
+        let auth = match db_handle.auth.as_ref() {
+            Some(auth) => auth,
+            None => {
+                error!("Could not find auth in database");
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        };
+        let web_token = match &auth.web_token {
+            Some(token) => token.clone(),
+            None => return Err(StatusCode::UNAUTHORIZED),
+        };
+        (web_token, db_handle.base_url.clone())
     };
-    let remote_uri = db_handle
-        .base_url
-        .parse::<Uri>()
-        .expect("Failed to parse base url");
+    let remote_uri = base_url.parse::<Uri>().expect("Failed to parse base url");
 
     let path = request.uri().path();
 
@@ -79,19 +122,15 @@ async fn handle_server_proto(request: Request<Vec<u8>>) -> Result<Response<Vec<u
         return Ok(Response::new(Vec::new()));
     }
 
-    let client = DROP_CLIENT_SYNC.clone();
-    let response = match client
-        .request(request.method().clone(), new_uri.to_string())
-        .header("Authorization", format!("Bearer {web_token}"))
-        .headers(request.headers().clone())
-        .send()
-    {
-        Ok(response) => response,
-        Err(e) => {
-            warn!("Could not send response. Got {e} when sending");
-            return Err(e.status().unwrap_or(StatusCode::BAD_REQUEST));
+    let mut response = send_proxied_request(&request, &new_uri, &web_token)?;
+
+    // The web token expires on its own, so get a new one and try once more
+    if response.status() == StatusCode::UNAUTHORIZED {
+        match refresh_web_token() {
+            Ok(web_token) => response = send_proxied_request(&request, &new_uri, &web_token)?,
+            Err(e) => warn!("Could not refresh web token: {e}"),
         }
-    };
+    }
 
     let response_status = response.status();
     let response_body = match response.bytes() {
@@ -106,3 +145,24 @@ async fn handle_server_proto(request: Request<Vec<u8>>) -> Result<Response<Vec<u
 
     Ok(http_response)
 }
+
+fn send_proxied_request(
+    request: &Request<Vec<u8>>,
+    uri: &Uri,
+    web_token: &str,
+) -> Result<reqwest::blocking::Response, StatusCode> {
+    let client = DROP_CLIENT_SYNC.clone();
+    match client
+        .request(request.method().clone(), uri.to_string())
+        .header("Authorization", format!("Bearer {web_token}"))
+        .headers(request.headers().clone())
+        .headers(generate_extra_headers())
+        .send()
+    {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            warn!("Could not send response. Got {e} when sending");
+            Err(e.status().unwrap_or(StatusCode::BAD_REQUEST))
+        }
+    }
+}
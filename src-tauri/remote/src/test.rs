@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpListener,
+    sync::{Arc, nonpoison::Mutex},
+    thread::spawn,
+    time::{Duration, SystemTime},
+};
+
+use http::{
+    HeaderMap, HeaderValue, Response,
+    header::{CACHE_CONTROL, RETRY_AFTER},
+};
+use tokio::sync::Semaphore;
+use utils::test_support::test_dir;
+
+use crate::{
+    cache::{
+        cache_size, clear_structural_cache, evict_cache, get_cache_path, max_age,
+        read_cached_object, write_sync,
+    },
+    certificates::{add_certificate, list_certificates, remove_certificate},
+    error::{CacheError, RemoteAccessError},
+    fetch_object::{cancel_object_fetch, prefetch_with, run_cancellable_fetch},
+    requests::retry_after,
+    utils::{ServerVersion, get_client_sync},
+};
+
+const MANIFEST: &str = r#"{"game.exe":{"permissions":493,"ids":["a"],"checksums":["b"],"lengths":[4],"versionName":"1.0"}}"#;
+
+// MANIFEST, gzipped
+const GZIPPED_MANIFEST: [u8; 103] = [
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0xab, 0x56, 0x4a, 0x4f, 0xcc, 0x4d,
+    0xd5, 0x4b, 0xad, 0x48, 0x55, 0xb2, 0xaa, 0x56, 0x2a, 0x48, 0x2d, 0xca, 0xcd, 0x2c, 0x2e, 0xce,
+    0xcc, 0xcf, 0x2b, 0x56, 0xb2, 0x32, 0xb1, 0x34, 0xd6, 0x51, 0xca, 0x4c, 0x01, 0xb2, 0xa2, 0x95,
+    0x12, 0x95, 0x62, 0x75, 0x94, 0x92, 0x33, 0x52, 0x93, 0xb3, 0x8b, 0x4b, 0x73, 0xc1, 0x22, 0x49,
+    0x20, 0x91, 0x9c, 0xd4, 0xbc, 0xf4, 0x92, 0x0c, 0x10, 0xdf, 0x04, 0xc8, 0x2b, 0x4b, 0x2d, 0x02,
+    0x69, 0xf5, 0x03, 0x1a, 0xa8, 0x64, 0xa5, 0x64, 0xa8, 0x67, 0xa0, 0x54, 0x5b, 0x0b, 0x00, 0x15,
+    0x64, 0x4b, 0xcc, 0x60, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn decompresses_gzip_manifest() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server = spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut accept_encoding = None;
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("accept-encoding")
+            {
+                accept_encoding = Some(value.trim().to_owned());
+            }
+        }
+
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            GZIPPED_MANIFEST.len()
+        )
+        .unwrap();
+        stream.write_all(&GZIPPED_MANIFEST).unwrap();
+
+        accept_encoding
+    });
+
+    let response = get_client_sync(None)
+        .get(format!("http://{address}/api/v1/client/game/manifest"))
+        .send()
+        .unwrap();
+    assert_eq!(response.text().unwrap(), MANIFEST);
+
+    let accept_encoding = server.join().unwrap().expect("no Accept-Encoding sent");
+    assert!(accept_encoding.contains("gzip"));
+    assert!(accept_encoding.contains("deflate"));
+}
+
+fn assert_cache_miss(result: Result<Vec<String>, RemoteAccessError>) {
+    match result {
+        Err(RemoteAccessError::Cache(e)) => assert_eq!(e.kind(), ErrorKind::NotFound),
+        Err(e) => panic!("expected a cache miss, got {e}"),
+        Ok(data) => panic!("expected a cache miss, got {data:?}"),
+    }
+}
+
+#[test]
+fn truncated_cache_entry_is_a_miss() {
+    let dir = test_dir("truncated-cache");
+    let library = vec!["first game".to_owned(), "second game".to_owned()];
+    let bytes = bitcode::encode(&library);
+
+    write_sync(&dir, "library", bytes.clone()).unwrap();
+    assert_eq!(
+        read_cached_object::<Vec<String>>(&dir, "library").unwrap(),
+        library
+    );
+
+    write_sync(&dir, "library", bytes[..bytes.len() / 2].to_vec()).unwrap();
+    assert_cache_miss(read_cached_object(&dir, "library"));
+    // The corrupt entry is gone, so the next read is a plain miss too
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    assert_cache_miss(read_cached_object(&dir, "library"));
+
+    write_sync(&dir, "library", Vec::new()).unwrap();
+    assert_cache_miss(read_cached_object(&dir, "library"));
+}
+
+#[test]
+fn sign_out_clears_structural_cache() {
+    let dir = test_dir("structural-cache");
+    let entry = bitcode::encode(&vec!["cached".to_owned()]);
+    for key in ["library", "collections", "user", "some-game"] {
+        write_sync(&dir, key, entry.clone()).unwrap();
+    }
+
+    clear_structural_cache(&dir).unwrap();
+    for key in ["library", "collections", "user"] {
+        assert_cache_miss(read_cached_object(&dir, key));
+    }
+    // Game entries aren't tied to the account
+    assert!(read_cached_object::<Vec<String>>(&dir, "some-game").is_ok());
+
+    // Nothing left to clear is fine
+    clear_structural_cache(&dir).unwrap();
+}
+
+#[test]
+fn reads_max_age_from_cache_control() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(max_age(&headers), None);
+
+    headers.insert(
+        CACHE_CONTROL,
+        HeaderValue::from_static("public, Max-Age=604800, immutable"),
+    );
+    assert_eq!(max_age(&headers), Some(604800));
+
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    assert_eq!(max_age(&headers), None);
+}
+
+#[test]
+fn reads_retry_after_seconds_and_dates() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(retry_after(&headers), None);
+
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+    // A date in the past means retry straight away
+    headers.insert(
+        RETRY_AFTER,
+        HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+    );
+    assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+    assert_eq!(retry_after(&headers), None);
+}
+
+#[test]
+fn parses_and_checks_server_versions() {
+    assert_eq!(
+        ServerVersion::parse("0.3.3"),
+        Some(ServerVersion::new(0, 3, 3))
+    );
+    assert_eq!(
+        ServerVersion::parse("v0.3"),
+        Some(ServerVersion::new(0, 3, 0))
+    );
+    assert_eq!(
+        ServerVersion::parse("0.3.1-beta.2"),
+        Some(ServerVersion::new(0, 3, 1))
+    );
+    assert_eq!(ServerVersion::parse("latest"), None);
+    assert_eq!(ServerVersion::parse("1.2.3.4"), None);
+
+    assert!(ServerVersion::new(0, 3, 9).is_supported());
+    assert!(!ServerVersion::new(0, 2, 0).is_supported());
+    assert!(!ServerVersion::new(0, 4, 0).is_supported());
+}
+
+#[test]
+fn evicts_least_recently_used_unpinned_entries() {
+    let dir = test_dir("evict-cache");
+    let bytes = bitcode::encode(&vec!["art".to_owned(); 16]);
+    let now = SystemTime::now();
+    // Oldest first
+    for (i, key) in ["library", "old-art", "new-art"].iter().enumerate() {
+        write_sync(&dir, key, bytes.clone()).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(get_cache_path(&dir, key))
+            .unwrap()
+            .set_modified(now - Duration::from_secs(300 - i as u64 * 100))
+            .unwrap();
+    }
+    let size = bytes.len() as u64;
+    assert_eq!(cache_size(&dir).unwrap(), size * 3);
+
+    // "library" is the oldest, but pinned
+    let freed = evict_cache(&dir, size * 3 - 1, &["library".to_owned()]).unwrap();
+    assert_eq!(freed, size);
+    assert!(read_cached_object::<Vec<String>>(&dir, "library").is_ok());
+    assert!(read_cached_object::<Vec<String>>(&dir, "new-art").is_ok());
+    assert_cache_miss(read_cached_object::<Vec<String>>(&dir, "old-art"));
+}
+
+#[test]
+fn prefetch_fetches_each_object_once() {
+    let fetched = Arc::new(Mutex::new(Vec::new()));
+    let ids = ["banner", "cover", "banner", "missing", "cover"]
+        .map(str::to_owned)
+        .to_vec();
+
+    let results = tauri::async_runtime::block_on(prefetch_with(ids, |object_id| {
+        let fetched = fetched.clone();
+        async move {
+            let available = object_id != "missing";
+            fetched.lock().push(object_id);
+            available
+        }
+    }));
+
+    let mut fetched = fetched.lock().clone();
+    fetched.sort();
+    assert_eq!(fetched, ["banner", "cover", "missing"]);
+    assert_eq!(
+        results,
+        HashMap::from([
+            ("banner".to_owned(), true),
+            ("cover".to_owned(), true),
+            ("missing".to_owned(), false),
+        ])
+    );
+}
+
+/// A fetch that doesn't finish until `permits` has a permit for it
+async fn gated_fetch(permits: Arc<Semaphore>) -> Result<Response<Vec<u8>>, CacheError> {
+    permits.acquire().await.unwrap().forget();
+    Ok(Response::new(b"art".to_vec()))
+}
+
+#[test]
+fn cancelling_a_fetch_leaves_other_requests_for_the_object() {
+    let permits = Arc::new(Semaphore::new(0));
+    let fetch = |owner: Option<&str>| {
+        run_cancellable_fetch(
+            "shared-art".to_owned(),
+            owner.map(str::to_owned),
+            gated_fetch(permits.clone()),
+        )
+    };
+    let left_page = fetch(Some("left-page"));
+    let other_page = fetch(Some("other-page"));
+    let unowned = fetch(None);
+
+    assert!(cancel_object_fetch("shared-art", "left-page"));
+    // Nothing else of its own to cancel
+    assert!(!cancel_object_fetch("shared-art", "left-page"));
+    assert!(!cancel_object_fetch("shared-art", "unknown-page"));
+    assert!(!cancel_object_fetch("other-art", "other-page"));
+
+    tauri::async_runtime::block_on(async {
+        assert!(matches!(
+            left_page.await,
+            Err(CacheError::Remote(RemoteAccessError::Cancelled))
+        ));
+        permits.add_permits(2);
+        assert_eq!(other_page.await.unwrap().body(), b"art");
+        assert_eq!(unowned.await.unwrap().body(), b"art");
+    });
+
+    // Finished requests can't be cancelled
+    assert!(!cancel_object_fetch("shared-art", "other-page"));
+}
+
+#[test]
+fn cancelling_a_fetch_cancels_all_of_its_owners_requests() {
+    let permits = Arc::new(Semaphore::new(0));
+    let fetch = || {
+        run_cancellable_fetch(
+            "repeated-art".to_owned(),
+            Some("page".to_owned()),
+            gated_fetch(permits.clone()),
+        )
+    };
+    let first = fetch();
+    let second = fetch();
+
+    assert!(cancel_object_fetch("repeated-art", "page"));
+    tauri::async_runtime::block_on(async {
+        for request in [first, second] {
+            assert!(matches!(
+                request.await,
+                Err(CacheError::Remote(RemoteAccessError::Cancelled))
+            ));
+        }
+    });
+}
+
+/// A self-signed certificate for `drop-test`
+const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----\n\
+MIIBfzCCASWgAwIBAgIUYBk+XmU1k6oLWpK8I/uGiHUmwhgwCgYIKoZIzj0EAwIw\n\
+FDESMBAGA1UEAwwJZHJvcC10ZXN0MCAXDTI2MTAxNjExMzI1OFoYDzIxMjYwOTIy\n\
+MTEzMjU4WjAUMRIwEAYDVQQDDAlkcm9wLXRlc3QwWTATBgcqhkjOPQIBBggqhkjO\n\
+PQMBBwNCAARhf73htgpb2AE1nIlkrgwlWHx4akaE+x8lq98NlGlAhjDWZziSnncu\n\
+lsujyPVuAtH6APTuM/HPY394/vsB0HT8o1MwUTAdBgNVHQ4EFgQUbdhIXtFhq/Mn\n\
+I7GmYm7DLK714WswHwYDVR0jBBgwFoAUbdhIXtFhq/MnI7GmYm7DLK714WswDwYD\n\
+VR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiEA5WEjyKsmpwt5LZgqEIic\n\
+lFj6coA15fmZmaPNJyT1CBMCID/w6AxqylvFvEc2dImeMVBNORnMwKv2EEaxv2lB\n\
+E7Su\n\
+-----END CERTIFICATE-----\n\
+";
+
+#[test]
+fn certificates_round_trip() {
+    let dir = test_dir("certificates");
+    assert!(list_certificates(&dir).unwrap().certificates.is_empty());
+
+    let added = add_certificate(&dir, CERTIFICATE.as_bytes()).unwrap();
+    assert_eq!(added.certificates, 1);
+    assert!(!added.loaded);
+    // Adding it again doesn't make a second copy
+    assert_eq!(
+        add_certificate(&dir, CERTIFICATE.as_bytes()).unwrap().name,
+        added.name
+    );
+    assert!(matches!(
+        add_certificate(&dir, b"not a certificate"),
+        Err(RemoteAccessError::InvalidCertificate(_))
+    ));
+
+    let list = list_certificates(&dir).unwrap();
+    assert_eq!(list.certificates.len(), 1);
+    assert_eq!(list.certificates[0].name, added.name);
+    assert_eq!(list.certificates[0].certificates, 1);
+
+    // Only listed names can be removed
+    fs::write(dir.with_file_name("drop-test-outside.pem"), CERTIFICATE).unwrap();
+    assert!(matches!(
+        remove_certificate(&dir, "../drop-test-outside.pem"),
+        Err(RemoteAccessError::CertificateNotFound(_))
+    ));
+    fs::remove_file(dir.with_file_name("drop-test-outside.pem")).unwrap();
+
+    remove_certificate(&dir, &added.name).unwrap();
+    assert!(list_certificates(&dir).unwrap().certificates.is_empty());
+    assert!(matches!(
+        remove_certificate(&dir, &added.name),
+        Err(RemoteAccessError::CertificateNotFound(_))
+    ));
+}
@@ -1,33 +1,171 @@
 use std::{
+    collections::HashSet,
+    fmt::Display,
     fs::{self, File},
     io::Read,
-    sync::LazyLock,
+    sync::{LazyLock, OnceLock},
+    time::Duration,
 };
 
-use database::db::DATA_ROOT_DIR;
+use database::borrow_db_checked;
 use log::{debug, info, warn};
-use reqwest::Certificate;
+use reqwest::{Certificate, NoProxy, Proxy};
 use serde::Deserialize;
 
+use crate::{
+    certificates::{certificates_dir, mark_loaded},
+    requests::generate_extra_headers,
+};
+
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DropHealthcheck {
     app_name: String,
+    // Older servers don't report this
+    #[serde(default)]
+    version: Option<String>,
 }
 impl DropHealthcheck {
     pub fn app_name(&self) -> &String {
         &self.app_name
     }
+    pub fn version(&self) -> Option<&String> {
+        self.version.as_ref()
+    }
+}
+
+/// Oldest server version this client supports
+pub const MIN_SERVER_VERSION: ServerVersion = ServerVersion::new(0, 3, 0);
+/// First server version this client doesn't support any more
+pub const MAX_SERVER_VERSION: ServerVersion = ServerVersion::new(0, 4, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServerVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+impl ServerVersion {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses `1.2.3`, `v1.2` or `1.2.3-beta.1`. Pre-release and build
+    /// suffixes are ignored.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split(['-', '+']).next()?;
+        let mut parts = version.split('.').map(str::parse::<u64>);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+
+    pub fn is_supported(&self) -> bool {
+        (MIN_SERVER_VERSION..MAX_SERVER_VERSION).contains(self)
+    }
+}
+impl Display for ServerVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Whether the server answers its healthcheck as Drop. Cheap enough to call
+/// regularly, it doesn't need auth.
+pub async fn server_reachable() -> bool {
+    let base_url = borrow_db_checked().base_url.clone();
+    let Ok(endpoint) = url::Url::parse(&base_url).and_then(|url| url.join("/api/v1")) else {
+        return false;
+    };
+    let response = DROP_CLIENT_ASYNC
+        .get(endpoint)
+        .headers(generate_extra_headers())
+        .timeout(HEALTHCHECK_TIMEOUT)
+        .send()
+        .await;
+    match response {
+        Ok(response) => match response.json::<DropHealthcheck>().await {
+            Ok(healthcheck) => healthcheck.app_name() == "Drop",
+            Err(e) => {
+                debug!("healthcheck returned something other than Drop: {e}");
+                false
+            }
+        },
+        Err(e) => {
+            debug!("healthcheck failed: {e}");
+            false
+        }
+    }
+}
+
+/// The version of the server we're connected to, if it told us
+pub fn server_version() -> Option<ServerVersion> {
+    ServerVersion::parse(borrow_db_checked().server_version.as_ref()?)
 }
 static DROP_CERT_BUNDLE: LazyLock<Vec<Certificate>> = LazyLock::new(fetch_certificates);
-pub static DROP_CLIENT_SYNC: LazyLock<reqwest::blocking::Client> = LazyLock::new(get_client_sync);
-pub static DROP_CLIENT_ASYNC: LazyLock<reqwest::Client> = LazyLock::new(get_client_async);
-pub static DROP_CLIENT_WS_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(get_client_ws);
+/// Set once by [`init_clients`], so building a client never has to borrow
+/// the database
+static DROP_PROXY: OnceLock<Option<Proxy>> = OnceLock::new();
+// The clients are built once, so proxy settings take effect after a restart
+pub static DROP_CLIENT_SYNC: LazyLock<reqwest::blocking::Client> =
+    LazyLock::new(|| get_client_sync(configured_proxy()));
+pub static DROP_CLIENT_ASYNC: LazyLock<reqwest::Client> =
+    LazyLock::new(|| get_client_async(configured_proxy()));
+pub static DROP_CLIENT_WS_CLIENT: LazyLock<reqwest::Client> =
+    LazyLock::new(|| get_client_ws(configured_proxy()));
+
+/// Builds the clients with the proxy from settings. Called once at startup,
+/// before anything makes a request.
+pub fn init_clients(proxy_url: Option<String>, no_proxy: &[String]) {
+    if DROP_PROXY.set(drop_proxy(proxy_url, no_proxy)).is_err() {
+        warn!("clients were already set up, ignoring the proxy settings");
+        return;
+    }
+    LazyLock::force(&DROP_CLIENT_SYNC);
+    LazyLock::force(&DROP_CLIENT_ASYNC);
+    LazyLock::force(&DROP_CLIENT_WS_CLIENT);
+}
+
+fn configured_proxy() -> Option<Proxy> {
+    DROP_PROXY.get_or_init(|| None).clone()
+}
+
+/// The proxy from settings, if there is one. Without one, reqwest uses
+/// `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY` and `NO_PROXY` from the environment.
+fn drop_proxy(url: Option<String>, no_proxy: &[String]) -> Option<Proxy> {
+    let url = url.filter(|url| !url.trim().is_empty())?;
+    let proxy = match Proxy::all(url.trim()) {
+        Ok(proxy) => proxy,
+        Err(e) => {
+            warn!("ignoring invalid proxy {url}: {e}");
+            return None;
+        }
+    };
+    let no_proxy = if no_proxy.is_empty() {
+        NoProxy::from_env()
+    } else {
+        NoProxy::from_string(&no_proxy.join(","))
+    };
+    info!("using proxy {url}");
+    Some(proxy.no_proxy(no_proxy))
+}
 
 fn fetch_certificates() -> Vec<Certificate> {
-    let certificate_dir = DATA_ROOT_DIR.join("certificates");
+    let certificate_dir = certificates_dir();
 
     let mut certs = Vec::new();
+    let mut loaded = HashSet::new();
     match fs::read_dir(certificate_dir) {
         Ok(c) => {
             for entry in c {
@@ -59,6 +197,7 @@ fn fetch_certificates() -> Vec<Certificate> {
                                 for cert in certificates {
                                     certs.push(cert);
                                 }
+                                loaded.insert(c.file_name().to_string_lossy().to_string());
                                 info!(
                                     "added {} certificate(s) from {}",
                                     certs.len(),
@@ -72,7 +211,7 @@ fn fetch_certificates() -> Vec<Certificate> {
                             ),
                         }
                     }
-                    Err(_) => todo!(),
+                    Err(e) => warn!("skipping unreadable certificate entry: {e}"),
                 }
             }
         }
@@ -80,33 +219,47 @@ fn fetch_certificates() -> Vec<Certificate> {
             debug!("not loading certificates due to error: {e}");
         }
     };
+    mark_loaded(loaded);
     certs
 }
 
-pub fn get_client_sync() -> reqwest::blocking::Client {
+pub fn get_client_sync(proxy: Option<Proxy>) -> reqwest::blocking::Client {
     let mut client = reqwest::blocking::ClientBuilder::new();
+    if let Some(proxy) = proxy {
+        client = client.proxy(proxy);
+    }
 
     for cert in DROP_CERT_BUNDLE.iter() {
         client = client.add_root_certificate(cert.clone());
     }
     client
         .use_rustls_tls()
+        .gzip(true)
+        .deflate(true)
         .build()
         .expect("Failed to build synchronous client")
 }
-pub fn get_client_async() -> reqwest::Client {
+pub fn get_client_async(proxy: Option<Proxy>) -> reqwest::Client {
     let mut client = reqwest::ClientBuilder::new();
+    if let Some(proxy) = proxy {
+        client = client.proxy(proxy);
+    }
 
     for cert in DROP_CERT_BUNDLE.iter() {
         client = client.add_root_certificate(cert.clone());
     }
     client
         .use_rustls_tls()
+        .gzip(true)
+        .deflate(true)
         .build()
         .expect("Failed to build asynchronous client")
 }
-pub fn get_client_ws() -> reqwest::Client {
+pub fn get_client_ws(proxy: Option<Proxy>) -> reqwest::Client {
     let mut client = reqwest::ClientBuilder::new();
+    if let Some(proxy) = proxy {
+        client = client.proxy(proxy);
+    }
 
     for cert in DROP_CERT_BUNDLE.iter() {
         client = client.add_root_certificate(cert.clone());
@@ -1,12 +1,18 @@
-use std::sync::nonpoison::Mutex;
+use std::{
+    fs::{read_dir, remove_file},
+    path::PathBuf,
+    sync::nonpoison::Mutex,
+};
 
-use database::{borrow_db_checked, borrow_db_mut_checked};
+use database::{borrow_db_checked, borrow_db_mut_checked, db::DATA_ROOT_DIR};
 use download_manager::DOWNLOAD_MANAGER;
 use log::{debug, error};
+use serde::Serialize;
 use tauri::AppHandle;
 use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_opener::OpenerExt;
 
-use crate::AppState;
+use crate::{AppState, process::kill_all_games};
 
 #[tauri::command]
 pub fn fetch_state(state: tauri::State<'_, Mutex<AppState>>) -> Result<String, String> {
@@ -23,6 +29,7 @@ pub fn quit(app: tauri::AppHandle) {
 
 pub fn cleanup_and_exit(app: &AppHandle) {
     debug!("cleaning up and exiting application");
+    kill_all_games();
     match DOWNLOAD_MANAGER.ensure_terminated() {
         Ok(res) => match res {
             Ok(()) => debug!("download manager terminated correctly"),
@@ -72,3 +79,55 @@ pub fn get_autostart_enabled(app: AppHandle) -> Result<bool, tauri_plugin_autost
 
     Ok(db_state)
 }
+
+#[derive(Serialize)]
+pub struct CrashReport {
+    name: String,
+    timestamp: u64,
+}
+
+/// Parses the timestamp out of a `crash-<timestamp>.log` name, as written by
+/// `custom_panic_handler`
+fn crash_report_timestamp(name: &str) -> Option<u64> {
+    name.strip_prefix("crash-")?
+        .strip_suffix(".log")?
+        .parse()
+        .ok()
+}
+
+fn crash_report_path(name: &str) -> Result<PathBuf, String> {
+    // Stops the frontend from pointing these commands at anything else
+    if crash_report_timestamp(name).is_none() {
+        return Err(format!("{name} is not a crash report"));
+    }
+    Ok(DATA_ROOT_DIR.join(name))
+}
+
+#[tauri::command]
+pub fn fetch_crash_reports() -> Result<Vec<CrashReport>, String> {
+    let mut reports = read_dir(&*DATA_ROOT_DIR)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let timestamp = crash_report_timestamp(&name)?;
+            Some(CrashReport { name, timestamp })
+        })
+        .collect::<Vec<CrashReport>>();
+    reports.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    Ok(reports)
+}
+
+#[tauri::command]
+pub fn open_crash_report(name: String, app: AppHandle) -> Result<(), String> {
+    let path = crash_report_path(&name)?;
+    app.opener()
+        .open_path(path.display().to_string(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_crash_report(name: String) -> Result<(), String> {
+    let path = crash_report_path(&name)?;
+    remove_file(path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,118 @@
+use ::cloud_saves::{
+    error::BackupError,
+    metadata::{ConflictResolution, SavePathPreview, SnapshotInfo, UnmappedSave},
+    progress::SaveSyncProgress,
+    resolver::preview,
+    snapshots::{
+        create_snapshot, effective_config, list_snapshots, resolve_conflict, restore_snapshot,
+    },
+};
+use database::{CloudSaveConfig, GameVersion, borrow_db_checked, borrow_db_mut_checked};
+use tauri::AppHandle;
+
+#[tauri::command]
+pub fn fetch_cloud_save_config(game_id: String) -> CloudSaveConfig {
+    borrow_db_checked()
+        .applications
+        .game_cloud_saves
+        .get(&game_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn update_cloud_save_config(game_id: String, config: CloudSaveConfig) {
+    let mut db_lock = borrow_db_mut_checked();
+    if config == CloudSaveConfig::default() {
+        db_lock.applications.game_cloud_saves.remove(&game_id);
+    } else {
+        db_lock
+            .applications
+            .game_cloud_saves
+            .insert(game_id, config);
+    }
+}
+
+/// The installed version of a game and its save config, with the global
+/// excludes added
+fn save_target(game_id: &str) -> Result<(GameVersion, CloudSaveConfig), BackupError> {
+    let db_lock = borrow_db_checked();
+    let game_version = db_lock
+        .applications
+        .installed_game_version
+        .get(game_id)
+        .and_then(|meta| {
+            db_lock
+                .applications
+                .game_versions
+                .get(game_id)?
+                .get(meta.version.as_ref()?)
+        })
+        .ok_or_else(|| BackupError::NotInstalled(game_id.to_string()))?;
+    let config = effective_config(&db_lock, game_id)
+        .filter(|config| !config.paths.is_empty())
+        .ok_or_else(|| BackupError::NoSavePaths(game_id.to_string()))?;
+    Ok((game_version.clone(), config))
+}
+
+#[tauri::command]
+pub async fn resolve_save_conflict(
+    game_id: String,
+    resolution: ConflictResolution,
+    app_handle: AppHandle,
+) -> Result<(), BackupError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (game_version, config) = save_target(&game_id)?;
+        let progress = SaveSyncProgress::new(app_handle, game_id);
+        resolve_conflict(&game_version, &config, resolution, &progress)
+    })
+    .await
+    .map_err(|e| BackupError::IOError(e.to_string()))?
+}
+
+/// Backs up a game's saves now, outside of the automatic sync
+#[tauri::command]
+pub async fn backup_game_saves(
+    game_id: String,
+    app_handle: AppHandle,
+) -> Result<SnapshotInfo, BackupError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (game_version, config) = save_target(&game_id)?;
+        let progress = SaveSyncProgress::new(app_handle, game_id);
+        create_snapshot(&game_version, &config, &progress)
+    })
+    .await
+    .map_err(|e| BackupError::IOError(e.to_string()))?
+}
+
+/// Rolls a game's saves back to one of its backups, overwriting the local
+/// ones. Saves from another OS are mapped to where they go here, and the ones
+/// that can't be are returned.
+#[tauri::command]
+pub async fn restore_game_saves(
+    game_id: String,
+    snapshot_id: String,
+    app_handle: AppHandle,
+) -> Result<Vec<UnmappedSave>, BackupError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (game_version, config) = save_target(&game_id)?;
+        let progress = SaveSyncProgress::new(app_handle, game_id);
+        restore_snapshot(&game_version, &config, &snapshot_id, &progress)
+    })
+    .await
+    .map_err(|e| BackupError::IOError(e.to_string()))?
+}
+
+/// A game's save backups, newest first
+#[tauri::command]
+pub fn list_save_snapshots(game_id: String) -> Result<Vec<SnapshotInfo>, BackupError> {
+    list_snapshots(&game_id)
+}
+
+/// Where each of a game's save paths resolves to on this machine, to check
+/// them before a backup runs
+#[tauri::command]
+pub fn preview_save_paths(game_id: String) -> Result<Vec<SavePathPreview>, BackupError> {
+    let (game_version, config) = save_target(&game_id)?;
+    Ok(preview(&game_version, &config.paths))
+}
@@ -1,9 +1,8 @@
 use games::collections::collection::{Collection, Collections};
 use remote::{
-    auth::generate_authorization_header,
     cache::{cache_object, get_cached_object},
     error::RemoteAccessError,
-    requests::{generate_url, make_authenticated_get},
+    requests::{generate_authenticated_headers, generate_url, make_authenticated_get},
     utils::DROP_CLIENT_ASYNC,
 };
 use serde_json::json;
@@ -46,7 +45,7 @@ pub async fn create_collection(name: String) -> Result<Collection, RemoteAccessE
 
     let response = client
         .post(url)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .json(&json!({"name": name}))
         .send()
         .await?;
@@ -65,7 +64,7 @@ pub async fn add_game_to_collection(
 
     client
         .post(url)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .json(&json!({"id": game_id}))
         .send()
         .await?;
@@ -80,7 +79,7 @@ pub async fn delete_collection(collection_id: String) -> Result<bool, RemoteAcce
 
     let response = client
         .delete(url)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .send()
         .await?;
 
@@ -97,7 +96,7 @@ pub async fn delete_game_in_collection(
 
     client
         .delete(url)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .json(&json!({"id": game_id}))
         .send()
         .await?;
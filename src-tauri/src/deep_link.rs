@@ -0,0 +1,176 @@
+use std::sync::{atomic::Ordering, nonpoison::Mutex};
+
+use ::download_manager::DOWNLOAD_MANAGER;
+use ::process::{error::ProcessError, process_manager::LaunchErrorEvent};
+use client::app_status::AppStatus;
+use database::{GameDownloadStatus, borrow_db_checked};
+use log::{info, warn};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use url::Url;
+use utils::app_emit;
+
+use crate::{
+    AppState, PAUSED_BY_TRAY, downloads::download_game, games::default_version,
+    process::launch_game,
+};
+
+/// An install link that came in before the user signed in, replayed once
+/// they have
+static PENDING_INSTALL: Mutex<Option<InstallRequest>> = Mutex::new(None);
+
+#[derive(Clone, Debug)]
+struct InstallRequest {
+    game_id: String,
+    version: Option<String>,
+}
+
+/// Tells the frontend to show a game's page, and its install options if
+/// `install` is set
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OpenGameEvent {
+    game_id: String,
+    install: bool,
+}
+
+fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+    if PAUSED_BY_TRAY.swap(false, Ordering::AcqRel) {
+        DOWNLOAD_MANAGER.resume_downloads();
+    }
+}
+
+fn open_game(app: &AppHandle, game_id: String, install: bool) {
+    app_emit!(app, "open_game", OpenGameEvent { game_id, install });
+}
+
+/// Handles `drop://launch/<game_id>`, e.g. from a desktop shortcut
+pub fn launch(app: &AppHandle, path: &str) {
+    let game_id = path.trim_matches('/').to_string();
+    let installed = matches!(
+        borrow_db_checked().applications.game_statuses.get(&game_id),
+        Some(GameDownloadStatus::Installed { .. })
+    );
+
+    show_main_window(app);
+
+    if !installed {
+        warn!("not launching {game_id} from deep link, it isn't installed");
+        app_emit!(
+            app,
+            "launch_error",
+            LaunchErrorEvent::new(game_id, ProcessError::NotInstalled.to_string())
+        );
+        return;
+    }
+    info!("launching {game_id} from deep link");
+    if let Err(e) = launch_game(game_id.clone(), app.state::<Mutex<AppState>>(), app.clone()) {
+        warn!("could not launch {game_id} from deep link: {e}");
+        app_emit!(
+            app,
+            "launch_error",
+            LaunchErrorEvent::new(game_id, e.to_string())
+        );
+    }
+}
+
+/// Handles `drop://install/<game_id>?version=<version>`, e.g. from the store
+/// page on the web. Without a version, the default one is picked.
+pub fn install(app: &AppHandle, url: &Url) {
+    let request = InstallRequest {
+        game_id: url.path().trim_matches('/').to_string(),
+        version: url
+            .query_pairs()
+            .find(|(key, _)| key == "version")
+            .map(|(_, value)| value.into_owned()),
+    };
+    if request.game_id.is_empty() {
+        warn!("install deep link is missing a game id");
+        return;
+    }
+
+    show_main_window(app);
+
+    let signed_in = app.state::<Mutex<AppState>>().lock().status == AppStatus::SignedIn;
+    if !signed_in {
+        info!(
+            "holding on to install link for {} until signed in",
+            request.game_id
+        );
+        *PENDING_INSTALL.lock() = Some(request);
+        return;
+    }
+    tauri::async_runtime::spawn(install_game(app.clone(), request));
+}
+
+/// Picks up an install link that came in while signed out
+pub fn replay_pending_install(app: &AppHandle) {
+    if let Some(request) = PENDING_INSTALL.lock().take() {
+        info!("replaying install link for {}", request.game_id);
+        tauri::async_runtime::spawn(install_game(app.clone(), request));
+    }
+}
+
+async fn install_game(app: AppHandle, request: InstallRequest) {
+    let InstallRequest { game_id, version } = request;
+
+    let (status, has_install_dirs) = {
+        let db_lock = borrow_db_checked();
+        (
+            db_lock.applications.game_statuses.get(&game_id).cloned(),
+            !db_lock.applications.install_dirs.is_empty(),
+        )
+    };
+    if !matches!(status, None | Some(GameDownloadStatus::Remote {})) {
+        info!("{game_id} is already installed, showing it instead");
+        open_game(&app, game_id, false);
+        return;
+    }
+    // Let the user pick where it goes
+    if !has_install_dirs {
+        open_game(&app, game_id, true);
+        return;
+    }
+
+    let version = match version {
+        Some(version) => version,
+        None => match default_version(game_id.clone(), app.state()).await {
+            Ok(Some(version)) => version.version_name,
+            Ok(None) => {
+                warn!("{game_id} has no versions to install");
+                open_game(&app, game_id, true);
+                return;
+            }
+            Err(e) => {
+                warn!("could not pick a version of {game_id} to install: {e}");
+                app_emit!(&app, "download_error", e.to_string());
+                return;
+            }
+        },
+    };
+
+    info!("installing {game_id} {version} from deep link");
+    let result = download_game(
+        game_id.clone(),
+        version,
+        None,
+        None,
+        None,
+        None,
+        app.clone(),
+        app.state(),
+    )
+    .await;
+    match result {
+        Ok(()) => open_game(&app, game_id, false),
+        Err(e) => {
+            warn!("could not install {game_id} from deep link: {e}");
+            app_emit!(&app, "download_error", e.to_string());
+        }
+    }
+}
@@ -1,5 +1,10 @@
 use database::DownloadableMetadata;
-use download_manager::DOWNLOAD_MANAGER;
+use download_manager::{
+    DOWNLOAD_MANAGER,
+    debug_state::DownloadManagerDebugState,
+    error::DownloadManagerError,
+    util::bandwidth::{self, QuotaUsage},
+};
 
 #[tauri::command]
 pub fn pause_downloads() {
@@ -11,12 +16,52 @@ pub fn resume_downloads() {
     DOWNLOAD_MANAGER.resume_downloads();
 }
 
+/// Pauses one download, leaving the rest of the queue running
 #[tauri::command]
-pub fn move_download_in_queue(old_index: usize, new_index: usize) {
-    DOWNLOAD_MANAGER.rearrange(old_index, new_index);
+pub fn pause_download(meta: DownloadableMetadata) {
+    DOWNLOAD_MANAGER.pause_download(meta);
+}
+
+#[tauri::command]
+pub fn resume_download_item(meta: DownloadableMetadata) {
+    DOWNLOAD_MANAGER.resume_download(meta);
+}
+
+#[tauri::command]
+pub fn move_download_in_queue(
+    old_index: usize,
+    new_index: usize,
+) -> Result<(), DownloadManagerError<()>> {
+    DOWNLOAD_MANAGER.rearrange(old_index, new_index)
 }
 
 #[tauri::command]
 pub fn cancel_game(meta: DownloadableMetadata) {
     DOWNLOAD_MANAGER.cancel(meta);
 }
+
+/// Cancels every download waiting in the queue, but lets the active ones finish
+#[tauri::command]
+pub async fn clear_queued_downloads() -> Vec<DownloadableMetadata> {
+    // Waits on the download manager, which can be busy stopping a download
+    tauri::async_runtime::spawn_blocking(|| DOWNLOAD_MANAGER.cancel_queued())
+        .await
+        .unwrap_or_default()
+}
+
+/// How much of the monthly download quota has been used
+#[tauri::command]
+pub fn fetch_quota_usage() -> QuotaUsage {
+    bandwidth::usage()
+}
+
+/// Snapshot of the download manager's internals, for debugging
+#[tauri::command]
+pub fn dump_download_state() -> Result<DownloadManagerDebugState, String> {
+    if !cfg!(debug_assertions) {
+        return Err("Download state is only available in debug builds".to_owned());
+    }
+    DOWNLOAD_MANAGER
+        .dump_state()
+        .ok_or("Download manager did not respond".to_owned())
+}
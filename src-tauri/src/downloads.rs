@@ -1,19 +1,113 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{Arc, nonpoison::Mutex},
+};
 
-use database::{GameDownloadStatus, borrow_db_checked};
+use database::{
+    DownloadHistoryEntry, GameDownloadStatus, GameVersion, borrow_db_checked,
+    borrow_db_mut_checked,
+    download_history::{MonthlyDownloadUsage, monthly_rollup},
+};
 use download_manager::{
     DOWNLOAD_MANAGER, downloadable::Downloadable, error::ApplicationDownloadError,
 };
-use games::downloads::download_agent::GameDownloadAgent;
+use games::downloads::{
+    download_agent::{DownloadPhase, GameDownloadAgent, push_download_phase},
+    import::import_offline_game as import_offline_game_logic,
+    summary::{DownloadSummary, get_last_download_summary},
+    update::{GameComponent, UpdateSize, get_game_components, get_update_size},
+    utils::{
+        DownloadFeasibility, assign_install_folder_name,
+        check_download_feasible as check_download_feasible_logic, check_install_quota,
+        get_required_space, pick_install_dir, set_excluded_components,
+    },
+};
+use log::warn;
+use remote::error::RemoteAccessError;
+use tauri::AppHandle;
+
+use crate::{
+    AppState,
+    games::{fetch_game_logic, fetch_game_version_options_logic},
+    settings::register_download_dir,
+};
 
 #[tauri::command]
 pub async fn download_game(
     game_id: String,
     game_version: String,
-    install_dir: usize,
+    install_dir: Option<usize>,
+    // An absolute path to install to instead, added to the install dirs if
+    // it isn't one already
+    install_path: Option<PathBuf>,
+    install_folder_name: Option<String>,
+    excluded_components: Option<Vec<String>>,
+    app_handle: AppHandle,
+    state: tauri::State<'_, Mutex<AppState>>,
 ) -> Result<(), ApplicationDownloadError> {
+    push_download_phase(&app_handle, &game_id, DownloadPhase::FetchingManifest);
+
+    // Hidden games are un-hidden on install, so they don't disappear once
+    // they're no longer just on the server
+    if borrow_db_checked()
+        .applications
+        .hidden_games
+        .contains(&game_id)
+    {
+        borrow_db_mut_checked()
+            .applications
+            .hidden_games
+            .remove(&game_id);
+    }
+
+    // Games opened from a direct link might not be in the library yet
+    let is_known = state.lock().games.contains_key(&game_id);
+    if !is_known {
+        fetch_game_logic(game_id.clone(), state.clone())
+            .await
+            .map_err(ApplicationDownloadError::Communication)?;
+        let versions = fetch_game_version_options_logic(game_id.clone(), state)
+            .await
+            .map_err(ApplicationDownloadError::Communication)?;
+        if !versions.iter().any(|v| v.version_name == game_version) {
+            return Err(ApplicationDownloadError::Communication(
+                RemoteAccessError::GameNotFound(game_id),
+            ));
+        }
+    }
+
     let sender = { DOWNLOAD_MANAGER.get_sender().clone() };
 
+    let install_dir = match install_path {
+        Some(install_path) => Some(register_download_dir(install_path)?),
+        None => install_dir,
+    };
+    let install_dir = match install_dir {
+        Some(install_dir) => {
+            let dir = borrow_db_checked()
+                .applications
+                .install_dirs
+                .get(install_dir)
+                .cloned();
+            if let Some(dir) = dir {
+                let required_space = get_required_space(&game_id, &game_version).await?;
+                check_install_quota(&game_id, &dir, required_space)?;
+            }
+            install_dir
+        }
+        None => pick_install_dir(&game_id, &game_version).await?,
+    };
+
+    let base_dir = borrow_db_checked()
+        .applications
+        .install_dirs
+        .get(install_dir)
+        .cloned();
+    if let Some(base_dir) = base_dir {
+        assign_install_folder_name(&game_id, &base_dir, install_folder_name.as_deref());
+    }
+    set_excluded_components(&game_id, excluded_components);
+
     let game_download_agent = GameDownloadAgent::new_from_index(
         game_id.clone(),
         game_version.clone(),
@@ -33,7 +127,10 @@ pub async fn download_game(
 }
 
 #[tauri::command]
-pub async fn resume_download(game_id: String) -> Result<(), ApplicationDownloadError> {
+pub async fn resume_download(
+    game_id: String,
+    app_handle: AppHandle,
+) -> Result<(), ApplicationDownloadError> {
     let s = borrow_db_checked()
         .applications
         .game_statuses
@@ -54,6 +151,8 @@ pub async fn resume_download(game_id: String) -> Result<(), ApplicationDownloadE
     let sender = DOWNLOAD_MANAGER.get_sender();
     let parent_dir: PathBuf = install_dir.into();
 
+    push_download_phase(&app_handle, &game_id, DownloadPhase::FetchingManifest);
+
     let game_download_agent = Arc::new(Box::new(
         GameDownloadAgent::new(
             game_id,
@@ -74,3 +173,172 @@ pub async fn resume_download(game_id: String) -> Result<(), ApplicationDownloadE
         .unwrap();
     Ok(())
 }
+
+/// Queues the downloads that were waiting when Drop last closed, in their
+/// saved order. They stay paused until the user resumes them. Ones that
+/// can't be rebuilt yet, e.g. while offline, stay saved and are tried again
+/// the next time this runs.
+pub async fn restore_download_queue() {
+    let saved_queue = borrow_db_checked().applications.download_queue.clone();
+    let sender = DOWNLOAD_MANAGER.get_sender();
+    let already_queued = DOWNLOAD_MANAGER.read_queue();
+
+    for queued in saved_queue {
+        if already_queued.contains(&queued.meta) {
+            continue;
+        }
+        let Some(version) = queued.meta.version.clone() else {
+            continue;
+        };
+        let game_download_agent = match GameDownloadAgent::new(
+            queued.meta.id.clone(),
+            version,
+            queued.install_dir.clone(),
+            sender.clone(),
+        )
+        .await
+        {
+            Ok(game_download_agent) => game_download_agent,
+            Err(e) => {
+                warn!(
+                    "couldn't restore queued download for {}, keeping it for later: {e}",
+                    queued.meta.id
+                );
+                DOWNLOAD_MANAGER.keep_unrestored(queued);
+                continue;
+            }
+        };
+
+        DOWNLOAD_MANAGER
+            .queue_download_paused(Arc::new(
+                Box::new(game_download_agent) as Box<dyn Downloadable + Send + Sync>
+            ))
+            .unwrap();
+    }
+}
+
+/// Checks an installed game's files through the download queue, so it shows
+/// progress like a download. If any are corrupted the game is left partially
+/// installed, and resuming it downloads just those.
+#[tauri::command]
+pub async fn verify_game(game_id: String) -> Result<(), ApplicationDownloadError> {
+    let status = borrow_db_checked()
+        .applications
+        .game_statuses
+        .get(&game_id)
+        .cloned();
+    let (version_name, install_dir) = match status {
+        Some(
+            GameDownloadStatus::Installed {
+                version_name,
+                install_dir,
+            }
+            | GameDownloadStatus::SetupRequired {
+                version_name,
+                install_dir,
+            },
+        ) => (version_name, install_dir),
+        _ => return Err(ApplicationDownloadError::NotInstalled(game_id)),
+    };
+
+    let install_dir: PathBuf = install_dir.into();
+    let base_dir = install_dir
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| install_dir.clone());
+
+    let game_download_agent = GameDownloadAgent::new_for_verify(
+        game_id,
+        version_name,
+        base_dir,
+        DOWNLOAD_MANAGER.get_sender(),
+    )
+    .await?;
+
+    DOWNLOAD_MANAGER
+        .queue_download(Arc::new(
+            Box::new(game_download_agent) as Box<dyn Downloadable + Send + Sync>
+        ))
+        .unwrap();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn fetch_last_download_summary(game_id: String) -> Option<DownloadSummary> {
+    get_last_download_summary(&game_id)
+}
+
+/// The last `limit` finished downloads, newest first
+#[tauri::command]
+pub fn fetch_download_history(limit: usize) -> Vec<DownloadHistoryEntry> {
+    borrow_db_checked()
+        .download_history
+        .iter()
+        .rev()
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+#[tauri::command]
+pub fn fetch_monthly_download_usage() -> Vec<MonthlyDownloadUsage> {
+    monthly_rollup(&borrow_db_checked().download_history)
+}
+
+#[tauri::command]
+pub async fn fetch_update_size(
+    game_id: String,
+    version: String,
+) -> Result<UpdateSize, ApplicationDownloadError> {
+    get_update_size(game_id, version).await
+}
+
+/// Whether a game fits in an install dir, for checking before the user
+/// confirms the download
+#[tauri::command]
+pub async fn check_download_feasible(
+    game_id: String,
+    version: String,
+    install_dir: usize,
+    excluded_components: Option<Vec<String>>,
+) -> Result<DownloadFeasibility, ApplicationDownloadError> {
+    check_download_feasible_logic(game_id, version, install_dir, excluded_components).await
+}
+
+/// Optional parts of a game that can be left out of `download_game`. Ones
+/// left out can be added later by downloading the game again with them
+/// included, which only fetches the missing files.
+#[tauri::command]
+pub async fn fetch_game_components(
+    game_id: String,
+    version: String,
+) -> Result<Vec<GameComponent>, ApplicationDownloadError> {
+    get_game_components(game_id, version).await
+}
+
+/// Installs a game from files already on disk, for machines that can't reach
+/// the server to download it
+#[tauri::command]
+pub async fn import_offline_game(
+    game_id: String,
+    version: String,
+    dir: PathBuf,
+    manifest_path: PathBuf,
+    game_version: Option<GameVersion>,
+    app_handle: AppHandle,
+) -> Result<GameDownloadStatus, ApplicationDownloadError> {
+    // Hashing every file and the blocking version fetch can't run on the
+    // async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        import_offline_game_logic(
+            game_id,
+            version,
+            dir,
+            &manifest_path,
+            game_version,
+            &app_handle,
+        )
+    })
+    .await
+    .map_err(|e| ApplicationDownloadError::IoError(Arc::new(std::io::Error::other(e))))?
+}
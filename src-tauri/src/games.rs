@@ -1,36 +1,61 @@
-use std::sync::nonpoison::Mutex;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::nonpoison::Mutex,
+    time::SystemTime,
+};
 
-use database::{GameDownloadStatus, GameVersion, borrow_db_checked, borrow_db_mut_checked};
+use database::{
+    Database, GameDownloadStatus, GamePlaytime, GameVersion, LaunchConfig, borrow_db_checked,
+    borrow_db_mut_checked,
+};
 use games::{
-    downloads::error::LibraryError,
-    library::{FetchGameStruct, FrontendGameOptions, Game, get_current_meta, uninstall_game_logic},
+    downloads::{error::LibraryError, update::clear_manifest_cache},
+    executables::{
+        EXECUTABLE_SCAN_DEPTH, is_contained_relative_path,
+        list_executables as list_executables_logic,
+    },
+    install_size::{InstallSize, game_install_size},
+    library::{
+        FetchGameStruct, FrontendGameOptions, FrontendLaunchConfig, Game, RecentlyPlayedGame,
+        get_current_meta, push_library_update, uninstall_game_logic,
+    },
+    scan::{ScanSummary, rescan_install_dirs as rescan_install_dirs_logic},
+    shortcuts::{self, ShortcutError, save_icon},
     state::{GameStatusManager, GameStatusWithTransient},
+    versions::pick_default_version,
 };
 use log::warn;
 use process::PROCESS_MANAGER;
 use remote::{
-    auth::generate_authorization_header,
     cache::{cache_object, cache_object_db, get_cached_object, get_cached_object_db},
     error::{DropServerError, RemoteAccessError},
     offline,
-    requests::generate_url,
+    requests::{generate_authenticated_headers, generate_url},
     utils::DROP_CLIENT_ASYNC,
 };
 use tauri::AppHandle;
 
-use crate::AppState;
+use crate::{AppState, remote::run_cancellable};
 
 #[tauri::command]
 pub async fn fetch_library(
     state: tauri::State<'_, Mutex<AppState>>,
     hard_refresh: Option<bool>,
+    include_hidden: Option<bool>,
+    request_id: Option<String>,
 ) -> Result<Vec<Game>, RemoteAccessError> {
-    offline!(
-        state,
-        fetch_library_logic,
-        fetch_library_logic_offline,
-        state,
-        hard_refresh
+    run_cancellable(
+        request_id,
+        offline!(
+            state,
+            fetch_library_logic,
+            fetch_library_logic_offline,
+            state,
+            hard_refresh,
+            include_hidden
+        ),
     )
     .await
 }
@@ -38,17 +63,18 @@ pub async fn fetch_library(
 pub async fn fetch_library_logic(
     state: tauri::State<'_, Mutex<AppState>>,
     hard_fresh: Option<bool>,
+    include_hidden: Option<bool>,
 ) -> Result<Vec<Game>, RemoteAccessError> {
     let do_hard_refresh = hard_fresh.unwrap_or(false);
     if !do_hard_refresh && let Ok(library) = get_cached_object("library") {
-        return Ok(library);
+        return Ok(arrange_library(library, include_hidden));
     }
 
     let client = DROP_CLIENT_ASYNC.clone();
     let response = generate_url(&["/api/v1/client/user/library"], &[])?;
     let response = client
         .get(response)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .send()
         .await?;
 
@@ -62,13 +88,24 @@ pub async fn fetch_library_logic(
     }
 
     let mut games: Vec<Game> = response.json().await?;
+    clear_manifest_cache();
 
     let mut handle = state.lock();
 
     let mut db_handle = borrow_db_mut_checked();
 
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
     for game in &games {
         handle.games.insert(game.id().clone(), game.clone());
+        db_handle
+            .applications
+            .game_first_seen
+            .entry(game.id().clone())
+            .or_insert(now);
         if !db_handle.applications.game_statuses.contains_key(game.id()) {
             db_handle
                 .applications
@@ -101,11 +138,12 @@ pub async fn fetch_library_logic(
     drop(db_handle);
     cache_object("library", &games)?;
 
-    Ok(games)
+    Ok(arrange_library(games, include_hidden))
 }
 pub async fn fetch_library_logic_offline(
     _state: tauri::State<'_, Mutex<AppState>>,
     _hard_refresh: Option<bool>,
+    include_hidden: Option<bool>,
 ) -> Result<Vec<Game>, RemoteAccessError> {
     let mut games: Vec<Game> = get_cached_object("library")?;
 
@@ -121,13 +159,112 @@ pub async fn fetch_library_logic_offline(
             GameDownloadStatus::Installed { .. } | GameDownloadStatus::SetupRequired { .. }
         )
     });
+    drop(db_handle);
 
-    Ok(games)
+    Ok(arrange_library(games, include_hidden))
+}
+/// Leaves out hidden games, unless `include_hidden`, and moves favorites to
+/// the front, keeping the server's order otherwise. Games that aren't just
+/// on the server are never hidden.
+fn arrange_library(mut games: Vec<Game>, include_hidden: Option<bool>) -> Vec<Game> {
+    let db_handle = borrow_db_checked();
+    if !include_hidden.unwrap_or(false) {
+        games.retain(|game| {
+            !db_handle.applications.hidden_games.contains(game.id())
+                || !matches!(
+                    db_handle.applications.game_statuses.get(game.id()),
+                    None | Some(GameDownloadStatus::Remote {})
+                )
+        });
+    }
+    games.sort_by_key(|game| !db_handle.applications.favorites.contains(game.id()));
+    games
+}
+fn sort_recently_added(mut games: Vec<Game>, limit: usize) -> Vec<Game> {
+    let db_handle = borrow_db_checked();
+    games.sort_by_key(|game| {
+        Reverse(
+            db_handle
+                .applications
+                .game_first_seen
+                .get(game.id())
+                .copied()
+                .unwrap_or_default(),
+        )
+    });
+    games.truncate(limit);
+    games
+}
+pub async fn fetch_recently_added_logic(
+    state: tauri::State<'_, Mutex<AppState>>,
+    limit: usize,
+) -> Result<Vec<Game>, RemoteAccessError> {
+    let games = fetch_library_logic(state, None, None).await?;
+    Ok(sort_recently_added(games, limit))
+}
+pub async fn fetch_recently_added_logic_offline(
+    _state: tauri::State<'_, Mutex<AppState>>,
+    limit: usize,
+) -> Result<Vec<Game>, RemoteAccessError> {
+    let games: Vec<Game> = get_cached_object("library")?;
+    Ok(sort_recently_added(arrange_library(games, None), limit))
+}
+/// The last library fetched, by game id
+fn cached_library(db: &Database) -> HashMap<String, Game> {
+    get_cached_object_db::<Vec<Game>>("library", db)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|game| (game.id().clone(), game))
+        .collect()
+}
+
+/// A game from the cached library, or from its own cache entry if it's no
+/// longer in the library
+fn cached_game(id: &str, library: &HashMap<String, Game>, db: &Database) -> Option<Game> {
+    match library.get(id) {
+        Some(game) => Some(game.clone()),
+        None => get_cached_object_db::<Game>(id, db).ok(),
+    }
+}
+
+/// Games with a recorded session, most recently played first. Built from
+/// the playtime records and the object cache, so it works offline and keeps
+/// games that have since been uninstalled.
+#[tauri::command]
+pub fn fetch_recently_played(limit: usize) -> Vec<RecentlyPlayedGame> {
+    let db_handle = borrow_db_checked();
+    let library = cached_library(&db_handle);
+
+    let mut played = db_handle
+        .applications
+        .game_playtime
+        .iter()
+        .filter(|(_, playtime)| playtime.last_played.is_some())
+        .collect::<Vec<_>>();
+    played.sort_by_key(|(_, playtime)| Reverse(playtime.last_played));
+
+    played
+        .into_iter()
+        .filter_map(|(id, playtime)| {
+            let game = cached_game(id, &library, &db_handle)?;
+            let (status, transient) = GameStatusManager::fetch_state(id, &db_handle);
+            Some(RecentlyPlayedGame {
+                game,
+                status: (
+                    Some(status.unwrap_or(GameDownloadStatus::Remote {})),
+                    transient,
+                ),
+                playtime: playtime.clone(),
+            })
+        })
+        .take(limit)
+        .collect()
 }
 pub async fn fetch_game_logic(
     id: String,
     state: tauri::State<'_, Mutex<AppState>>,
 ) -> Result<FetchGameStruct, RemoteAccessError> {
+    let install_size = spawn_install_size(id.clone()).await;
     let version = {
         let state_handle = state.lock();
 
@@ -148,7 +285,21 @@ pub async fn fetch_game_logic(
         if let Some(game) = game {
             let status = GameStatusManager::fetch_state(&id, &db_lock);
 
-            let data = FetchGameStruct::new(game.clone(), status, version);
+            let note = db_lock.applications.game_notes.get(&id).cloned();
+            let playtime = db_lock.applications.game_playtime.get(&id).cloned();
+            let favorite = db_lock.applications.favorites.contains(&id);
+            let tags = game_tags(&db_lock, &id);
+
+            let data = FetchGameStruct {
+                game: game.clone(),
+                status,
+                version,
+                note,
+                playtime,
+                favorite,
+                install_size,
+                tags,
+            };
 
             cache_object_db(&id, game, &db_lock)?;
 
@@ -162,7 +313,7 @@ pub async fn fetch_game_logic(
     let response = generate_url(&["/api/v1/client/game/", &id], &[])?;
     let response = client
         .get(response)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .send()
         .await?;
 
@@ -194,10 +345,23 @@ pub async fn fetch_game_logic(
         .or_insert(GameDownloadStatus::Remote {});
 
     let status = GameStatusManager::fetch_state(&id, &db_handle);
+    let note = db_handle.applications.game_notes.get(&id).cloned();
+    let playtime = db_handle.applications.game_playtime.get(&id).cloned();
+    let favorite = db_handle.applications.favorites.contains(&id);
+    let tags = game_tags(&db_handle, &id);
 
     drop(db_handle);
 
-    let data = FetchGameStruct::new(game.clone(), status, version);
+    let data = FetchGameStruct {
+        game: game.clone(),
+        status,
+        version,
+        note,
+        playtime,
+        favorite,
+        install_size,
+        tags,
+    };
 
     cache_object(&id, &game)?;
 
@@ -213,7 +377,7 @@ pub async fn fetch_game_version_options_logic(
     let response = generate_url(&["/api/v1/client/game/versions"], &[("id", &game_id)])?;
     let response = client
         .get(response)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .send()
         .await?;
 
@@ -241,6 +405,7 @@ pub async fn fetch_game_logic_offline(
     id: String,
     _state: tauri::State<'_, Mutex<AppState>>,
 ) -> Result<FetchGameStruct, RemoteAccessError> {
+    let install_size = spawn_install_size(id.clone()).await;
     let db_handle = borrow_db_checked();
     let metadata_option = db_handle.applications.installed_game_version.get(&id);
     let version = match metadata_option {
@@ -254,24 +419,56 @@ pub async fn fetch_game_logic_offline(
     };
 
     let status = GameStatusManager::fetch_state(&id, &db_handle);
+    let note = db_handle.applications.game_notes.get(&id).cloned();
+    let playtime = db_handle.applications.game_playtime.get(&id).cloned();
+    let favorite = db_handle.applications.favorites.contains(&id);
+    let tags = game_tags(&db_handle, &id);
     let game = get_cached_object::<Game>(&id)?;
 
     drop(db_handle);
 
-    Ok(FetchGameStruct::new(game, status, version))
+    Ok(FetchGameStruct {
+        game,
+        status,
+        version,
+        note,
+        playtime,
+        favorite,
+        install_size,
+        tags,
+    })
+}
+
+#[tauri::command]
+pub async fn fetch_recently_added(
+    limit: usize,
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<Vec<Game>, RemoteAccessError> {
+    offline!(
+        state,
+        fetch_recently_added_logic,
+        fetch_recently_added_logic_offline,
+        state,
+        limit
+    )
+    .await
 }
 
 #[tauri::command]
 pub async fn fetch_game(
     game_id: String,
     state: tauri::State<'_, Mutex<AppState>>,
+    request_id: Option<String>,
 ) -> Result<FetchGameStruct, RemoteAccessError> {
-    offline!(
-        state,
-        fetch_game_logic,
-        fetch_game_logic_offline,
-        game_id,
-        state
+    run_cancellable(
+        request_id,
+        offline!(
+            state,
+            fetch_game_logic,
+            fetch_game_logic_offline,
+            game_id,
+            state
+        ),
     )
     .await
 }
@@ -301,13 +498,21 @@ pub async fn fetch_game_version_options(
     fetch_game_version_options_logic(game_id, state).await
 }
 
+/// The version a one-click install should use, following the user's
+/// default version policy
 #[tauri::command]
-pub fn update_game_configuration(
+pub async fn default_version(
     game_id: String,
-    options: FrontendGameOptions,
-) -> Result<(), LibraryError> {
-    let mut handle = borrow_db_mut_checked();
-    let installed_version = handle
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<Option<GameVersion>, RemoteAccessError> {
+    let versions = fetch_game_version_options_logic(game_id, state).await?;
+    let policy = borrow_db_checked().settings.default_version_policy;
+    Ok(pick_default_version(&versions, policy).cloned())
+}
+
+/// The installed game id and version name for `game_id`
+fn installed_version(db: &Database, game_id: String) -> Result<(String, String), LibraryError> {
+    let installed_version = db
         .applications
         .installed_game_version
         .get(&game_id)
@@ -318,6 +523,16 @@ pub fn update_game_configuration(
         .version
         .clone()
         .ok_or(LibraryError::VersionNotFound(id.clone()))?;
+    Ok((id, version))
+}
+
+#[tauri::command]
+pub fn update_game_configuration(
+    game_id: String,
+    options: FrontendGameOptions,
+) -> Result<(), LibraryError> {
+    let mut handle = borrow_db_mut_checked();
+    let (id, version) = installed_version(&handle, game_id)?;
 
     let mut existing_configuration = handle
         .applications
@@ -329,7 +544,17 @@ pub fn update_game_configuration(
         .clone();
 
     // Add more options in here
-    existing_configuration.launch_command_template = options.launch_string().clone();
+    if let Some(launch_string) = options.launch_string() {
+        existing_configuration.launch_command_template = launch_string.clone();
+    }
+    existing_configuration.preferred_launcher = options.preferred_launcher();
+    let launch_executable = options.launch_executable();
+    if let Some(executable) = &launch_executable
+        && !is_contained_relative_path(executable)
+    {
+        return Err(LibraryError::InvalidExecutable(executable.clone()));
+    }
+    existing_configuration.executable_override = launch_executable;
 
     // Add no more options past here
 
@@ -342,3 +567,287 @@ pub fn update_game_configuration(
 
     Ok(())
 }
+
+#[tauri::command]
+pub fn fetch_launch_config(game_id: String) -> Result<FrontendLaunchConfig, LibraryError> {
+    let handle = borrow_db_checked();
+    let (id, version) = installed_version(&handle, game_id)?;
+    let game_version = handle
+        .applications
+        .game_versions
+        .get(&id)
+        .and_then(|versions| versions.get(&version))
+        .ok_or(LibraryError::VersionNotFound(id.clone()))?;
+
+    let config = handle
+        .applications
+        .game_launch_configs
+        .get(&id)
+        .cloned()
+        .unwrap_or_default();
+    let raw_template = game_version.launch_command_template.clone();
+
+    Ok(FrontendLaunchConfig {
+        base_command: game_version.launch_command.clone(),
+        base_args: game_version.launch_args.clone(),
+        custom_template: raw_template != config.template(),
+        config,
+        raw_template,
+    })
+}
+
+/// Saves structured launch options and rebuilds the installed version's
+/// launch command template from them, replacing any hand-edited template
+#[tauri::command]
+pub fn update_launch_config(game_id: String, config: LaunchConfig) -> Result<(), LibraryError> {
+    if let Some(key) = config.invalid_env_key() {
+        return Err(LibraryError::InvalidEnvKey(key.to_owned()));
+    }
+    let mut handle = borrow_db_mut_checked();
+    let (id, version) = installed_version(&handle, game_id)?;
+
+    let game_version = handle
+        .applications
+        .game_versions
+        .get_mut(&id)
+        .and_then(|versions| versions.get_mut(&version))
+        .ok_or(LibraryError::VersionNotFound(id.clone()))?;
+    game_version.launch_command_template = config.template();
+
+    if config == LaunchConfig::default() {
+        handle.applications.game_launch_configs.remove(&id);
+    } else {
+        handle.applications.game_launch_configs.insert(id, config);
+    }
+
+    Ok(())
+}
+
+/// Empty notes are removed rather than stored
+#[tauri::command]
+pub fn set_game_note(game_id: String, note: String) {
+    let mut handle = borrow_db_mut_checked();
+    if note.trim().is_empty() {
+        handle.applications.game_notes.remove(&game_id);
+    } else {
+        handle.applications.game_notes.insert(game_id, note);
+    }
+}
+
+#[tauri::command]
+pub fn fetch_game_note(game_id: String) -> Option<String> {
+    borrow_db_checked()
+        .applications
+        .game_notes
+        .get(&game_id)
+        .cloned()
+}
+
+#[tauri::command]
+pub fn fetch_game_notes() -> HashMap<String, String> {
+    borrow_db_checked().applications.game_notes.clone()
+}
+
+/// Returns whether the game is now a favorite
+#[tauri::command]
+pub fn toggle_favorite(game_id: String, app_handle: AppHandle) -> bool {
+    let mut handle = borrow_db_mut_checked();
+    let favorite = !handle.applications.favorites.remove(&game_id);
+    if favorite {
+        handle.applications.favorites.insert(game_id);
+    }
+    drop(handle);
+    push_library_update(&app_handle);
+    favorite
+}
+
+#[tauri::command]
+pub fn fetch_favorites() -> HashSet<String> {
+    borrow_db_checked().applications.favorites.clone()
+}
+
+/// Only games that are just on the server can be hidden, so a download or
+/// install never disappears from the library
+#[tauri::command]
+pub fn set_game_hidden(
+    game_id: String,
+    hidden: bool,
+    app_handle: AppHandle,
+) -> Result<(), LibraryError> {
+    let mut handle = borrow_db_mut_checked();
+    if hidden {
+        let status = GameStatusManager::fetch_state(&game_id, &handle);
+        if !matches!(status, (None | Some(GameDownloadStatus::Remote {}), None)) {
+            return Err(LibraryError::CannotHide(game_id));
+        }
+        handle.applications.hidden_games.insert(game_id);
+    } else {
+        handle.applications.hidden_games.remove(&game_id);
+    }
+    drop(handle);
+    push_library_update(&app_handle);
+    Ok(())
+}
+
+/// Bytes the game takes up on disk, or `None` if it isn't installed
+#[tauri::command]
+pub async fn fetch_game_install_size(game_id: String) -> Option<InstallSize> {
+    spawn_install_size(game_id).await
+}
+
+/// Sizing can walk the whole install, so it's kept off the async runtime
+async fn spawn_install_size(game_id: String) -> Option<InstallSize> {
+    tauri::async_runtime::spawn_blocking(move || game_install_size(&game_id))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Creates a desktop shortcut that launches the game through Drop. Running it
+/// again replaces the existing shortcut.
+#[tauri::command]
+pub async fn create_shortcut(
+    game_id: String,
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<PathBuf, ShortcutError> {
+    // Not with the database borrowed, as launching locks them the other way
+    let known = state.lock().games.get(&game_id).cloned();
+    let game = {
+        let db_handle = borrow_db_checked();
+        if !matches!(
+            db_handle.applications.game_statuses.get(&game_id),
+            Some(GameDownloadStatus::Installed { .. })
+        ) {
+            return Err(ShortcutError::NotInstalled(game_id));
+        }
+        known.or_else(|| cached_game(&game_id, &cached_library(&db_handle), &db_handle))
+    }
+    .ok_or_else(|| ShortcutError::NotInstalled(game_id.clone()))?;
+
+    let icon = save_icon(&game).await;
+    tauri::async_runtime::spawn_blocking(move || shortcuts::create_shortcut(&game, icon.as_deref()))
+        .await
+        .map_err(|e| ShortcutError::Io(std::io::Error::other(e)))?
+}
+
+fn game_tags(db: &Database, game_id: &str) -> Vec<String> {
+    db.applications
+        .game_tags
+        .get(game_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Tags are trimmed, and adding one the game already has does nothing
+#[tauri::command]
+pub fn add_tag(game_id: String, tag: String) -> Vec<String> {
+    let mut handle = borrow_db_mut_checked();
+    let tag = tag.trim();
+    if !tag.is_empty() {
+        let tags = handle
+            .applications
+            .game_tags
+            .entry(game_id.clone())
+            .or_default();
+        if !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+    game_tags(&handle, &game_id)
+}
+
+#[tauri::command]
+pub fn remove_tag(game_id: String, tag: String) -> Vec<String> {
+    let mut handle = borrow_db_mut_checked();
+    if let Some(tags) = handle.applications.game_tags.get_mut(&game_id) {
+        tags.retain(|existing| existing != tag.trim());
+        if tags.is_empty() {
+            handle.applications.game_tags.remove(&game_id);
+        }
+    }
+    game_tags(&handle, &game_id)
+}
+
+/// Every tag in use, sorted
+#[tauri::command]
+pub fn fetch_tags() -> Vec<String> {
+    let handle = borrow_db_checked();
+    let mut tags = handle
+        .applications
+        .game_tags
+        .values()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Games with `tag`, from the cache so it works offline
+#[tauri::command]
+pub fn fetch_games_by_tag(tag: String) -> Vec<Game> {
+    let handle = borrow_db_checked();
+    let library = cached_library(&handle);
+    let mut games = handle
+        .applications
+        .game_tags
+        .iter()
+        .filter(|(_, tags)| tags.contains(&tag))
+        .filter_map(|(id, _)| cached_game(id, &library, &handle))
+        .collect::<Vec<_>>();
+    games.sort_by(|a, b| a.name().cmp(b.name()));
+    games
+}
+
+#[tauri::command]
+pub fn fetch_hidden_games() -> HashSet<String> {
+    borrow_db_checked().applications.hidden_games.clone()
+}
+
+/// Files in the game's install that could be picked to launch instead of the
+/// version's launch command
+#[tauri::command]
+pub fn list_executables(game_id: String) -> Result<Vec<String>, LibraryError> {
+    let handle = borrow_db_checked();
+    let (id, version) = installed_version(&handle, game_id)?;
+    let platform = handle
+        .applications
+        .game_versions
+        .get(&id)
+        .and_then(|versions| versions.get(&version))
+        .ok_or(LibraryError::VersionNotFound(id.clone()))?
+        .platform;
+    let install_dir = match handle.applications.game_statuses.get(&id) {
+        Some(
+            GameDownloadStatus::Installed { install_dir, .. }
+            | GameDownloadStatus::SetupRequired { install_dir, .. },
+        ) => PathBuf::from(install_dir),
+        _ => return Err(LibraryError::MetaNotFound(id)),
+    };
+    drop(handle);
+
+    Ok(list_executables_logic(
+        &install_dir,
+        platform,
+        EXECUTABLE_SCAN_DEPTH,
+    ))
+}
+
+/// Games that have never been played have no playtime and no last played
+#[tauri::command]
+pub fn fetch_game_playtime(game_id: String) -> GamePlaytime {
+    borrow_db_checked()
+        .applications
+        .game_playtime
+        .get(&game_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Checks the install dirs again without a restart, e.g. after plugging an
+/// external drive back in or copying a game into one
+#[tauri::command]
+pub fn rescan_install_dirs(app_handle: AppHandle) -> ScanSummary {
+    rescan_install_dirs_logic(&app_handle)
+}
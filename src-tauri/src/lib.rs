@@ -8,26 +8,39 @@
 #![deny(clippy::all)]
 
 use std::{
-    collections::HashMap, env, fs::File, io::Write, panic::PanicHookInfo, path::Path, str::FromStr,
-    sync::nonpoison::Mutex, time::SystemTime,
+    collections::HashMap,
+    env,
+    fs::File,
+    io::Write,
+    panic::PanicHookInfo,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        nonpoison::Mutex,
+    },
+    time::SystemTime,
 };
 
 use ::client::{app_status::AppStatus, autostart::sync_autostart_on_startup, user::User};
-use ::download_manager::DownloadManagerWrapper;
-use ::games::{library::Game, scan::scan_install_dirs};
+use ::download_manager::{DOWNLOAD_MANAGER, DownloadManagerWrapper};
+use ::games::{
+    downloads::utils::soft_fd_limit,
+    library::Game,
+    scan::{mark_missing_games, scan_install_dirs},
+};
 use ::process::ProcessManagerWrapper;
 use ::remote::{
-    auth::{self, HandshakeRequestBody, HandshakeResponse, generate_authorization_header},
+    auth::{self, HandshakeRequestBody, HandshakeResponse, ReauthRequired},
     cache::clear_cached_object,
     error::RemoteAccessError,
     fetch_object::fetch_object_wrapper,
     offline,
+    requests::{generate_authenticated_headers, generate_extra_headers},
     server_proto::{handle_server_proto_offline_wrapper, handle_server_proto_wrapper},
-    utils::DROP_CLIENT_ASYNC,
+    utils::{DROP_CLIENT_ASYNC, init_clients},
 };
 use database::{
-    DB, GameDownloadStatus, borrow_db_checked, borrow_db_mut_checked, db::DATA_ROOT_DIR,
-    interface::DatabaseImpls,
+    DB, borrow_db_checked, borrow_db_mut_checked, db::DATA_ROOT_DIR, interface::DatabaseImpls,
 };
 use log::{LevelFilter, debug, info, warn};
 use log4rs::{
@@ -50,7 +63,9 @@ use utils::app_emit;
 use crate::client::cleanup_and_exit;
 
 mod client;
+mod cloud_saves;
 mod collections;
+mod deep_link;
 mod download_manager;
 mod downloads;
 mod games;
@@ -59,6 +74,7 @@ mod remote;
 mod settings;
 
 use client::*;
+use cloud_saves::*;
 use collections::*;
 use download_manager::*;
 use downloads::*;
@@ -73,6 +89,65 @@ pub struct AppState {
     status: AppStatus,
     user: Option<User>,
     games: HashMap<String, Game>,
+    safe_mode: bool,
+}
+
+impl AppState {
+    /// All status transitions should go through here, so the frontend gets
+    /// an `app_status_changed` event for each of them
+    pub fn set_status(&mut self, app: &AppHandle, status: AppStatus) {
+        if self.status == status {
+            return;
+        }
+        let old = self.status;
+        self.status = status;
+        app_emit!(
+            app,
+            "app_status_changed",
+            AppStatusChangedEvent { old, new: status }
+        );
+        if status == AppStatus::SignedIn {
+            deep_link::replay_pending_install(app);
+            // Picks up queued downloads that couldn't be rebuilt while offline
+            if !self.safe_mode {
+                tauri::async_runtime::spawn(restore_download_queue());
+            }
+        }
+    }
+
+    /// Applies the result of `auth::setup`, letting the frontend know why if
+    /// the user now has to sign in again
+    pub fn set_auth_status(
+        &mut self,
+        app: &AppHandle,
+        status: AppStatus,
+        reauth: Option<ReauthRequired>,
+    ) {
+        let was_needing_reauth = self.status == AppStatus::SignedInNeedsReauth;
+        self.set_status(app, status);
+        if was_needing_reauth {
+            return;
+        }
+        if let Some(reauth) = reauth {
+            app_emit!(app, "auth/needs_reauth", reauth);
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct AppStatusChangedEvent {
+    old: AppStatus,
+    new: AppStatus,
+}
+
+/// Safe mode skips everything at startup that touches games or downloads on
+/// its own, so a broken install or download can be cleaned up from the UI
+fn is_safe_mode() -> bool {
+    env::args().any(|arg| arg == "--safe-mode")
+        || match env::var("DROP_SAFE_MODE") {
+            Ok(s) => s.to_lowercase() == "true",
+            Err(_) => false,
+        }
 }
 
 async fn setup(handle: AppHandle) -> AppState {
@@ -108,69 +183,77 @@ async fn setup(handle: AppHandle) -> AppState {
 
     let games = HashMap::new();
 
+    let (proxy_url, no_proxy) = {
+        let db_lock = borrow_db_checked();
+        (
+            db_lock.settings.proxy_url.clone(),
+            db_lock.settings.no_proxy.clone(),
+        )
+    };
+    init_clients(proxy_url, &no_proxy);
+
     ProcessManagerWrapper::init(handle.clone());
     DownloadManagerWrapper::init(handle.clone());
 
+    match soft_fd_limit() {
+        Some(limit) => info!("open file limit is {limit}"),
+        None => debug!("no open file limit"),
+    }
+
+    let safe_mode = is_safe_mode();
+    if safe_mode {
+        warn!("starting in safe mode, skipping install dir scans and startup sync");
+    }
+
+    // Older versions saved settings without validating them
+    if borrow_db_checked().settings.validate().is_err() {
+        for setting in borrow_db_mut_checked().settings.clamp() {
+            warn!(
+                "reset invalid setting {}: {}",
+                setting.field, setting.reason
+            );
+        }
+    }
+
     debug!("checking if database is set up");
     let is_set_up = DB.database_is_set_up();
 
-    scan_install_dirs();
+    if !safe_mode {
+        scan_install_dirs();
+    }
 
     if !is_set_up {
         return AppState {
             status: AppStatus::NotConfigured,
             user: None,
             games,
+            safe_mode,
         };
     }
 
     debug!("database is set up");
 
     // TODO: Account for possible failure
-    let (app_status, user) = auth::setup().await;
-
-    let db_handle = borrow_db_checked();
-    let mut missing_games = Vec::new();
-    let statuses = db_handle.applications.game_statuses.clone();
-    drop(db_handle);
-
-    for (game_id, status) in statuses {
-        match status {
-            GameDownloadStatus::Remote {} => {}
-            GameDownloadStatus::PartiallyInstalled { .. } => {}
-            GameDownloadStatus::SetupRequired {
-                version_name: _,
-                install_dir,
-            } => {
-                let install_dir_path = Path::new(&install_dir);
-                if !install_dir_path.exists() {
-                    missing_games.push(game_id);
-                }
-            }
-            GameDownloadStatus::Installed {
-                version_name: _,
-                install_dir,
-            } => {
-                let install_dir_path = Path::new(&install_dir);
-                if !install_dir_path.exists() {
-                    missing_games.push(game_id);
-                }
-            }
-        }
+    let (app_status, user, reauth) = auth::setup().await;
+    if let Some(reauth) = reauth {
+        app_emit!(&handle, "auth/needs_reauth", reauth);
     }
 
-    info!("detected games missing: {missing_games:?}");
-
-    let mut db_handle = borrow_db_mut_checked();
-    for game_id in missing_games {
-        db_handle
-            .applications
-            .game_statuses
-            .entry(game_id)
-            .and_modify(|v| *v = GameDownloadStatus::Remote {});
+    if safe_mode {
+        return AppState {
+            status: app_status,
+            user,
+            games,
+            safe_mode,
+        };
     }
 
-    drop(db_handle);
+    mark_missing_games();
+
+    // Building the agents fetches their manifests, so it can't happen offline
+    if app_status == AppStatus::SignedIn {
+        tauri::async_runtime::spawn(restore_download_queue());
+    }
 
     debug!("finished setup!");
 
@@ -183,6 +266,7 @@ async fn setup(handle: AppHandle) -> AppState {
         status: app_status,
         user,
         games,
+        safe_mode,
     }
 }
 
@@ -217,8 +301,16 @@ pub fn run() {
     #[cfg(desktop)]
     #[allow(unused_variables)]
     {
-        builder = builder.plugin(tauri_plugin_single_instance::init(|_app, argv, _cwd| {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
             // when defining deep link schemes at runtime, you must also check `argv` here
+
+            // Clicking one of our notifications launches Drop again on some
+            // platforms, so bring the running window up instead
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
         }));
     }
 
@@ -229,6 +321,11 @@ pub fn run() {
             fetch_state,
             quit,
             fetch_system_data,
+            export_database,
+            import_database,
+            fetch_crash_reports,
+            open_crash_report,
+            delete_crash_report,
             // User utils
             update_settings,
             fetch_settings,
@@ -236,21 +333,56 @@ pub fn run() {
             auth_initiate,
             auth_initiate_code,
             retry_connect,
+            set_offline_mode,
+            get_offline_mode,
             manual_recieve_handshake,
             sign_out,
+            refresh_web_token,
             // Remote
             use_remote,
+            prefetch_objects,
+            cancel_object_fetch,
             gen_drop_url,
             fetch_drop_object,
+            cancel_request,
+            get_cache_size,
+            clear_cache,
+            list_certificates,
+            add_certificate,
+            remove_certificate,
             // Library
             fetch_library,
+            fetch_recently_added,
+            fetch_recently_played,
             fetch_game,
             add_download_dir,
             delete_download_dir,
             fetch_download_dir_stats,
+            reorder_install_dirs,
+            set_install_dir_quota,
+            fetch_install_dir_quotas,
             fetch_game_status,
             fetch_game_version_options,
+            default_version,
             update_game_configuration,
+            fetch_launch_config,
+            update_launch_config,
+            set_game_note,
+            fetch_game_note,
+            fetch_game_notes,
+            toggle_favorite,
+            fetch_favorites,
+            set_game_hidden,
+            fetch_hidden_games,
+            add_tag,
+            remove_tag,
+            fetch_tags,
+            fetch_games_by_tag,
+            fetch_game_install_size,
+            create_shortcut,
+            fetch_game_playtime,
+            list_executables,
+            rescan_install_dirs,
             // Collections
             fetch_collections,
             fetch_collection,
@@ -261,20 +393,46 @@ pub fn run() {
             // Downloads
             download_game,
             resume_download,
+            verify_game,
             move_download_in_queue,
             pause_downloads,
             resume_downloads,
+            pause_download,
+            fetch_quota_usage,
+            resume_download_item,
             cancel_game,
+            clear_queued_downloads,
+            dump_download_state,
             uninstall_game,
+            fetch_last_download_summary,
+            fetch_download_history,
+            fetch_monthly_download_usage,
+            fetch_update_size,
+            check_download_feasible,
+            fetch_game_components,
+            import_offline_game,
             // Processes
             launch_game,
             kill_game,
+            kill_all_games,
+            fetch_available_launchers,
+            fetch_game_prefix,
+            set_game_prefix,
+            // Cloud saves
+            fetch_cloud_save_config,
+            update_cloud_save_config,
+            resolve_save_conflict,
+            backup_game_saves,
+            restore_game_saves,
+            list_save_snapshots,
+            preview_save_paths,
             toggle_autostart,
             get_autostart_enabled,
             open_process_logs
         ])
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_autostart::init(
             tauri_plugin_autostart::MacosLauncher::LaunchAgent,
             Some(vec!["--minimize"]),
@@ -286,6 +444,7 @@ pub fn run() {
                 let state = setup(handle).await;
                 info!("initialized drop client");
                 app.manage(Mutex::new(state));
+                tauri::async_runtime::spawn(watch_connectivity(app.handle().clone()));
 
                 {
                     use tauri_plugin_deep_link::DeepLinkExt;
@@ -319,11 +478,22 @@ pub fn run() {
                             return;
                         }
                     };
-                    if let Some("handshake") = url.host_str() {
-                        tauri::async_runtime::spawn(recieve_handshake(
-                            handle.clone(),
-                            url.path().to_string(),
-                        ));
+                    match url.host_str() {
+                        Some("handshake") => {
+                            tauri::async_runtime::spawn(recieve_handshake(
+                                handle.clone(),
+                                url.path().to_string(),
+                            ));
+                        }
+                        Some("launch") => {
+                            let handle = handle.clone();
+                            let path = url.path().to_string();
+                            tauri::async_runtime::spawn_blocking(move || {
+                                deep_link::launch(&handle, &path)
+                            });
+                        }
+                        Some("install") => deep_link::install(&handle, url),
+                        host => warn!("unknown deep link {host:?}"),
                     }
                 });
                 let open_menu_item = MenuItem::with_id(app, "open", "Open", true, None::<&str>)
@@ -365,6 +535,9 @@ pub fn run() {
                                     .expect("Failed to get webview")
                                     .show()
                                     .expect("Failed to show window");
+                                if PAUSED_BY_TRAY.swap(false, Ordering::AcqRel) {
+                                    DOWNLOAD_MANAGER.resume_downloads();
+                                }
                             }
                             "quit" => {
                                 cleanup_and_exit(app);
@@ -417,6 +590,7 @@ pub fn run() {
                     state,
                     handle_server_proto_wrapper,
                     handle_server_proto_offline_wrapper,
+                    ctx.app_handle().clone(),
                     request,
                     responder
                 )
@@ -428,6 +602,11 @@ pub fn run() {
                 run_on_tray(|| {
                     window.hide().expect("Failed to close window in tray");
                     api.prevent_close();
+                    let pause_on_tray = borrow_db_checked().settings.pause_on_tray;
+                    // Only resumed on reopen if it was us that paused them
+                    if pause_on_tray && DOWNLOAD_MANAGER.pause_active_downloads() {
+                        PAUSED_BY_TRAY.store(true, Ordering::Release);
+                    }
                 });
             }
         })
@@ -445,6 +624,10 @@ pub fn run() {
     });
 }
 
+/// Set when hiding the window paused downloads, so opening it again only
+/// resumes downloads that the tray paused
+static PAUSED_BY_TRAY: AtomicBool = AtomicBool::new(false);
+
 fn run_on_tray<T: FnOnce()>(f: T) {
     if match std::env::var("NO_TRAY_ICON") {
         Ok(s) => s.to_lowercase() != "true",
@@ -468,11 +651,11 @@ pub async fn recieve_handshake(app: AppHandle, path: String) {
 
     let app_state = app.state::<Mutex<AppState>>();
 
-    let (app_status, user) = auth::setup().await;
+    let (app_status, user, reauth) = auth::setup().await;
 
     let mut state_lock = app_state.lock();
 
-    state_lock.status = app_status;
+    state_lock.set_auth_status(&app, app_status, reauth);
     state_lock.user = user;
 
     let _ = clear_cached_object("collections");
@@ -508,7 +691,12 @@ async fn recieve_handshake_logic(app: &AppHandle, path: String) -> Result<(), Re
 
     let endpoint = base_url.join("/api/v1/client/auth/handshake")?;
     let client = DROP_CLIENT_ASYNC.clone();
-    let response = client.post(endpoint).json(&body).send().await?;
+    let response = client
+        .post(endpoint)
+        .headers(generate_extra_headers())
+        .json(&body)
+        .send()
+        .await?;
     debug!("handshake responsded with {}", response.status().as_u16());
     if !response.status().is_success() {
         return Err(RemoteAccessError::InvalidResponse(response.json().await?));
@@ -521,10 +709,9 @@ async fn recieve_handshake_logic(app: &AppHandle, path: String) -> Result<(), Re
     }
 
     let web_token = {
-        let header = generate_authorization_header();
         let token = client
             .post(base_url.join("/api/v1/client/user/webtoken")?)
-            .header("Authorization", header)
+            .headers(generate_authenticated_headers()?)
             .send()
             .await?;
 
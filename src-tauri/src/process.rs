@@ -1,6 +1,16 @@
 use std::sync::nonpoison::Mutex;
 
-use process::{PROCESS_MANAGER, error::ProcessError};
+use std::path::PathBuf;
+
+use database::{
+    LauncherKind, borrow_db_checked, borrow_db_mut_checked,
+    prefix::{game_prefix_dir, games_sharing_prefix},
+};
+use log::warn;
+use process::{
+    PROCESS_MANAGER, error::ProcessError, logs::log_sessions,
+    process_manager::restore_saves_before_launch,
+};
 use tauri::AppHandle;
 use tauri_plugin_opener::OpenerExt;
 
@@ -10,7 +20,14 @@ use crate::AppState;
 pub fn launch_game(
     id: String,
     state: tauri::State<'_, Mutex<AppState>>,
+    app_handle: AppHandle,
 ) -> Result<(), ProcessError> {
+    // Not while holding the app state or the process manager, so a slow
+    // restore doesn't hold up everything else that needs them
+    let running = PROCESS_MANAGER.lock().is_running(&id);
+    if !running {
+        restore_saves_before_launch(&app_handle, &id);
+    }
     let state_lock = state.lock();
     let mut process_manager_lock = PROCESS_MANAGER.lock();
     //let meta = DownloadableMetadata {
@@ -38,13 +55,86 @@ pub fn kill_game(game_id: String) -> Result<(), ProcessError> {
         .map_err(ProcessError::IOError)
 }
 
+/// Kills every running game and waits for each one to be cleaned up
+#[tauri::command]
+pub fn kill_all_games() {
+    // Has to be unlocked before joining, as the wait threads lock it too
+    let wait_threads = PROCESS_MANAGER.lock().kill_all();
+    for wait_thread in wait_threads {
+        if wait_thread.join().is_err() {
+            warn!("a game's wait thread panicked while being killed");
+        }
+    }
+}
+
 #[tauri::command]
 pub fn open_process_logs(game_id: String, app_handle: AppHandle) -> Result<(), ProcessError> {
     let process_manager_lock = PROCESS_MANAGER.lock();
 
     let dir = process_manager_lock.get_log_dir(game_id);
+    // Straight to the log if there's nothing else to pick from
+    let sessions = log_sessions(&dir);
+    let path = match sessions.as_slice() {
+        [session] => session.output_log().cloned().unwrap_or(dir),
+        _ => dir,
+    };
     app_handle
         .opener()
-        .open_path(dir.display().to_string(), None::<&str>)
+        .open_path(path.display().to_string(), None::<&str>)
         .map_err(ProcessError::OpenerError)
 }
+
+/// The launchers that can run the installed version of a game, for the user
+/// to pick from
+#[tauri::command]
+pub fn fetch_available_launchers(game_id: String) -> Result<Vec<LauncherKind>, ProcessError> {
+    let platform = {
+        let db_lock = borrow_db_checked();
+        let meta = db_lock
+            .applications
+            .installed_game_version
+            .get(&game_id)
+            .ok_or(ProcessError::NotInstalled)?;
+        db_lock
+            .applications
+            .game_versions
+            .get(&game_id)
+            .and_then(|versions| versions.get(meta.version.as_ref()?))
+            .ok_or(ProcessError::InvalidVersion)?
+            .platform
+    };
+
+    // The process manager has to be locked before the database, as when
+    // launching
+    let process_manager_lock = PROCESS_MANAGER.lock();
+    Ok(process_manager_lock.available_launchers(&borrow_db_checked(), &platform))
+}
+
+#[tauri::command]
+pub fn fetch_game_prefix(game_id: String) -> PathBuf {
+    game_prefix_dir(&borrow_db_checked(), &game_id)
+}
+
+/// Runs the game in `prefix` instead of its own, or goes back to its own with
+/// `None`. Returns the other games that now share the prefix, so the user can
+/// be warned.
+#[tauri::command]
+pub fn set_game_prefix(game_id: String, prefix: Option<PathBuf>) -> Vec<String> {
+    let mut db_lock = borrow_db_mut_checked();
+    match prefix {
+        Some(prefix) => db_lock
+            .applications
+            .game_prefix_overrides
+            .insert(game_id.clone(), prefix),
+        None => db_lock.applications.game_prefix_overrides.remove(&game_id),
+    };
+
+    let sharing = games_sharing_prefix(&db_lock, &game_id);
+    if !sharing.is_empty() {
+        warn!(
+            "{game_id} now shares its prefix with {}",
+            sharing.join(", ")
+        );
+    }
+    sharing
+}
@@ -1,20 +1,44 @@
-use std::{sync::nonpoison::Mutex, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{
+        LazyLock,
+        atomic::{AtomicU64, Ordering},
+        nonpoison::Mutex,
+    },
+    time::Duration,
+};
 
 use client::app_status::AppStatus;
 use database::{borrow_db_checked, borrow_db_mut_checked};
 use futures_lite::StreamExt;
-use log::{debug, warn};
+use log::{debug, info, warn};
 use remote::{
-    auth::{auth_initiate_logic, generate_authorization_header},
-    cache::{cache_object, get_cached_object},
+    auth::{auth_initiate_logic, refresh_web_token as refresh_web_token_logic},
+    cache::{
+        cache_object, cache_size, clear_cache as clear_cache_logic, clear_structural_cache,
+        get_cached_object, pinned_keys,
+    },
+    certificates::{
+        CertificateInfo, CertificateList, add_certificate as add_certificate_logic,
+        certificates_dir, list_certificates as list_certificates_logic,
+        remove_certificate as remove_certificate_logic,
+    },
     error::RemoteAccessError,
-    requests::generate_url,
+    fetch_object::{
+        cancel_object_fetch as cancel_object_fetch_logic,
+        prefetch_objects as prefetch_objects_logic,
+    },
+    requests::{generate_authenticated_headers, generate_extra_headers, generate_url},
     setup,
-    utils::{DROP_CLIENT_ASYNC, DROP_CLIENT_WS_CLIENT, DropHealthcheck},
+    utils::{
+        DROP_CLIENT_ASYNC, DROP_CLIENT_WS_CLIENT, DropHealthcheck, MAX_SERVER_VERSION,
+        MIN_SERVER_VERSION, ServerVersion, server_reachable,
+    },
 };
 use reqwest_websocket::{Message, RequestBuilderExt};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
 use url::Url;
 use utils::{app_emit, webbrowser_open::webbrowser_open};
 
@@ -24,6 +48,7 @@ use crate::{AppState, recieve_handshake};
 pub async fn use_remote(
     url: String,
     state: tauri::State<'_, Mutex<AppState>>,
+    app: AppHandle,
 ) -> Result<(), RemoteAccessError> {
     debug!("connecting to url {url}");
     let base_url = Url::parse(&url)?;
@@ -33,6 +58,7 @@ pub async fn use_remote(
     let client = DROP_CLIENT_ASYNC.clone();
     let response = client
         .get(test_endpoint.to_string())
+        .headers(generate_extra_headers())
         .timeout(Duration::from_secs(3))
         .send()
         .await?;
@@ -44,16 +70,45 @@ pub async fn use_remote(
         return Err(RemoteAccessError::InvalidEndpoint);
     }
 
+    match result.version().map(|v| (v, ServerVersion::parse(v))) {
+        Some((_, Some(version))) if !version.is_supported() => {
+            return Err(RemoteAccessError::IncompatibleServer(
+                format!(">={MIN_SERVER_VERSION}, <{MAX_SERVER_VERSION}"),
+                version.to_string(),
+            ));
+        }
+        Some((version, None)) => warn!("couldn't parse server version {version}"),
+        Some(_) => {}
+        None => debug!("server didn't report its version"),
+    }
+
     let mut app_state = state.lock();
-    app_state.status = AppStatus::SignedOut;
+    app_state.set_status(&app, AppStatus::SignedOut);
     drop(app_state);
 
     let mut db_state = borrow_db_mut_checked();
     db_state.base_url = base_url.to_string();
+    db_state.server_version = result.version().cloned();
 
     Ok(())
 }
 
+/// Warms the object cache with a batch of objects, e.g. the library's
+/// artwork, before the frontend requests them one by one. Returns whether
+/// each id could be fetched, so failures can get a placeholder.
+#[tauri::command]
+pub async fn prefetch_objects(ids: Vec<String>) -> HashMap<String, bool> {
+    prefetch_objects_logic(ids).await
+}
+
+/// Stops loading an object a page isn't waiting on any more. Only the
+/// requests `owner` made are cancelled. Returns whether any were still
+/// loading.
+#[tauri::command]
+pub fn cancel_object_fetch(id: String, owner: String) -> bool {
+    cancel_object_fetch_logic(&id, &owner)
+}
+
 #[tauri::command]
 pub fn gen_drop_url(path: String) -> Result<String, RemoteAccessError> {
     let base_url = {
@@ -73,7 +128,7 @@ pub fn fetch_drop_object(path: String) -> Result<Vec<u8>, RemoteAccessError> {
     let req = generate_url(&[&path], &[])?;
     let req = remote::utils::DROP_CLIENT_SYNC
         .get(req)
-        .header("Authorization", generate_authorization_header())
+        .headers(generate_authenticated_headers()?)
         .send();
 
     match req {
@@ -96,12 +151,19 @@ pub fn sign_out(app: AppHandle) {
         handle.auth = None;
     }
 
+    // The library and user belong to this account, so the next one to sign
+    // in doesn't see them
+    if let Err(e) = clear_structural_cache(&borrow_db_checked().cache_dir) {
+        warn!("could not clear cached library and user: {e}");
+    }
+
     // Update app state
     {
         let state = app.state::<Mutex<AppState>>();
         let mut app_state_handle = state.lock();
-        app_state_handle.status = AppStatus::SignedOut;
+        app_state_handle.set_status(&app, AppStatus::SignedOut);
         app_state_handle.user = None;
+        app_state_handle.games.clear();
     }
 
     // Emit event for frontend
@@ -109,17 +171,128 @@ pub fn sign_out(app: AppHandle) {
 }
 
 #[tauri::command]
-pub async fn retry_connect(state: tauri::State<'_, Mutex<AppState>>) -> Result<(), ()> {
-    let (app_status, user) = setup().await;
+pub async fn retry_connect(
+    state: tauri::State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<(), ()> {
+    let (app_status, user, reauth) = setup().await;
 
     let mut guard = state.lock();
-    guard.status = app_status;
+    guard.set_auth_status(&app, app_status, reauth);
     guard.user = user;
     drop(guard);
 
     Ok(())
 }
 
+/// Switches offline mode on or off without a restart. Going back online
+/// reconnects, the same as `retry_connect`.
+#[tauri::command]
+pub async fn set_offline_mode(
+    enabled: bool,
+    state: tauri::State<'_, Mutex<AppState>>,
+    app: AppHandle,
+) -> Result<(), ()> {
+    borrow_db_mut_checked().settings.force_offline = enabled;
+    if !enabled {
+        return retry_connect(state, app).await;
+    }
+
+    let mut guard = state.lock();
+    // Signed out or needing reauth, there's no session to take offline
+    if guard.status == AppStatus::SignedIn {
+        guard.set_status(&app, AppStatus::Offline);
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+struct ConnectivityEvent {
+    online: bool,
+}
+
+/// Pings the server every `healthcheck_interval_secs`, going offline when it
+/// stops answering and reconnecting when it's back, so `offline!` picks the
+/// cached path without waiting on requests to time out. Doesn't ping while
+/// offline mode is forced on.
+pub async fn watch_connectivity(app: AppHandle) {
+    loop {
+        let interval = borrow_db_checked().settings.healthcheck_interval_secs;
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        if borrow_db_checked().settings.force_offline {
+            continue;
+        }
+        let status = app.state::<Mutex<AppState>>().lock().status;
+        if !matches!(status, AppStatus::SignedIn | AppStatus::Offline) {
+            continue;
+        }
+
+        let online = server_reachable().await;
+        match (status, online) {
+            (AppStatus::SignedIn, false) => {
+                let state = app.state::<Mutex<AppState>>();
+                let mut guard = state.lock();
+                // Could have signed out while the ping was in flight
+                if guard.status != AppStatus::SignedIn {
+                    continue;
+                }
+                warn!("lost connection to the server, going offline");
+                guard.set_status(&app, AppStatus::Offline);
+            }
+            (AppStatus::Offline, true) => {
+                info!("server is reachable again, reconnecting");
+                // Offline from startup may not have a user yet, so this goes
+                // through auth again rather than just flipping the status
+                let _ = retry_connect(app.state(), app.clone()).await;
+                let reconnected =
+                    app.state::<Mutex<AppState>>().lock().status != AppStatus::Offline;
+                if !reconnected {
+                    continue;
+                }
+            }
+            _ => continue,
+        }
+        app_emit!(&app, "connectivity", ConnectivityEvent { online });
+    }
+}
+
+#[tauri::command]
+pub fn get_offline_mode() -> bool {
+    borrow_db_checked().settings.force_offline
+}
+
+/// Size of the object cache on disk, in bytes
+#[tauri::command]
+pub fn get_cache_size() -> Result<u64, RemoteAccessError> {
+    cache_size(&borrow_db_checked().cache_dir).map_err(RemoteAccessError::Cache)
+}
+
+/// Empties the object cache, apart from what offline mode needs. Returns how
+/// many bytes were freed.
+#[tauri::command]
+pub fn clear_cache() -> Result<u64, RemoteAccessError> {
+    let db_lock = borrow_db_checked();
+    clear_cache_logic(&db_lock.cache_dir, &pinned_keys(&db_lock)).map_err(RemoteAccessError::Cache)
+}
+
+#[tauri::command]
+pub fn list_certificates() -> Result<CertificateList, RemoteAccessError> {
+    list_certificates_logic(&certificates_dir())
+}
+
+/// Trusts a PEM bundle, e.g. a self-signed server certificate. Takes effect
+/// after a restart, see `CertificateList::restart_required`.
+#[tauri::command]
+pub fn add_certificate(pem: Vec<u8>) -> Result<CertificateInfo, RemoteAccessError> {
+    add_certificate_logic(&certificates_dir(), &pem)
+}
+
+#[tauri::command]
+pub fn remove_certificate(name: String) -> Result<(), RemoteAccessError> {
+    remove_certificate_logic(&certificates_dir(), &name)
+}
+
 #[tauri::command]
 pub fn auth_initiate() -> Result<(), RemoteAccessError> {
     let base_url = {
@@ -135,6 +308,12 @@ pub fn auth_initiate() -> Result<(), RemoteAccessError> {
     Ok(())
 }
 
+#[tauri::command]
+pub fn refresh_web_token() -> Result<(), RemoteAccessError> {
+    refresh_web_token_logic()?;
+    Ok(())
+}
+
 #[derive(Deserialize)]
 struct CodeWebsocketResponse {
     #[serde(rename = "type")]
@@ -159,6 +338,7 @@ pub fn auth_initiate_code(app: AppHandle) -> Result<String, RemoteAccessError> {
             let ws_url = base_url.join("/api/v1/client/auth/code/ws")?;
             let response = DROP_CLIENT_WS_CLIENT
                 .get(ws_url)
+                .headers(generate_extra_headers())
                 .header("Authorization", header_code)
                 .upgrade()
                 .send()
@@ -199,3 +379,57 @@ pub fn auth_initiate_code(app: AppHandle) -> Result<String, RemoteAccessError> {
 pub async fn manual_recieve_handshake(app: AppHandle, token: String) {
     recieve_handshake(app, format!("handshake/{token}")).await;
 }
+
+static NEXT_REQUEST_GENERATION: AtomicU64 = AtomicU64::new(0);
+static IN_FLIGHT_REQUESTS: LazyLock<Mutex<HashMap<String, (u64, CancellationToken)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Runs `request`, letting the frontend abort it with `cancel_request` using
+/// the same id. Dropping the request future also drops its connection.
+///
+/// Starting a request with an id that's still in flight cancels the older one.
+pub async fn run_cancellable<T>(
+    request_id: Option<String>,
+    request: impl Future<Output = Result<T, RemoteAccessError>>,
+) -> Result<T, RemoteAccessError> {
+    let Some(request_id) = request_id else {
+        return request.await;
+    };
+
+    let generation = NEXT_REQUEST_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let token = CancellationToken::new();
+    let previous = IN_FLIGHT_REQUESTS
+        .lock()
+        .insert(request_id.clone(), (generation, token.clone()));
+    if let Some((_, previous)) = previous {
+        previous.cancel();
+    }
+
+    let result = token.run_until_cancelled(request).await;
+
+    let mut in_flight = IN_FLIGHT_REQUESTS.lock();
+    if in_flight
+        .get(&request_id)
+        .is_some_and(|(current, _)| *current == generation)
+    {
+        in_flight.remove(&request_id);
+    }
+    drop(in_flight);
+
+    result.unwrap_or_else(|| {
+        debug!("cancelled request {request_id}");
+        Err(RemoteAccessError::Cancelled)
+    })
+}
+
+/// Returns whether a request with that id was still running
+#[tauri::command]
+pub fn cancel_request(request_id: String) -> bool {
+    match IN_FLIGHT_REQUESTS.lock().remove(&request_id) {
+        Some((_, token)) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
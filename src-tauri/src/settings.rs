@@ -1,16 +1,32 @@
 use std::{
-    fs::create_dir_all,
-    io::{Error, ErrorKind},
+    collections::HashMap,
+    fs::{File, create_dir_all, remove_file},
+    io::{self, Error, ErrorKind},
     path::{Path, PathBuf},
+    sync::nonpoison::Mutex,
 };
 
 use database::{
-    Settings, borrow_db_checked, borrow_db_mut_checked, db::DATA_ROOT_DIR, debug::SystemData,
+    GameDownloadStatus, Settings, borrow_db_checked, borrow_db_mut_checked,
+    db::DATA_ROOT_DIR,
+    debug::SystemData,
+    interface::{
+        export_database as export_database_logic, import_database as import_database_logic,
+    },
+    settings::SettingsError,
 };
-use download_manager::error::DownloadManagerError;
-use games::scan::scan_install_dirs;
-use log::error;
+use download_manager::{DOWNLOAD_MANAGER, error::DownloadManagerError};
+use games::{
+    library::push_library_update,
+    scan::{mark_missing_games, scan_install_dirs},
+};
+use log::{error, info};
+use process::PROCESS_MANAGER;
+use serde::Serialize;
 use serde_json::Value;
+use tauri::AppHandle;
+
+use crate::AppState;
 
 // Will, in future, return disk/remaining size
 // Just returns the directories that have been set up
@@ -21,13 +37,20 @@ pub fn fetch_download_dir_stats() -> Vec<PathBuf> {
 }
 
 #[tauri::command]
-pub fn delete_download_dir(index: usize) {
+pub fn delete_download_dir(index: usize) -> Result<(), DownloadManagerError<()>> {
     let mut lock = borrow_db_mut_checked();
-    lock.applications.install_dirs.remove(index);
+    if index >= lock.applications.install_dirs.len() {
+        return Err(Error::new(ErrorKind::NotFound, "No install directory at that index").into());
+    }
+    let removed = lock.applications.install_dirs.remove(index);
+    lock.applications.install_dir_quotas.remove(&removed);
+    Ok(())
 }
 
+/// Adds a new, empty install directory and returns its index, so the install
+/// flow can pick it straight away
 #[tauri::command]
-pub fn add_download_dir(new_dir: PathBuf) -> Result<(), DownloadManagerError<()>> {
+pub fn add_download_dir(new_dir: PathBuf) -> Result<usize, DownloadManagerError<()>> {
     // Check the new directory is all good
     let new_dir_path = Path::new(&new_dir);
     if new_dir_path.exists() {
@@ -39,50 +62,148 @@ pub fn add_download_dir(new_dir: PathBuf) -> Result<(), DownloadManagerError<()>
             )
             .into());
         }
-    } else {
-        create_dir_all(new_dir_path)?;
     }
 
-    // Add it to the dictionary
-    let mut lock = borrow_db_mut_checked();
-    if lock.applications.install_dirs.contains(&new_dir) {
+    if borrow_db_checked()
+        .applications
+        .install_dirs
+        .contains(&new_dir)
+    {
         return Err(Error::new(
             ErrorKind::AlreadyExists,
             "Selected directory already exists in database",
         )
         .into());
     }
-    lock.applications.install_dirs.push(new_dir);
-    drop(lock);
+
+    Ok(register_download_dir(new_dir)?)
+}
+
+/// Returns the index of `new_dir` in the install dirs, adding it first if it
+/// isn't there yet. New directories have to be absolute, writable and outside
+/// of any game's install.
+pub fn register_download_dir(new_dir: PathBuf) -> io::Result<usize> {
+    if let Some(index) = borrow_db_checked()
+        .applications
+        .install_dirs
+        .iter()
+        .position(|dir| *dir == new_dir)
+    {
+        return Ok(index);
+    }
+
+    if !new_dir.is_absolute() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Install directory must be an absolute path",
+        ));
+    }
+
+    let inside_game = borrow_db_checked()
+        .applications
+        .game_statuses
+        .values()
+        .filter_map(|status| match status {
+            GameDownloadStatus::Installed { install_dir, .. }
+            | GameDownloadStatus::SetupRequired { install_dir, .. }
+            | GameDownloadStatus::PartiallyInstalled { install_dir, .. } => Some(install_dir),
+            GameDownloadStatus::Remote {} => None,
+        })
+        .any(|install_dir| new_dir.starts_with(install_dir));
+    if inside_game {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Install directory can't be inside a game's install",
+        ));
+    }
+
+    create_dir_all(&new_dir)?;
+    // Writing a file is the only reliable way to tell across platforms
+    let probe = new_dir.join(".drop-write-test");
+    File::create(&probe)
+        .map_err(|e| Error::new(e.kind(), format!("Install directory isn't writable: {e}")))?;
+    remove_file(&probe)?;
+
+    let index = {
+        let mut lock = borrow_db_mut_checked();
+        lock.applications.install_dirs.push(new_dir);
+        lock.applications.install_dirs.len() - 1
+    };
 
     scan_install_dirs();
 
+    Ok(index)
+}
+
+/// Install dirs are in priority order, which is used when a download doesn't
+/// pick a directory itself
+#[tauri::command]
+pub fn reorder_install_dirs(order: Vec<PathBuf>) -> Result<(), DownloadManagerError<()>> {
+    let mut lock = borrow_db_mut_checked();
+
+    let mut existing = lock.applications.install_dirs.clone();
+    let mut requested = order.clone();
+    existing.sort();
+    requested.sort();
+    if existing != requested {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "New order must contain each existing directory exactly once",
+        )
+        .into());
+    }
+
+    lock.applications.install_dirs = order;
+
     Ok(())
 }
 
+/// Sets a soft limit on how many bytes of games Drop may install into `dir`,
+/// or clears it with `None`
 #[tauri::command]
-pub fn update_settings(new_settings: Value) {
+pub fn set_install_dir_quota(
+    dir: PathBuf,
+    quota: Option<u64>,
+) -> Result<(), DownloadManagerError<()>> {
+    let mut lock = borrow_db_mut_checked();
+    if !lock.applications.install_dirs.contains(&dir) {
+        return Err(
+            Error::new(ErrorKind::NotFound, "Directory is not an install directory").into(),
+        );
+    }
+
+    match quota {
+        Some(quota) => lock.applications.install_dir_quotas.insert(dir, quota),
+        None => lock.applications.install_dir_quotas.remove(&dir),
+    };
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn fetch_install_dir_quotas() -> HashMap<PathBuf, u64> {
+    borrow_db_checked().applications.install_dir_quotas.clone()
+}
+
+#[tauri::command]
+pub fn update_settings(new_settings: Value) -> Result<(), SettingsError> {
     let mut db_lock = borrow_db_mut_checked();
     let mut current_settings =
         serde_json::to_value(db_lock.settings.clone()).expect("Failed to parse existing settings");
-    let values = match new_settings.as_object() {
-        Some(values) => values,
-        None => {
-            error!("Could not parse settings values into object");
-            return;
-        }
-    };
+    let values = new_settings.as_object().ok_or_else(|| {
+        SettingsError::Unparseable("settings values must be an object".to_string())
+    })?;
     for (key, value) in values {
         current_settings[key] = value.clone();
     }
-    let new_settings: Settings = match serde_json::from_value(current_settings) {
-        Ok(settings) => settings,
-        Err(e) => {
-            error!("Could not parse settings with error {}", e);
-            return;
-        }
-    };
+    let new_settings: Settings = serde_json::from_value(current_settings)
+        .map_err(|e| SettingsError::Unparseable(e.to_string()))?;
+    if let Err(e) = new_settings.validate() {
+        error!("{e}");
+        return Err(e);
+    }
     db_lock.settings = new_settings;
+    Ok(())
 }
 #[tauri::command]
 pub fn fetch_settings() -> Settings {
@@ -98,3 +219,60 @@ pub fn fetch_system_data() -> SystemData {
         std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()),
     )
 }
+
+#[tauri::command]
+pub fn export_database(path: PathBuf) -> Result<(), DownloadManagerError<()>> {
+    export_database_logic(&path)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedDatabase {
+    backup_path: PathBuf,
+    missing_games: Vec<String>,
+}
+
+/// Swaps in a database exported from another install. Games whose install
+/// dir doesn't exist here are marked as not installed, and their ids are
+/// returned so the user knows what to reinstall or relocate, along with
+/// where the old database was backed up to.
+#[tauri::command]
+pub fn import_database(
+    path: PathBuf,
+    state: tauri::State<'_, Mutex<AppState>>,
+    app_handle: AppHandle,
+) -> Result<ImportedDatabase, DownloadManagerError<()>> {
+    if !DOWNLOAD_MANAGER.read_queue().is_empty() {
+        return Err(Error::new(
+            ErrorKind::ResourceBusy,
+            "Finish or cancel your downloads before importing a database",
+        )
+        .into());
+    }
+    // Held until the import is done, so nothing launches halfway through
+    let process_manager_lock = PROCESS_MANAGER.lock();
+    if process_manager_lock.any_running() {
+        return Err(Error::new(
+            ErrorKind::ResourceBusy,
+            "Close your running games before importing a database",
+        )
+        .into());
+    }
+
+    let backup_path = import_database_logic(&path)?;
+    drop(process_manager_lock);
+    info!(
+        "previous database kept at {}, clearing loaded games",
+        backup_path.display()
+    );
+    state.lock().games.clear();
+
+    let missing_games = mark_missing_games();
+    scan_install_dirs();
+    push_library_update(&app_handle);
+    Ok(ImportedDatabase {
+        backup_path,
+        missing_games,
+    })
+}
@@ -2,3 +2,6 @@ mod app_emit;
 mod download_manager_send;
 mod lock;
 pub mod webbrowser_open;
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
@@ -0,0 +1,10 @@
+use std::{fs, path::PathBuf};
+
+/// An empty directory for a test to work in. `name` has to be unique across
+/// every crate's tests, since they share the temp directory.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("drop-test-{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}